@@ -0,0 +1,345 @@
+use std::io;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::{Path, PathBuf};
+
+/// the game's own sheets and data files, compiled in for targets without a filesystem, and
+/// with the embed feature for binaries run away from the repo.
+#[cfg(any(target_arch = "wasm32", feature = "embed"))]
+const EMBEDDED: &[(&str, &[u8])] = &[
+    (
+        "res/dialogue.json",
+        include_bytes!("../../res/dialogue.json"),
+    ),
+    ("res/prices.json", include_bytes!("../../res/prices.json")),
+    (
+        "res/sheets/character-00.json",
+        include_bytes!("../../res/sheets/character-00.json"),
+    ),
+    (
+        "res/sheets/character-00.png",
+        include_bytes!("../../res/sheets/character-00.png"),
+    ),
+    (
+        "res/sheets/character-01.json",
+        include_bytes!("../../res/sheets/character-01.json"),
+    ),
+    (
+        "res/sheets/character-01.png",
+        include_bytes!("../../res/sheets/character-01.png"),
+    ),
+    (
+        "res/sheets/tile-dirt.json",
+        include_bytes!("../../res/sheets/tile-dirt.json"),
+    ),
+    (
+        "res/sheets/tile-dirt.png",
+        include_bytes!("../../res/sheets/tile-dirt.png"),
+    ),
+    (
+        "res/sheets/grass.json",
+        include_bytes!("../../res/sheets/grass.json"),
+    ),
+    (
+        "res/sheets/grass.png",
+        include_bytes!("../../res/sheets/grass.png"),
+    ),
+    (
+        "res/sheets/tool-shovel.json",
+        include_bytes!("../../res/sheets/tool-shovel.json"),
+    ),
+    (
+        "res/sheets/tool-shovel.png",
+        include_bytes!("../../res/sheets/tool-shovel.png"),
+    ),
+    (
+        "res/sheets/tool-watercan.json",
+        include_bytes!("../../res/sheets/tool-watercan.json"),
+    ),
+    (
+        "res/sheets/tool-watercan.png",
+        include_bytes!("../../res/sheets/tool-watercan.png"),
+    ),
+    (
+        "res/sheets/tool-packet.json",
+        include_bytes!("../../res/sheets/tool-packet.json"),
+    ),
+    (
+        "res/sheets/tool-packet.png",
+        include_bytes!("../../res/sheets/tool-packet.png"),
+    ),
+    (
+        "res/sheets/tool-packet2.json",
+        include_bytes!("../../res/sheets/tool-packet2.json"),
+    ),
+    (
+        "res/sheets/tool-packet2.png",
+        include_bytes!("../../res/sheets/tool-packet2.png"),
+    ),
+    (
+        "res/sheets/crop-empty.json",
+        include_bytes!("../../res/sheets/crop-empty.json"),
+    ),
+    (
+        "res/sheets/crop-empty.png",
+        include_bytes!("../../res/sheets/crop-empty.png"),
+    ),
+    (
+        "res/sheets/crop-leaf.json",
+        include_bytes!("../../res/sheets/crop-leaf.json"),
+    ),
+    (
+        "res/sheets/crop-leaf.png",
+        include_bytes!("../../res/sheets/crop-leaf.png"),
+    ),
+    (
+        "res/sheets/crop-flower.json",
+        include_bytes!("../../res/sheets/crop-flower.json"),
+    ),
+    (
+        "res/sheets/crop-flower.png",
+        include_bytes!("../../res/sheets/crop-flower.png"),
+    ),
+    (
+        "res/sheets/cryopod.json",
+        include_bytes!("../../res/sheets/cryopod.json"),
+    ),
+    (
+        "res/sheets/cryopod.png",
+        include_bytes!("../../res/sheets/cryopod.png"),
+    ),
+    (
+        "res/sheets/terminal.json",
+        include_bytes!("../../res/sheets/terminal.json"),
+    ),
+    (
+        "res/sheets/terminal.png",
+        include_bytes!("../../res/sheets/terminal.png"),
+    ),
+    (
+        "res/sheets/compost-bin.json",
+        include_bytes!("../../res/sheets/compost-bin.json"),
+    ),
+    (
+        "res/sheets/compost-bin.png",
+        include_bytes!("../../res/sheets/compost-bin.png"),
+    ),
+    (
+        "res/sheets/dome-wall.json",
+        include_bytes!("../../res/sheets/dome-wall.json"),
+    ),
+    (
+        "res/sheets/dome-wall.png",
+        include_bytes!("../../res/sheets/dome-wall.png"),
+    ),
+    (
+        "res/sheets/solar-panel.json",
+        include_bytes!("../../res/sheets/solar-panel.json"),
+    ),
+    (
+        "res/sheets/solar-panel.png",
+        include_bytes!("../../res/sheets/solar-panel.png"),
+    ),
+    (
+        "res/sheets/sprinkler.json",
+        include_bytes!("../../res/sheets/sprinkler.json"),
+    ),
+    (
+        "res/sheets/sprinkler.png",
+        include_bytes!("../../res/sheets/sprinkler.png"),
+    ),
+    (
+        "res/sheets/robot.json",
+        include_bytes!("../../res/sheets/robot.json"),
+    ),
+    (
+        "res/sheets/robot.png",
+        include_bytes!("../../res/sheets/robot.png"),
+    ),
+    (
+        "res/sheets/teleporter.json",
+        include_bytes!("../../res/sheets/teleporter.json"),
+    ),
+    (
+        "res/sheets/teleporter.png",
+        include_bytes!("../../res/sheets/teleporter.png"),
+    ),
+    (
+        "res/sheets/tool-fiber.json",
+        include_bytes!("../../res/sheets/tool-fiber.json"),
+    ),
+    (
+        "res/sheets/tool-fiber.png",
+        include_bytes!("../../res/sheets/tool-fiber.png"),
+    ),
+    (
+        "res/sheets/tool-seed.json",
+        include_bytes!("../../res/sheets/tool-seed.json"),
+    ),
+    (
+        "res/sheets/tool-seed.png",
+        include_bytes!("../../res/sheets/tool-seed.png"),
+    ),
+    (
+        "res/sheets/transition.json",
+        include_bytes!("../../res/sheets/transition.json"),
+    ),
+    (
+        "res/sheets/transition.png",
+        include_bytes!("../../res/sheets/transition.png"),
+    ),
+    (
+        "res/sheets/particle-dirt.json",
+        include_bytes!("../../res/sheets/particle-dirt.json"),
+    ),
+    (
+        "res/sheets/particle-dirt.png",
+        include_bytes!("../../res/sheets/particle-dirt.png"),
+    ),
+    (
+        "res/sheets/particle-water.json",
+        include_bytes!("../../res/sheets/particle-water.json"),
+    ),
+    (
+        "res/sheets/particle-water.png",
+        include_bytes!("../../res/sheets/particle-water.png"),
+    ),
+    (
+        "res/sheets/particle-heart.json",
+        include_bytes!("../../res/sheets/particle-heart.json"),
+    ),
+    (
+        "res/sheets/particle-heart.png",
+        include_bytes!("../../res/sheets/particle-heart.png"),
+    ),
+    (
+        "res/sheets/portrait-k.json",
+        include_bytes!("../../res/sheets/portrait-k.json"),
+    ),
+    (
+        "res/sheets/portrait-k.png",
+        include_bytes!("../../res/sheets/portrait-k.png"),
+    ),
+    (
+        "res/sheets/emote.json",
+        include_bytes!("../../res/sheets/emote.json"),
+    ),
+    (
+        "res/sheets/emote.png",
+        include_bytes!("../../res/sheets/emote.png"),
+    ),
+    (
+        "res/sheets/channel.json",
+        include_bytes!("../../res/sheets/channel.json"),
+    ),
+    (
+        "res/sheets/channel.png",
+        include_bytes!("../../res/sheets/channel.png"),
+    ),
+    (
+        "res/sheets/tap.json",
+        include_bytes!("../../res/sheets/tap.json"),
+    ),
+    (
+        "res/sheets/tap.png",
+        include_bytes!("../../res/sheets/tap.png"),
+    ),
+    (
+        "res/sheets/tile-ground.json",
+        include_bytes!("../../res/sheets/tile-ground.json"),
+    ),
+    (
+        "res/sheets/tile-ground.png",
+        include_bytes!("../../res/sheets/tile-ground.png"),
+    ),
+    (
+        "res/sheets/tile-water.json",
+        include_bytes!("../../res/sheets/tile-water.json"),
+    ),
+    (
+        "res/sheets/tile-water.png",
+        include_bytes!("../../res/sheets/tile-water.png"),
+    ),
+];
+
+/// the compiled-in copy of the asset at 'path'.
+#[cfg(any(target_arch = "wasm32", feature = "embed"))]
+fn embedded(path: &str) -> io::Result<Vec<u8>> {
+    EMBEDDED
+        .iter()
+        .find(|(p, _)| *p == path)
+        .map(|(_, bytes)| bytes.to_vec())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, path.to_string()))
+}
+
+/// the directories relative asset paths like "res/sheets/grass.json" are looked for in, in
+/// order: the working directory, the executable's directory, $QFFP_ASSETS, then qffp's XDG
+/// data directory.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn search_path() -> Vec<PathBuf> {
+    let mut dirs = vec![PathBuf::new()];
+    if let Some(dir) = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+    {
+        dirs.push(dir);
+    }
+    if let Some(dir) = std::env::var_os("QFFP_ASSETS") {
+        dirs.push(dir.into());
+    }
+    let data = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")));
+    if let Some(data) = data {
+        dirs.push(data.join("qffp"));
+    }
+    dirs
+}
+
+/// the first file on the search path for the asset at 'path', or a not found error listing
+/// everywhere it was looked for. absolute paths are taken as they are.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn find(path: &str) -> io::Result<PathBuf> {
+    let path = Path::new(path);
+    if path.is_absolute() {
+        return Ok(path.to_path_buf());
+    }
+    let tried: Vec<PathBuf> = search_path().iter().map(|dir| dir.join(path)).collect();
+    tried.iter().find(|p| p.is_file()).cloned().ok_or_else(|| {
+        let tried: Vec<String> = tried.iter().map(|p| p.display().to_string()).collect();
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("not found in any of {}", tried.join(", ")),
+        )
+    })
+}
+
+/// whether there's an asset at 'path' to read.
+pub fn exists(path: &str) -> bool {
+    #[cfg(target_arch = "wasm32")]
+    {
+        embedded(path).is_ok()
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        find(path).is_ok()
+    }
+}
+
+/// read an asset file, from the first place on the search path that has it. wasm builds have
+/// no filesystem, so they read the compiled-in sheets. with the embed feature, files missing
+/// from every place are read from the compiled-in sheets, so edited ones on disk still win.
+pub fn read(path: &str) -> io::Result<Vec<u8>> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        embedded(path)
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        match find(path) {
+            Ok(found) => std::fs::read(found),
+            #[cfg(feature = "embed")]
+            Err(e) if e.kind() == io::ErrorKind::NotFound => embedded(path).map_err(|_| e),
+            Err(e) => Err(e),
+        }
+    }
+}
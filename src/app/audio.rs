@@ -0,0 +1,156 @@
+use super::sounds::{SoundMap, BUILTIN};
+use super::{assets, settings, state};
+use rodio::source::{SineWave, Source, SquareWave, TriangleWave};
+use rodio::{Decoder, DeviceSinkBuilder, MixerDeviceSink, Player};
+use specs::{Read, System};
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// notes of the background music loop as (frequency, milliseconds). a frequency of 0 rests.
+const MUSIC: &[(f32, u64)] = &[
+    (262.0, 400),
+    (330.0, 400),
+    (392.0, 400),
+    (330.0, 400),
+    (294.0, 400),
+    (349.0, 400),
+    (440.0, 800),
+    (0.0, 400),
+    (247.0, 400),
+    (294.0, 400),
+    (392.0, 400),
+    (294.0, 400),
+    (262.0, 1200),
+    (0.0, 800),
+];
+
+/// a short triangle wave note, the voice of the music and most effects.
+fn note(freq: f32, ms: u64) -> impl Source + Send {
+    let volume = if freq > 0.0 { 1.0 } else { 0.0 };
+    TriangleWave::new(freq.max(1.0))
+        .take_duration(Duration::from_millis(ms))
+        .amplify(volume)
+}
+
+/// plays music and effect sounds for gameplay events on the default audio device, as mapped in
+/// the sound map.
+pub struct AudioSystem {
+    sink: MixerDeviceSink,
+    music: Player,
+    files: HashMap<String, Arc<[u8]>>,
+}
+
+impl AudioSystem {
+    /// returns None when there's no audio device to play on. the sound map's files are read
+    /// up front; files that can't be read stay silent.
+    pub fn new(sounds: &SoundMap) -> Option<AudioSystem> {
+        let mut sink = DeviceSinkBuilder::open_default_sink().ok()?;
+        // rodio reports dropping the device on stderr, which would land on the game screen
+        sink.log_on_drop(false);
+        let music = Player::connect_new(sink.mixer());
+        let files = sounds
+            .files()
+            .into_iter()
+            .filter_map(|path| Some((path.to_string(), assets::read(path).ok()?.into())))
+            .collect();
+        Some(AudioSystem { sink, music, files })
+    }
+
+    fn decode(&self, path: &str) -> Option<Decoder<Cursor<Arc<[u8]>>>> {
+        let bytes = self.files.get(path)?.clone();
+        Decoder::new(Cursor::new(bytes)).ok()
+    }
+
+    fn play_effect(&self, source: impl Source + Send + 'static, volume: f32) {
+        self.sink.mixer().add(source.amplify(volume));
+    }
+
+    /// play a sound file or builtin sound. unknown sounds are ignored.
+    fn play_sound(&self, sound: &str, volume: f32) {
+        let Some(name) = sound.strip_prefix(BUILTIN) else {
+            if let Some(source) = self.decode(sound) {
+                self.play_effect(source, volume);
+            }
+            return;
+        };
+        match name {
+            "dig" => self.play_effect(
+                SquareWave::new(110.0)
+                    .take_duration(Duration::from_millis(80))
+                    .amplify(0.3),
+                volume,
+            ),
+            "water" => self.play_effect(
+                note(880.0, 60).mix(SineWave::new(660.0).take_duration(Duration::from_millis(120))),
+                volume * 0.5,
+            ),
+            "seed" => self.play_effect(note(523.0, 50), volume),
+            "pickup" => {
+                self.play_effect(note(659.0, 40), volume);
+                self.play_effect(note(784.0, 60).delay(Duration::from_millis(40)), volume);
+            }
+            "sleep" => self.play_effect(
+                SineWave::new(196.0).take_duration(Duration::from_millis(600)),
+                volume * 0.5,
+            ),
+            "message" => {
+                self.play_effect(note(988.0, 80), volume);
+                self.play_effect(note(1319.0, 160).delay(Duration::from_millis(80)), volume);
+            }
+            "bloom" => {
+                for (i, freq) in [523.0, 659.0, 784.0].into_iter().enumerate() {
+                    let start = Duration::from_millis(90 * i as u64);
+                    self.play_effect(note(freq, 90).delay(start), volume);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn queue_music(&self, music: &str) {
+        match music.strip_prefix(BUILTIN) {
+            Some("music") => {
+                for &(freq, ms) in MUSIC {
+                    self.music.append(note(freq, ms).amplify(0.5));
+                }
+            }
+            Some(_) => {}
+            None => {
+                if let Some(source) = self.decode(music) {
+                    self.music.append(source);
+                }
+            }
+        }
+    }
+}
+
+impl<'a> System<'a> for AudioSystem {
+    type SystemData = (
+        Read<'a, settings::Settings>,
+        Read<'a, SoundMap>,
+        Read<'a, state::Events>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (settings, sounds, events) = data;
+
+        // keep the music looping, following the volume setting
+        let loudness = if settings.muted { 0.0 } else { 1.0 };
+        self.music
+            .set_volume(settings.music_volume.clamp(0.0, 1.0) * loudness);
+        if self.music.empty() {
+            if let Some(music) = &sounds.music {
+                self.queue_music(music);
+            }
+        }
+
+        let volume = settings.effects_volume.clamp(0.0, 1.0) * loudness;
+        for event in events.0.iter() {
+            if let Some(sound) = sounds.events.get(event).and_then(|e| e.sound.as_deref()) {
+                self.play_sound(sound, volume);
+            }
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use super::{sprite, state};
+use specs::{Read, ReadStorage, System, Write};
+
+/// the daily challenge's objective: this many flowers blooming at once, within this many days.
+pub const FLOWERS: usize = 5;
+pub const DAYS: u32 = 10;
+
+/// a calendar date as (year, month, day).
+pub type Date = (i64, u32, u32);
+
+/// today's date in UTC, so players everywhere share the same challenge.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn today() -> Date {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    // days since the epoch to a civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let z = (secs / 86_400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// the world seed for a date. everyone playing the same day gets the same farm layout, as long
+/// as their worlds are the same size.
+pub fn seed((year, month, day): Date) -> u64 {
+    (year * 10_000 + month as i64 * 100 + day as i64) as u64
+}
+
+/// specs resource tracking a daily challenge run. only present in daily challenge mode.
+pub struct Challenge {
+    pub date: Date,
+    /// the most flowers seen blooming at once.
+    pub best: usize,
+    /// set when the run is over: 100 points per blooming flower, plus 200 per day to spare if
+    /// the objective was met.
+    pub score: Option<u32>,
+}
+
+impl Challenge {
+    pub fn new(date: Date) -> Challenge {
+        Challenge {
+            date,
+            best: 0,
+            score: None,
+        }
+    }
+
+    /// a line describing the objective and progress, or the final score.
+    pub fn status(&self, day: u32) -> String {
+        let (year, month, date) = self.date;
+        let name = format!("daily {}-{:02}-{:02}", year, month, date);
+        match self.score {
+            Some(score) if self.best >= FLOWERS => {
+                format!("{}: bloomed {} flowers! score {}", name, self.best, score)
+            }
+            Some(score) => format!("{}: out of days. score {}", name, score),
+            None => format!(
+                "{}: bloom {}/{} flowers | day {}/{}",
+                name, self.best, FLOWERS, day, DAYS
+            ),
+        }
+    }
+}
+
+/// follows the challenge's objective and scores the run when it ends. does nothing outside
+/// daily challenge mode.
+pub struct ChallengeSystem;
+
+impl<'a> System<'a> for ChallengeSystem {
+    type SystemData = (
+        Option<Write<'a, Challenge>>,
+        Read<'a, state::Game>,
+        Read<'a, sprite::SpriteStore>,
+        ReadStorage<'a, state::Sprite>,
+        ReadStorage<'a, state::Interactible>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (challenge, game, store, sprites, interactibles) = data;
+        let Some(mut challenge) = challenge else {
+            return;
+        };
+        if challenge.score.is_some() {
+            return;
+        }
+
+        let blooming = state::blooming_flowers(&store, &interactibles, &sprites);
+        challenge.best = challenge.best.max(blooming);
+        if challenge.best >= FLOWERS {
+            let days_left = DAYS.saturating_sub(game.day);
+            challenge.score = Some(100 * challenge.best as u32 + 200 * days_left);
+        } else if game.day > DAYS {
+            challenge.score = Some(100 * challenge.best as u32);
+        }
+    }
+}
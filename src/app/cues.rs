@@ -0,0 +1,34 @@
+use super::sounds::SoundMap;
+use super::state::GameEvent;
+
+pub const BELL: &str = "\x07";
+
+/// specs resource mapping gameplay events to bytes written to the terminal, for audio feedback
+/// without an audio dependency. the bell works everywhere; terminals that support other
+/// escape-based sounds can be given those in the sound map. empty unless enabled with `--bell`.
+#[derive(Default)]
+pub struct Cues(pub Vec<(GameEvent, String)>);
+
+impl Cues {
+    /// the cues given in a sound map.
+    pub fn from_sounds(sounds: &SoundMap) -> Cues {
+        Cues(
+            sounds
+                .events
+                .iter()
+                .filter_map(|(event, sound)| Some((*event, sound.cue.clone()?)))
+                .collect(),
+        )
+    }
+
+    /// the bytes to write for this update's events, each cue at most once.
+    pub fn for_events(&self, events: &[GameEvent]) -> String {
+        let mut out = String::new();
+        for (event, cue) in self.0.iter() {
+            if events.contains(event) && !out.contains(cue.as_str()) {
+                out.push_str(cue);
+            }
+        }
+        out
+    }
+}
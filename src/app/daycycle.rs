@@ -0,0 +1,71 @@
+use super::state;
+use specs::{Read, System, Write};
+
+/// how long a day on the farm lasts, in game time, from dawn until the light is gone.
+pub const DAY_LENGTH: u64 = 360_000;
+
+/// the light through the day, as how much of each of red, green and blue gets through out of
+/// 256, from dawn at 0.0 until night at 1.0. the light fades between one and the next.
+const LIGHT: [(f32, [u16; 3]); 6] = [
+    (0.0, [110, 100, 140]),
+    (0.06, [256, 200, 160]),
+    (0.15, [256, 256, 256]),
+    (0.7, [256, 256, 256]),
+    (0.82, [240, 170, 140]),
+    (1.0, [80, 90, 150]),
+];
+
+/// how many steps the light changes in, so the screen isn't redrawn for every frame of dusk.
+const LIGHT_STEPS: u16 = 32;
+
+/// the time of day. each day starts at dawn, when the player wakes from the cryopod, and
+/// stays dark after nightfall until they sleep.
+#[derive(Debug, Default)]
+pub struct DayCycle {
+    /// the game's day that 'dawn' belongs to.
+    pub day: u32,
+    /// the game time the day began at.
+    pub dawn: u64,
+}
+
+impl DayCycle {
+    /// how far through the day it is at 'time', from 0.0 at dawn to 1.0 at nightfall.
+    pub fn hour(&self, time: u64) -> f32 {
+        (time.saturating_sub(self.dawn) as f32 / DAY_LENGTH as f32).min(1.0)
+    }
+
+    /// the light at 'time', out of 256 for each of red, green and blue.
+    pub fn light(&self, time: u64) -> [u16; 3] {
+        let hour = self.hour(time);
+        let i = LIGHT.iter().rposition(|l| l.0 <= hour).unwrap_or(0);
+        let ((from, a), (to, b)) = (LIGHT[i], LIGHT[(i + 1).min(LIGHT.len() - 1)]);
+        let t = if to > from {
+            (hour - from) / (to - from)
+        } else {
+            0.0
+        };
+        let step = 256 / LIGHT_STEPS;
+        [0, 1, 2].map(|c| {
+            let level = a[c] as f32 + (b[c] as f32 - a[c] as f32) * t;
+            (level as u16).div_ceil(step) * step
+        })
+    }
+}
+
+/// starts a new day at dawn whenever the game's day moves on, which is when the player sleeps.
+pub struct DayCycleSystem;
+
+impl<'a> System<'a> for DayCycleSystem {
+    type SystemData = (
+        Write<'a, DayCycle>,
+        Read<'a, state::Game>,
+        Read<'a, state::Time>,
+    );
+
+    fn run(&mut self, (mut cycle, game, time): Self::SystemData) {
+        if cycle.day != game.day {
+            cycle.day = game.day;
+            cycle.dawn = time.0;
+        }
+    }
+}
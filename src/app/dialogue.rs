@@ -0,0 +1,94 @@
+use super::{assets, story, AppError};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// the story's messages, by id: who sends each, what it says, the replies it offers and the
+/// ways on from it.
+pub const DIALOGUE: &str = "res/dialogue.json";
+
+/// the story as read from the dialogue file: its messages, the first arriving first.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Dialogue {
+    pub messages: Vec<Message>,
+}
+
+impl Dialogue {
+    /// read the dialogue at 'path', checking that every way on leads to a message.
+    pub fn load(path: &str) -> Result<Dialogue, AppError> {
+        let dialogue: Dialogue = serde_json::from_slice(&assets::read(path)?)?;
+        if dialogue.messages.is_empty() {
+            return Err(AppError::Dialogue("no messages".to_string()));
+        }
+        for message in &dialogue.messages {
+            if let Some(next) = message
+                .next
+                .iter()
+                .find(|n| dialogue.index(&n.to).is_none())
+            {
+                return Err(AppError::Dialogue(format!(
+                    "{} leads to {}, which isn't a message",
+                    message.id, next.to
+                )));
+            }
+        }
+        Ok(dialogue)
+    }
+
+    /// where the message called 'id' is in the story.
+    pub fn index(&self, id: &str) -> Option<usize> {
+        self.messages.iter().position(|m| m.id == id)
+    }
+}
+
+/// one message in the story. a message can offer replies, each setting a story flag, and can
+/// read differently depending on the flags set before it arrives.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Message {
+    pub id: String,
+    pub sender: String,
+    pub text: String,
+    /// replies picked at the terminal, and the flag each sets.
+    pub choices: Vec<Choice>,
+    /// text shown instead of the usual when a flag is set. the first set flag wins.
+    pub variants: Vec<Variant>,
+    /// a line quoting the last reply typed before this message arrived, with "{reply}" in
+    /// its place. left out if nothing was typed.
+    pub echo: Option<String>,
+    /// the ways on to the next message, tried each night in order. the story ends at a
+    /// message without any.
+    pub next: Vec<story::Next>,
+}
+
+impl Message {
+    pub fn text(&self, flags: &BTreeSet<String>) -> &str {
+        self.variants
+            .iter()
+            .find(|v| flags.contains(&v.flag))
+            .map_or(&self.text, |v| &v.text)
+    }
+
+    /// the first line of the message itself, past any "New message..." banner.
+    pub fn subject(text: &str) -> String {
+        let line = text
+            .lines()
+            .find(|line| !line.ends_with("..."))
+            .unwrap_or(text);
+        line.trim_start_matches("... > ")
+            .trim_start_matches("### ")
+            .to_string()
+    }
+}
+
+/// a reply to pick at the terminal.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Choice {
+    pub label: String,
+    pub flag: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Variant {
+    pub flag: String,
+    pub text: String,
+}
@@ -0,0 +1,405 @@
+use std::io::{Result, Write};
+use unicode_width::UnicodeWidthStr;
+
+/// escape sequences for preparing and restoring a terminal. they are plain bytes so they work on
+/// any output: the local terminal, a remote session, or a browser terminal.
+pub const ENTER_ALT_SCREEN: &str = "\x1b[?1049h";
+pub const LEAVE_ALT_SCREEN: &str = "\x1b[?1049l";
+pub const HIDE_CURSOR: &str = "\x1b[?25l";
+pub const SHOW_CURSOR: &str = "\x1b[?25h";
+pub const CLEAR: &str = "\x1b[2J";
+/// save and restore the window title, so it can be put back after the game changes it.
+pub const PUSH_TITLE: &str = "\x1b[22;0t";
+pub const POP_TITLE: &str = "\x1b[23;0t";
+
+/// how the game draws to a terminal. stored as a specs resource.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RenderMode {
+    /// two pixels to a cell with unicode half blocks.
+    #[default]
+    Blocks,
+    /// one color to a cell, shaded with ASCII characters, for terminals without good unicode
+    /// fonts.
+    Ascii,
+    /// sixteen colors, and only characters every console codepage can show, for the old windows
+    /// console.
+    Legacy,
+    /// a coarse grid of emoji, one for each sprite, over flat colors.
+    Emoji,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    Reset,
+    Rgb {
+        r: u8,
+        g: u8,
+        b: u8,
+    },
+    /// one of the sixteen standard colors, numbered as in SGR: 0 to 7, then their bright
+    /// versions 8 to 15.
+    Ansi(u8),
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Style {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub underline: bool,
+}
+
+impl Style {
+    pub const fn new() -> Style {
+        Style {
+            fg: None,
+            bg: None,
+            underline: false,
+        }
+    }
+
+    pub fn set_fg(&mut self, color: Color) {
+        self.fg = Some(color);
+    }
+
+    pub fn set_bg(&mut self, color: Color) {
+        self.bg = Some(color);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScreenPos {
+    pub x: u16,
+    pub y: u16,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Size {
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Size {
+    pub fn new(width: usize, height: usize) -> Size {
+        Size { width, height }
+    }
+}
+
+impl From<(u16, u16)> for Size {
+    fn from((width, height): (u16, u16)) -> Size {
+        Size::new(width as usize, height as usize)
+    }
+}
+
+/// a block of cells 'w' wide and 'h' high from 'x', 'y', which may lie partly off screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i64,
+    pub y: i64,
+    pub w: i64,
+    pub h: i64,
+}
+
+impl Rect {
+    pub fn overlaps(&self, other: &Rect) -> bool {
+        self.x < other.x + other.w
+            && other.x < self.x + self.w
+            && self.y < other.y + other.h
+            && other.y < self.y + self.h
+    }
+}
+
+pub fn write_fg(out: &mut impl Write, color: Color) -> Result<()> {
+    match color {
+        Color::Reset => write!(out, "\x1b[39m"),
+        Color::Rgb { r, g, b } => write!(out, "\x1b[38;2;{};{};{}m", r, g, b),
+        Color::Ansi(n) if n < 8 => write!(out, "\x1b[{}m", 30 + n),
+        Color::Ansi(n) => write!(out, "\x1b[{}m", 90 + (n - 8) % 8),
+    }
+}
+
+pub fn write_bg(out: &mut impl Write, color: Color) -> Result<()> {
+    match color {
+        Color::Reset => write!(out, "\x1b[49m"),
+        Color::Rgb { r, g, b } => write!(out, "\x1b[48;2;{};{};{}m", r, g, b),
+        Color::Ansi(n) if n < 8 => write!(out, "\x1b[{}m", 40 + n),
+        Color::Ansi(n) => write!(out, "\x1b[{}m", 100 + (n - 8) % 8),
+    }
+}
+
+pub fn write_underline(out: &mut impl Write, underline: bool) -> Result<()> {
+    write!(out, "\x1b[{}m", if underline { 4 } else { 24 })
+}
+
+/// move the cursor to a zero-based cell position.
+pub fn move_to(out: &mut impl Write, x: u16, y: u16) -> Result<()> {
+    write!(out, "\x1b[{};{}H", y + 1, x + 1)
+}
+
+pub fn set_title(out: &mut impl Write, title: &str) -> Result<()> {
+    write!(out, "\x1b]0;{}\x07", title)
+}
+
+/// what a cell shows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Glyph {
+    /// a single-width char, like the half blocks sprites are drawn with.
+    Char(char),
+    /// a grapheme of one or more chars, and how many columns it takes: 1 or 2.
+    Grapheme(Box<str>, u8),
+    /// the second column of the double-width grapheme to its left.
+    Continuation,
+}
+
+type Cell = Option<(Glyph, Style)>;
+
+/// a copy of a whole drawn frame, for programs driving the game without a terminal. stored as
+/// a specs resource when asked for, and filled in by the render system.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Frame {
+    pub size: Size,
+    /// each cell's text and style, row by row. empty cells are a blank in no style, and the
+    /// second column of a double-width grapheme is empty text.
+    pub cells: Vec<(String, Style)>,
+}
+
+impl Frame {
+    pub fn get(&self, pos: ScreenPos) -> Option<&(String, Style)> {
+        if pos.x as usize >= self.size.width {
+            return None;
+        }
+        self.cells
+            .get(pos.y as usize * self.size.width + pos.x as usize)
+    }
+
+    /// the frame's text without styles, one line per row.
+    pub fn text(&self) -> String {
+        let rows = self.cells.chunks(self.size.width.max(1));
+        let rows = rows.map(|row| {
+            row.iter()
+                .map(|(text, _)| text.as_str())
+                .collect::<String>()
+        });
+        rows.collect::<Vec<_>>().join("\n")
+    }
+}
+
+/// a double-buffered grid of styled cells. the game draws a whole frame into the new buffer,
+/// then render writes only the cells that differ from the last rendered frame.
+pub struct Screen {
+    size: Size,
+    old_buffer: Vec<Cell>,
+    new_buffer: Vec<Cell>,
+}
+
+impl Screen {
+    pub fn new(size: impl Into<Size>) -> Screen {
+        let size = size.into();
+        Screen {
+            size,
+            old_buffer: vec![None; size.width * size.height],
+            new_buffer: vec![None; size.width * size.height],
+        }
+    }
+
+    pub fn size(&self) -> Size {
+        self.size
+    }
+
+    /// resize both buffers, emptying them so everything is redrawn.
+    pub fn resize(&mut self, size: Size) {
+        *self = Screen::new(size);
+    }
+
+    fn index(&self, pos: ScreenPos) -> Option<usize> {
+        let (x, y) = (pos.x as usize, pos.y as usize);
+        (x < self.size.width && y < self.size.height).then_some(y * self.size.width + x)
+    }
+
+    /// 'style' with any colors it's missing kept from what was already drawn at 'index'. the
+    /// underline is always 'style's own.
+    fn merged_style(&self, index: usize, style: Style) -> Style {
+        let current = self.new_buffer[index]
+            .as_ref()
+            .map(|(_, st)| *st)
+            .unwrap_or_default();
+        Style {
+            fg: style.fg.or(current.fg),
+            bg: style.bg.or(current.bg),
+            underline: style.underline,
+        }
+    }
+
+    /// drawing over either half of a double-width grapheme leaves a blank in the other half.
+    fn split_wide(&mut self, index: usize) {
+        let other = match self.new_buffer[index] {
+            Some((Glyph::Continuation, _)) => index - 1,
+            Some((Glyph::Grapheme(_, 2), _)) => index + 1,
+            _ => return,
+        };
+        if let Some((glyph, _)) = &mut self.new_buffer[other] {
+            *glyph = Glyph::Char(' ');
+        }
+    }
+
+    /// put a single-width char with a style at a position. colors missing from 'style' are kept
+    /// from what was already drawn there. positions outside the screen are ignored.
+    pub fn put(&mut self, c: char, style: Style, pos: ScreenPos) {
+        let Some(index) = self.index(pos) else {
+            return;
+        };
+        let style = self.merged_style(index, style);
+        self.split_wide(index);
+        self.new_buffer[index] = Some((Glyph::Char(c), style));
+    }
+
+    /// put a grapheme like put does for a char, returning the columns it takes. a double-width
+    /// grapheme that doesn't fit before the right edge is drawn as a blank.
+    pub fn put_grapheme(&mut self, grapheme: &str, style: Style, pos: ScreenPos) -> u16 {
+        let width = grapheme.width().clamp(1, 2) as u16;
+        let mut chars = grapheme.chars();
+        if let (Some(c), None, 1) = (chars.next(), chars.next(), width) {
+            self.put(c, style, pos);
+            return width;
+        }
+        let Some(index) = self.index(pos) else {
+            return width;
+        };
+        let next = ScreenPos {
+            x: pos.x.saturating_add(1),
+            ..pos
+        };
+        let next_index = match self.index(next) {
+            Some(next_index) => Some(next_index),
+            None if width == 2 => {
+                self.put(' ', style, pos);
+                return width;
+            }
+            None => None,
+        };
+        let style = self.merged_style(index, style);
+        self.split_wide(index);
+        if let (2, Some(next_index)) = (width, next_index) {
+            self.split_wide(next_index);
+            self.new_buffer[next_index] = Some((Glyph::Continuation, style));
+        }
+        self.new_buffer[index] = Some((Glyph::Grapheme(grapheme.into(), width as u8), style));
+        width
+    }
+
+    /// the char and style at a position. a grapheme reports its first char, and the second
+    /// half of a double-width one reports a blank.
+    pub fn get(&self, pos: ScreenPos) -> Option<(char, Style)> {
+        let (glyph, style) = self.new_buffer[self.index(pos)?].as_ref()?;
+        let c = match glyph {
+            Glyph::Char(c) => *c,
+            Glyph::Grapheme(g, _) => g.chars().next().unwrap_or(' '),
+            Glyph::Continuation => ' ',
+        };
+        Some((c, *style))
+    }
+
+    /// empty the new buffer. call between frames.
+    pub fn erase(&mut self) {
+        self.new_buffer.fill(None);
+    }
+
+    /// start the new buffer over as a copy of what's drawn in 'other', a screen of the same size.
+    pub fn copy_from(&mut self, other: &Screen) {
+        self.new_buffer.clone_from(&other.new_buffer);
+    }
+
+    /// copy what's drawn in 'other', a screen of the same size, over the cells in 'rect'.
+    pub fn copy_rect(&mut self, other: &Screen, rect: Rect) {
+        let (width, height) = (self.size.width as i64, self.size.height as i64);
+        let (x0, x1) = (rect.x.clamp(0, width), (rect.x + rect.w).clamp(0, width));
+        for y in rect.y.max(0)..(rect.y + rect.h).min(height) {
+            let row = (y * width) as usize;
+            let cells = row + x0 as usize..row + x1 as usize;
+            self.new_buffer[cells.clone()].clone_from_slice(&other.new_buffer[cells]);
+        }
+    }
+
+    /// clear the whole terminal and both buffers. causes flicker, so only use it when the
+    /// terminal's contents can't be trusted, e.g. after a resize.
+    pub fn clear_all(&mut self, mut out: impl Write) -> Result<()> {
+        self.erase();
+        self.old_buffer.fill(None);
+        move_to(&mut out, 0, 0)?;
+        write_fg(&mut out, Color::Reset)?;
+        write_bg(&mut out, Color::Reset)?;
+        write!(out, "{}", CLEAR)?;
+        out.flush()
+    }
+
+    /// a copy of the frame drawn so far.
+    pub fn frame(&self) -> Frame {
+        let cells = self.new_buffer.iter().map(|cell| match cell {
+            Some((Glyph::Char(c), style)) => (c.to_string(), *style),
+            Some((Glyph::Grapheme(g, _), style)) => (g.to_string(), *style),
+            Some((Glyph::Continuation, style)) => (String::new(), *style),
+            None => (" ".to_string(), Style::new()),
+        });
+        Frame {
+            size: self.size,
+            cells: cells.collect(),
+        }
+    }
+
+    /// write the cells that changed since the last render.
+    pub fn render(&mut self, mut out: impl Write) -> Result<()> {
+        let mut cursor: Option<(usize, usize)> = None;
+        let mut current_style: Option<Style> = None;
+        for (index, (old, new)) in self
+            .old_buffer
+            .iter()
+            .zip(self.new_buffer.iter())
+            .enumerate()
+        {
+            if old == new {
+                continue;
+            }
+            // the second half of a double-width grapheme is drawn along with the first
+            if let Some((Glyph::Continuation, _)) = new {
+                continue;
+            }
+            let (x, y) = (index % self.size.width, index / self.size.width);
+            if cursor != Some((x, y)) {
+                move_to(&mut out, x as u16, y as u16)?;
+            }
+
+            // removed cells are drawn as blanks in the terminal's own colors
+            let (glyph, style) = new.clone().unwrap_or((Glyph::Char(' '), Style::new()));
+            if current_style != Some(style) {
+                write_fg(&mut out, style.fg.unwrap_or(Color::Reset))?;
+                write_bg(&mut out, style.bg.unwrap_or(Color::Reset))?;
+                // each render ends with the underline off
+                if current_style.is_some_and(|st| st.underline) != style.underline {
+                    write_underline(&mut out, style.underline)?;
+                }
+                current_style = Some(style);
+            }
+            match glyph {
+                Glyph::Grapheme(g, width) => {
+                    write!(out, "{}", g)?;
+                    cursor = Some((x + width as usize, y));
+                }
+                Glyph::Char(c) => {
+                    write!(out, "{}", c)?;
+                    cursor = Some((x + 1, y));
+                }
+                Glyph::Continuation => {}
+            }
+        }
+
+        if cursor.is_some() {
+            write_fg(&mut out, Color::Reset)?;
+            write_bg(&mut out, Color::Reset)?;
+            if current_style.is_some_and(|st| st.underline) {
+                write_underline(&mut out, false)?;
+            }
+            out.flush()?;
+            self.old_buffer.clone_from(&self.new_buffer);
+        }
+        Ok(())
+    }
+}
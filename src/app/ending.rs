@@ -0,0 +1,142 @@
+use super::{settings, state, Input, InputState};
+use specs::{Read, System, Write};
+
+/// milliseconds of real time each page of an ending stays up, unless skipped with use.
+const PAGE_TIME: u64 = 6000;
+
+/// an ending better than the quiet one takes this many flowers bloomed, fewer nights than this
+/// slept over unread messages, and finishing by this day.
+const BLOOMS: u32 = 3;
+const IGNORED: u32 = 3;
+const LATE_DAY: u32 = 20;
+
+/// how the story ends, depending on how it was played.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ending {
+    /// flowers grown, despite the notice.
+    Rebel,
+    /// flowers grown, keeping up with messages.
+    Bloom,
+    /// few flowers, many messages ignored, or a long time taken.
+    Quiet,
+}
+
+impl Ending {
+    pub fn choose(game: &state::Game) -> Ending {
+        if game.blooms < BLOOMS || game.ignored >= IGNORED || game.day > LATE_DAY {
+            Ending::Quiet
+        } else if game.flag(state::DEFIED) {
+            Ending::Rebel
+        } else {
+            Ending::Bloom
+        }
+    }
+
+    /// the story flag set once this ending is reached.
+    pub fn flag(&self) -> &'static str {
+        match self {
+            Ending::Rebel => state::ENDING_REBEL,
+            Ending::Bloom => state::ENDING_BLOOM,
+            Ending::Quiet => state::ENDING_QUIET,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Ending::Rebel => "rebel",
+            Ending::Bloom => "bloom",
+            Ending::Quiet => "quiet",
+        }
+    }
+
+    pub fn pages(&self) -> &'static [&'static str] {
+        match self {
+            Ending::Rebel => &[
+                "The next morning, K wakes you early.\n\"Come see.\"",
+                "Outside, a crowd in pressure suits waits\nat the airlock, seed packets in hand.",
+                "\"I told the others. Every dome on Luna\nwants flowers now.\"\nThe revolution smells nice.",
+            ],
+            Ending::Bloom => &[
+                "The next morning, K wakes you early.\n\"Come see.\"",
+                "Outside, the whole farm is in flower,\nred and gold under a black sky.",
+                "\"You grew them. You actually grew them.\"\nYou spend the day naming every one.",
+            ],
+            Ending::Quiet => &[
+                "The next morning, K wakes you gently.\n\"Hey. You've been busy... or not.\"",
+                "The fields are mostly dust,\nthe terminal blinking with unread messages.",
+                "\"It's okay. I'm here now.\" K takes your hand.\nTomorrow, you'll plant something together.",
+            ],
+        }
+    }
+}
+
+/// specs resource with the ending being played, if any, and the page it's on.
+#[derive(Debug, Default)]
+pub struct Cutscene {
+    pub ending: Option<Ending>,
+    pub page: usize,
+    pub page_start: u64,
+}
+
+impl Cutscene {
+    /// the page showing, with a footer naming the ending and the key, as typed on 'layout',
+    /// that turns the page.
+    pub fn text(&self, layout: settings::Layout) -> Option<String> {
+        let ending = self.ending?;
+        let page = ending.pages().get(self.page)?;
+        Some(format!(
+            "{}\n\n~ the {} ending ~  {}: next",
+            page,
+            ending.name(),
+            layout.keys("u")
+        ))
+    }
+}
+
+/// picks the ending the player earned when the story reaches its last message, then plays it
+/// page by page, holding back the local player's input until it's over.
+pub struct CutsceneSystem;
+
+impl<'a> System<'a> for CutsceneSystem {
+    type SystemData = (
+        Write<'a, state::Game>,
+        Write<'a, Cutscene>,
+        Write<'a, Input>,
+        Read<'a, state::RealTime>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (mut game, mut cutscene, mut input, time) = data;
+        let Some(ending) = cutscene.ending else {
+            let last = game.at_end();
+            let endings = [Ending::Rebel, Ending::Bloom, Ending::Quiet];
+            let ended = endings.iter().any(|ending| game.flag(ending.flag()));
+            if last && !ended {
+                let ending = Ending::choose(&game);
+                game.flags.insert(ending.flag().to_string());
+                *cutscene = Cutscene {
+                    ending: Some(ending),
+                    page: 0,
+                    page_start: time.0,
+                };
+            }
+            return;
+        };
+
+        let choice = input.slot(0);
+        if matches!(choice, InputState::Clear | InputState::Quit) {
+            return;
+        }
+        if let Some(i) = input.0.first_mut() {
+            *i = InputState::None;
+        }
+        let skipped = matches!(choice, InputState::Action | InputState::Pickup);
+        if skipped || time.0 >= cutscene.page_start + PAGE_TIME {
+            cutscene.page += 1;
+            cutscene.page_start = time.0;
+            if cutscene.page >= ending.pages().len() {
+                *cutscene = Cutscene::default();
+            }
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use super::{display, sprite, AppError, IOWrite};
+use display::Color;
+
+/// write one frame as half-block ANSI art, one line per two pixel rows. transparent pixels keep
+/// the terminal's own background, so the output pastes cleanly into READMEs and prompts.
+pub fn write_frame(
+    out: &mut impl IOWrite,
+    sheet: &sprite::LoadedSprite,
+    frame_number: usize,
+    flip: bool,
+) -> Result<(), AppError> {
+    let size = &sheet.data.frames[frame_number].source_size;
+    let pixel_at = |x: u32, y: u32| sheet.pixel(frame_number, x, y, flip);
+
+    for y in (0..size.h).step_by(2) {
+        for x in 0..size.w {
+            let (px, px2) = (pixel_at(x, y), pixel_at(x, y + 1));
+            let (fg, bg, ch) = match (px.is_visible(), px2.is_visible()) {
+                (true, true) => (px.color, px2.color, '▀'),
+                (true, false) => (px.color, Color::Reset, '▀'),
+                (false, true) => (px2.color, Color::Reset, '▄'),
+                (false, false) => (Color::Reset, Color::Reset, ' '),
+            };
+            display::write_fg(out, fg)?;
+            display::write_bg(out, bg)?;
+            write!(out, "{}", ch)?;
+        }
+        display::write_fg(out, Color::Reset)?;
+        display::write_bg(out, Color::Reset)?;
+        writeln!(out)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// print a sheet frame to stdout: `qffp export <sheet.json> [frame] [--flip]`.
+pub fn run(args: &[String]) -> Result<(), AppError> {
+    let usage = || AppError::Usage("qffp export <sheet.json> [frame] [--flip]".to_string());
+    let flip = args.iter().any(|a| a == "--flip");
+    let mut positional = args.iter().filter(|a| a.as_str() != "--flip");
+    let json_path = positional.next().ok_or_else(usage)?;
+    let frame_number = match positional.next() {
+        Some(n) => n.parse::<usize>().map_err(|_| usage())?,
+        None => 0,
+    };
+
+    let store = sprite::SpriteStore::new(vec![json_path])?;
+    let sheet = &store.0[0];
+    if frame_number >= sheet.data.frames.len() {
+        return Err(AppError::Usage(format!(
+            "{} has {} frames",
+            json_path,
+            sheet.data.frames.len()
+        )));
+    }
+
+    write_frame(&mut std::io::stdout(), sheet, frame_number, flip)
+}
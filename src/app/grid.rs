@@ -0,0 +1,179 @@
+use super::{sprite, state};
+use specs::storage::ComponentEvent;
+use specs::world::Index;
+use specs::{
+    BitSet, Entities, Entity, Join, Read, ReadStorage, ReaderId, System, Write, WriteStorage,
+};
+use std::collections::HashMap;
+
+/// the width and height in cells of each square of the grid.
+const SQUARE: i64 = 16;
+
+/// specs resource sorting interactibles into squares of the farm by their middle, so finding
+/// the ones near a player only looks in the squares around them instead of at everything.
+/// anything off the farm is kept aside, and always looked at.
+#[derive(Default)]
+pub struct Grid {
+    /// the farm's size in squares.
+    size: (i64, i64),
+    squares: Vec<Vec<Entity>>,
+    outside: Vec<Entity>,
+    /// the square each entity is kept in, or None if it's off the farm, by entity id.
+    placed: HashMap<Index, Option<usize>>,
+    readers: Option<(ReaderId<ComponentEvent>, ReaderId<ComponentEvent>)>,
+}
+
+/// the middle of an interactible's sprite, where distances to it are measured from.
+pub fn middle(
+    store: &sprite::SpriteStore,
+    sprite: &state::Sprite,
+    pos: &state::Position,
+) -> (i64, i64) {
+    let size = &store[sprite.store_index].data.frames[sprite.frame].source_size;
+    // two rows of pixels to a cell
+    (pos.x + size.w as i64 / 2, pos.y + size.h as i64 / 4)
+}
+
+/// the distance in whole cells between two cells.
+pub fn distance(a: (i64, i64), b: (i64, i64)) -> i64 {
+    let (dx, dy) = (a.0 - b.0, a.1 - b.1);
+    (dx * dx + dy * dy).isqrt()
+}
+
+/// the size in squares of a farm 'world' cells across.
+fn squares(world: (u16, u16)) -> (i64, i64) {
+    (
+        world.0.div_ceil(SQUARE as u16) as i64,
+        world.1.div_ceil(SQUARE as u16) as i64,
+    )
+}
+
+impl Grid {
+    fn clear(&mut self, size: (i64, i64)) {
+        self.size = size;
+        self.squares = vec![vec![]; (self.size.0 * self.size.1) as usize];
+        self.outside.clear();
+        self.placed.clear();
+    }
+
+    /// take out whichever entity has the id 'id', even one since deleted.
+    fn remove(&mut self, id: Index) {
+        let list = match self.placed.remove(&id) {
+            Some(Some(square)) => &mut self.squares[square],
+            Some(None) => &mut self.outside,
+            None => return,
+        };
+        list.retain(|e| e.id() != id);
+    }
+
+    fn insert(&mut self, entity: Entity, at: (i64, i64)) {
+        let (col, row) = (at.0.div_euclid(SQUARE), at.1.div_euclid(SQUARE));
+        let square = ((0..self.size.0).contains(&col) && (0..self.size.1).contains(&row))
+            .then_some((row * self.size.0 + col) as usize);
+        match square {
+            Some(square) => self.squares[square].push(entity),
+            None => self.outside.push(entity),
+        }
+        self.placed.insert(entity.id(), square);
+    }
+
+    /// everything whose middle might be within 'reach' cells of 'at', and a few more besides.
+    pub fn near(&self, at: (i64, i64), reach: i64) -> impl Iterator<Item = Entity> + '_ {
+        let span = |from: i64, size: i64| {
+            let first = (from - reach).div_euclid(SQUARE).max(0);
+            let last = (from + reach).div_euclid(SQUARE).min(size - 1);
+            first..=last
+        };
+        let (cols, rows) = (span(at.0, self.size.0), span(at.1, self.size.1));
+        rows.flat_map(move |row| {
+            cols.clone()
+                .map(move |col| (row * self.size.0 + col) as usize)
+        })
+        .flat_map(|square| self.squares[square].iter())
+        .chain(self.outside.iter())
+        .copied()
+    }
+
+    /// the closest entity to 'at' less than 'limit' cells away, as measured by 'distance',
+    /// which gives None for entities that don't count. ties go to the first entity created.
+    pub fn nearest(
+        &self,
+        at: (i64, i64),
+        limit: i64,
+        distance: impl Fn(Entity) -> Option<i64>,
+    ) -> Option<(Entity, i64)> {
+        // anything within 'reach' is sure to have been looked at, so widen the search until
+        // the closest found is that close
+        let mut reach = SQUARE;
+        loop {
+            reach = reach.min(limit);
+            let closest = self
+                .near(at, reach)
+                .filter_map(|e| Some((e, distance(e)?)))
+                .filter(|(_, dist)| *dist <= reach && *dist < limit)
+                .min_by_key(|(e, dist)| (*dist, e.id()));
+            if closest.is_some() || reach == limit {
+                return closest;
+            }
+            reach *= 2;
+        }
+    }
+}
+
+/// keeps the grid up to date with the interactibles whose sprites or positions were written
+/// since the last update.
+pub struct GridSystem;
+
+impl<'a> System<'a> for GridSystem {
+    type SystemData = (
+        Entities<'a>,
+        Write<'a, Grid>,
+        Read<'a, state::WorldSize>,
+        Read<'a, sprite::SpriteStore>,
+        ReadStorage<'a, state::Interactible>,
+        WriteStorage<'a, state::Sprite>,
+        WriteStorage<'a, state::Position>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (entities, mut grid, world_size, store, interactibles, mut sprites, mut positions) =
+            data;
+
+        // start over with everything on the first update, or when the farm changes size
+        let size = squares((world_size.0, world_size.1));
+        let mut changed = BitSet::new();
+        let readers = match grid.readers.take() {
+            Some(readers) if grid.size == size => readers,
+            Some(_) | None => {
+                grid.clear(size);
+                for (entity, _) in (&entities, &interactibles).join() {
+                    changed.add(entity.id());
+                }
+                (sprites.register_reader(), positions.register_reader())
+            }
+        };
+        let (mut sprite_reader, mut position_reader) = readers;
+        for event in sprites
+            .channel()
+            .read(&mut sprite_reader)
+            .chain(positions.channel().read(&mut position_reader))
+        {
+            let (ComponentEvent::Inserted(id)
+            | ComponentEvent::Modified(id)
+            | ComponentEvent::Removed(id)) = event;
+            changed.add(*id);
+        }
+        grid.readers = Some((sprite_reader, position_reader));
+
+        for id in (&changed).join() {
+            grid.remove(id);
+            let entity = entities.entity(id);
+            if !entities.is_alive(entity) || !interactibles.contains(entity) {
+                continue;
+            }
+            if let (Some(sprite), Some(pos)) = (sprites.get(entity), positions.get(entity)) {
+                grid.insert(entity, middle(&store, sprite, pos));
+            }
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use super::{sprite, state};
+use rand::Rng;
+use specs::{Read, System, Write};
+
+/// ground tiles are this many columns wide and rows high.
+pub const TILE: (i64, i64) = (8, 4);
+
+/// the pond's shimmer has this many frames for each tile, each shown for this many milliseconds.
+pub const SHIMMER_FRAMES: usize = 3;
+pub const SHIMMER_TIME: u64 = 400;
+
+/// what the ground is made of in one tile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ground {
+    Dirt,
+    Moss,
+    Path,
+    Water,
+}
+
+/// the ground under the whole world, a tile at a time, row by row.
+#[derive(Debug)]
+pub struct GroundMap {
+    pub cols: i64,
+    pub rows: i64,
+    tiles: Vec<Ground>,
+}
+
+impl GroundMap {
+    /// a map of 'tiles', row by row, 'cols' wide.
+    pub fn new(cols: i64, tiles: Vec<Ground>) -> GroundMap {
+        GroundMap {
+            cols,
+            rows: tiles.len() as i64 / cols.max(1),
+            tiles,
+        }
+    }
+
+    /// dirt with patches of moss, a pond out past the dome, and a path from the cryopod out
+    /// through the airlock to the tap.
+    pub fn generate(world: (u16, u16), dome: &state::Dome, rng: &mut impl Rng) -> GroundMap {
+        let (cols, rows) = (world.0 as i64 / TILE.0, world.1 as i64 / TILE.1);
+        let noise: Vec<bool> = (0..cols * rows).map(|_| rng.gen_range(0..3) == 0).collect();
+        let mut map = GroundMap {
+            cols,
+            rows,
+            tiles: vec![Ground::Dirt; (cols * rows) as usize],
+        };
+
+        // moss grows where most of the tiles around were rolled as moss, which clumps it together
+        for row in 0..rows {
+            for col in 0..cols {
+                let around = (-1..=1)
+                    .flat_map(|dy| (-1..=1).map(move |dx| (col + dx, row + dy)))
+                    .filter(|&(x, y)| (0..cols).contains(&x) && (0..rows).contains(&y))
+                    .filter(|&(x, y)| noise[(y * cols + x) as usize])
+                    .count();
+                if around >= 4 {
+                    map.set(col, row, Ground::Moss);
+                }
+            }
+        }
+
+        let center = ((dome.x / TILE.0 + cols) / 2 + 1, rows - 3);
+        for dy in -1..=1 {
+            for dx in -2..=2 {
+                if dx * dx * 9 + dy * dy * 16 <= 36 {
+                    map.set(center.0 + dx, center.1 + dy, Ground::Water);
+                }
+            }
+        }
+
+        let path_row = (dome.airlock.0 + TILE.1) / TILE.1;
+        for row in 2..=path_row {
+            map.set(0, row, Ground::Path);
+        }
+        for col in 0..=(dome.x + 8) / TILE.0 {
+            map.set(col, path_row, Ground::Path);
+        }
+        map
+    }
+
+    fn set(&mut self, col: i64, row: i64, ground: Ground) {
+        if (0..self.cols).contains(&col) && (0..self.rows).contains(&row) {
+            self.tiles[(row * self.cols + col) as usize] = ground;
+        }
+    }
+
+    /// the ground at a tile. past the edges of the world, it runs on as it was at the edge.
+    pub fn get(&self, col: i64, row: i64) -> Ground {
+        let col = col.clamp(0, self.cols - 1);
+        let row = row.clamp(0, self.rows - 1);
+        self.tiles[(row * self.cols + col) as usize]
+    }
+
+    /// the sheet and frame for a tile, or none for plain dirt. each kind of ground has sixteen
+    /// frames, one for every combination of its sides that border something else, with bits for
+    /// the north, east, south and west neighbors that match it. water's are the first of its
+    /// shimmer.
+    pub fn frame(
+        &self,
+        ids: &sprite::SpriteIds,
+        col: i64,
+        row: i64,
+    ) -> Option<(sprite::SpriteHandle, usize)> {
+        let ground = self.get(col, row);
+        let (sheet, first) = match ground {
+            Ground::Dirt => return None,
+            Ground::Moss => (ids.tile_ground, 0),
+            Ground::Path => (ids.tile_ground, 16),
+            Ground::Water => (ids.tile_water, 0),
+        };
+        let neighbors = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+        let mask = neighbors
+            .iter()
+            .enumerate()
+            .filter(|(_, (dx, dy))| self.get(col + dx, row + dy) == ground)
+            .fold(0, |mask, (bit, _)| mask | 1 << bit);
+        Some((sheet, first + mask))
+    }
+}
+
+/// one ground tile as it's drawn: a frame of a sheet, maybe flipped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile {
+    pub store_index: super::sprite::SpriteHandle,
+    pub frame: usize,
+    pub flip: bool,
+}
+
+impl Tile {
+    /// the tile as a sprite, for drawing.
+    pub fn sprite(&self) -> state::Sprite {
+        state::Sprite {
+            store_index: self.store_index,
+            frame: self.frame,
+            flip: self.flip,
+            ..state::Sprite::default()
+        }
+    }
+}
+
+/// specs resource with the tiles of ground drawn under everything else, row by row. they're
+/// drawn in one pass before the sprites, rather than each being an entity to sort and draw.
+#[derive(Debug, Default)]
+pub struct TileMap {
+    pub cols: i64,
+    pub rows: i64,
+    pub tiles: Vec<Tile>,
+}
+
+impl TileMap {
+    /// the tile at a column and row, if it's on the map.
+    pub fn get(&self, col: i64, row: i64) -> Option<&Tile> {
+        if !(0..self.cols).contains(&col) || !(0..self.rows).contains(&row) {
+            return None;
+        }
+        self.tiles.get((row * self.cols + col) as usize)
+    }
+
+    /// the columns and rows of the tiles in view of a screen of 'size' cells at 'camera'.
+    pub fn in_view(
+        &self,
+        camera: (i64, i64),
+        size: (u16, u16),
+    ) -> impl Iterator<Item = (i64, i64)> + '_ {
+        let span = |from: i64, len: u16, tile: i64, count: i64| {
+            from.div_euclid(tile).max(0)..(from + len as i64).div_euclid(tile).min(count - 1) + 1
+        };
+        let cols = span(camera.0, size.0, TILE.0, self.cols);
+        span(camera.1, size.1, TILE.1, self.rows)
+            .flat_map(move |row| cols.clone().map(move |col| (col, row)))
+    }
+}
+
+/// steps every water tile through its shimmer together, off the game clock, so the whole
+/// shoreline pulses at once.
+pub struct ShimmerSystem;
+
+impl<'a> System<'a> for ShimmerSystem {
+    type SystemData = (
+        Read<'a, state::Time>,
+        Read<'a, sprite::SpriteIds>,
+        Write<'a, TileMap>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (time, ids, mut tile_map) = data;
+        let water = ids.tile_water;
+        let phase = (time.0 / SHIMMER_TIME) as usize % SHIMMER_FRAMES;
+        for tile in tile_map.tiles.iter_mut() {
+            if tile.store_index == water {
+                tile.frame = tile.frame % 16 + phase * 16;
+            }
+        }
+    }
+}
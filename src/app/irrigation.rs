@@ -0,0 +1,140 @@
+use super::{sprite, state};
+use specs::{Join, Read, ReadStorage, System, Write, WriteStorage};
+use std::collections::BTreeSet;
+
+/// fiber it takes to dig one channel segment.
+pub const CHANNEL_FIBER: u32 = 1;
+
+/// channels are laid on a grid of cells this many columns wide and rows high.
+pub const CELL: (i64, i64) = (4, 2);
+
+/// a set of grid cells.
+pub type Cells = BTreeSet<(i64, i64)>;
+
+/// the grid cell holding the point 'pos'.
+pub fn cell(pos: (i64, i64)) -> (i64, i64) {
+    (pos.0.div_euclid(CELL.0), pos.1.div_euclid(CELL.1))
+}
+
+/// the neighbors of a grid cell, in the order of the bits of a channel's frame: north, east,
+/// south and west.
+fn neighbors(cell: (i64, i64)) -> [(i64, i64); 4] {
+    [
+        (cell.0, cell.1 - 1),
+        (cell.0 + 1, cell.1),
+        (cell.0, cell.1 + 1),
+        (cell.0 - 1, cell.1),
+    ]
+}
+
+/// the cells of every tap and channel among 'placed' machines.
+pub fn water_cells<'b>(
+    placed: impl Iterator<Item = (&'b state::Machine, &'b state::Position)>,
+) -> (Cells, Cells) {
+    let (mut taps, mut channels) = (BTreeSet::new(), BTreeSet::new());
+    for (machine, pos) in placed {
+        match machine.kind {
+            state::MachineKind::Tap => taps.insert(cell((pos.x, pos.y))),
+            state::MachineKind::Channel => channels.insert(cell((pos.x, pos.y))),
+            _ => false,
+        };
+    }
+    (taps, channels)
+}
+
+/// whether a new channel can be dug at 'cell': it has to be free, and run on from a tap or a
+/// channel beside it.
+pub fn can_dig(cell: (i64, i64), taps: &Cells, channels: &Cells) -> bool {
+    let taken = taps.contains(&cell) || channels.contains(&cell);
+    let joined = neighbors(cell)
+        .iter()
+        .any(|n| taps.contains(n) || channels.contains(n));
+    !taken && joined
+}
+
+/// the channels water reaches, flowing out from the taps through every joined channel.
+fn wet_cells(taps: &Cells, channels: &Cells) -> Cells {
+    let mut wet = BTreeSet::new();
+    let mut open: Vec<(i64, i64)> = taps.iter().copied().collect();
+    while let Some(cell) = open.pop() {
+        for n in neighbors(cell) {
+            if channels.contains(&n) && wet.insert(n) {
+                open.push(n);
+            }
+        }
+    }
+    wet
+}
+
+/// specs resource for the irrigation channels.
+#[derive(Debug, Default)]
+pub struct Irrigation {
+    /// the day the channels last watered.
+    pub day: u32,
+}
+
+/// joins up each channel's sprite with its neighbors, filling it with water when it connects
+/// back to a tap, and each morning waters every crop beside a wet channel. unlike sprinklers,
+/// channels draw no power.
+pub struct IrrigationSystem;
+
+impl<'a> System<'a> for IrrigationSystem {
+    type SystemData = (
+        Write<'a, Irrigation>,
+        Read<'a, state::Game>,
+        Read<'a, sprite::SpriteStore>,
+        Read<'a, sprite::SpriteIds>,
+        ReadStorage<'a, state::Machine>,
+        ReadStorage<'a, state::Interactible>,
+        ReadStorage<'a, state::Position>,
+        WriteStorage<'a, state::Sprite>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (mut irrigation, game, store, ids, machines, interactibles, positions, mut sprites) =
+            data;
+
+        let (taps, channels) = water_cells((&machines, &positions).join());
+        let wet = wet_cells(&taps, &channels);
+        for (machine, pos, mut entry) in (&machines, &positions, &mut sprites.restrict_mut()).join()
+        {
+            if machine.kind != state::MachineKind::Channel {
+                continue;
+            }
+            let here = cell((pos.x, pos.y));
+            let mask = neighbors(here)
+                .iter()
+                .enumerate()
+                .filter(|(_, n)| taps.contains(n) || channels.contains(n))
+                .fold(0, |mask, (bit, _)| mask | 1 << bit);
+            let frame = mask + if wet.contains(&here) { 16 } else { 0 };
+            if entry.get_unchecked().frame != frame {
+                entry.get_mut_unchecked().frame = frame;
+            }
+        }
+
+        if game.day == irrigation.day {
+            return;
+        }
+        irrigation.day = game.day;
+        let crop_empty = ids.crop_empty;
+        for (item, pos, sprite) in (&interactibles, &positions, &mut sprites).join() {
+            // as with sprinklers, dry plots are left alone
+            if item.item_type != state::ItemType::Crop || sprite.store_index == crop_empty {
+                continue;
+            }
+            let info = &store[sprite.store_index];
+            let Some(watered) = info.retag(sprite.frame, "dry", "watered") else {
+                continue;
+            };
+            // a crop covers two cells across and two down, and drinks from any channel touching it
+            let (left, top) = cell((pos.x, pos.y));
+            let beside = wet
+                .iter()
+                .any(|(x, y)| (left - 1..=left + 2).contains(x) && (top - 1..=top + 2).contains(y));
+            if beside {
+                sprite.frame = watered;
+            }
+        }
+    }
+}
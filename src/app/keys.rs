@@ -0,0 +1,176 @@
+use super::{settings, InputState};
+
+/// translate raw bytes from a terminal in raw mode into inputs, mapping the same keys as
+/// read_input does for the local terminal, with letters following 'layout'. while 'typing',
+/// keys are read as text.
+pub fn parse_keys(bytes: &[u8], typing: bool, layout: settings::Layout) -> Vec<InputState> {
+    let mut inputs: Vec<InputState> = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        if let Some((input, len)) = typing.then(|| typed(&bytes[i..])).flatten() {
+            inputs.push(input);
+            i += len;
+            continue;
+        }
+        let b = match bytes[i] {
+            b @ 0x20..=0x7e => layout.to_qwerty(b as char) as u8,
+            b => b,
+        };
+        i += 1;
+        let input = match b {
+            0x03 | b'q' => InputState::Quit,
+            b'h' => InputState::Left,
+            b'j' => InputState::Down,
+            b'k' => InputState::Up,
+            b'l' => InputState::Right,
+            b'H' => InputState::ShiftLeft,
+            b'J' => InputState::ShiftDown,
+            b'K' => InputState::ShiftUp,
+            b'L' => InputState::ShiftRight,
+            b'u' => InputState::Action,
+            b' ' => InputState::Pickup,
+            b'?' => InputState::ToggleHelp,
+            b'f' => InputState::CycleSpeed,
+            b'm' => InputState::Mute,
+            b'z' => InputState::Undo,
+            b'c' => InputState::Craft,
+            b'x' => InputState::Hop,
+            b'\t' => InputState::NextTarget,
+            0x1b if i < bytes.len() && (bytes[i] == b'[' || bytes[i] == b'O') => {
+                // escape sequence: parameters, then a final byte. "1;2" marks shift.
+                i += 1;
+                let start = i;
+                while i < bytes.len() && !(0x40..=0x7e).contains(&bytes[i]) {
+                    i += 1;
+                }
+                let Some(&last) = bytes.get(i) else {
+                    break;
+                };
+                i += 1;
+                let shift = bytes[start..i - 1].ends_with(b";2");
+                match (last, shift) {
+                    (b'A', false) => InputState::Up,
+                    (b'B', false) => InputState::Down,
+                    (b'C', false) => InputState::Right,
+                    (b'D', false) => InputState::Left,
+                    (b'A', true) => InputState::ShiftUp,
+                    (b'B', true) => InputState::ShiftDown,
+                    (b'C', true) => InputState::ShiftRight,
+                    (b'D', true) => InputState::ShiftLeft,
+                    _ => InputState::None,
+                }
+            }
+            _ => InputState::None,
+        };
+        if input != InputState::None {
+            inputs.push(input);
+        }
+    }
+    inputs
+}
+
+/// the text input at the start of 'bytes' and how many bytes it takes up, if it's text: a
+/// character, backspace, enter, or a lone escape. escape sequences are left to parse_keys.
+fn typed(bytes: &[u8]) -> Option<(InputState, usize)> {
+    let input = match bytes[0] {
+        0x03 => InputState::Quit,
+        b'\r' | b'\n' => InputState::Action,
+        0x08 | 0x7f => InputState::Backspace,
+        0x1b if !matches!(bytes.get(1), Some(b'[' | b'O')) => InputState::Pickup,
+        0x20..=0x7e => InputState::Char(bytes[0] as char),
+        b => {
+            let len = match b {
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                0xf0..=0xf7 => 4,
+                _ => return None,
+            };
+            let c = std::str::from_utf8(bytes.get(..len)?)
+                .ok()?
+                .chars()
+                .next()?;
+            return Some((InputState::Char(c), len));
+        }
+    };
+    Some((input, 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use settings::Layout;
+
+    #[test]
+    fn reads_game_keys() {
+        assert_eq!(
+            parse_keys(b"hjklu ?Kq\x03", false, Layout::Qwerty),
+            vec![
+                InputState::Left,
+                InputState::Down,
+                InputState::Up,
+                InputState::Right,
+                InputState::Action,
+                InputState::Pickup,
+                InputState::ToggleHelp,
+                InputState::ShiftUp,
+                InputState::Quit,
+                InputState::Quit,
+            ]
+        );
+        // keys that do nothing are dropped
+        assert_eq!(parse_keys(b"!1", false, Layout::Qwerty), vec![]);
+    }
+
+    #[test]
+    fn follows_the_layout() {
+        // the keys in the same places as qwerty's 'h j k l u'
+        assert_eq!(
+            parse_keys(b"dhtng", false, Layout::Dvorak),
+            parse_keys(b"hjklu", false, Layout::Qwerty)
+        );
+        assert_eq!(
+            parse_keys(b"a", false, Layout::Azerty),
+            vec![InputState::Quit]
+        );
+        assert_eq!(
+            parse_keys(b"N", false, Layout::Colemak),
+            vec![InputState::ShiftDown]
+        );
+    }
+
+    #[test]
+    fn reads_arrow_keys() {
+        assert_eq!(
+            parse_keys(b"\x1b[A\x1bOB\x1b[1;2C\x1b[1;2D", false, Layout::Qwerty),
+            vec![
+                InputState::Up,
+                InputState::Down,
+                InputState::ShiftRight,
+                InputState::ShiftLeft,
+            ]
+        );
+        // other sequences are skipped whole, and a sequence cut off ends the input
+        assert_eq!(
+            parse_keys(b"\x1b[15~u\x1b[1;", false, Layout::Qwerty),
+            vec![InputState::Action]
+        );
+    }
+
+    #[test]
+    fn reads_text_while_typing() {
+        assert_eq!(
+            parse_keys("hé\x7f\r".as_bytes(), true, Layout::Dvorak),
+            vec![
+                InputState::Char('h'),
+                InputState::Char('é'),
+                InputState::Backspace,
+                InputState::Action,
+            ]
+        );
+        // a lone escape cancels, but arrows still move the cursor
+        assert_eq!(
+            parse_keys(b"\x1b[D\x1b", true, Layout::Qwerty),
+            vec![InputState::Left, InputState::Pickup]
+        );
+    }
+}
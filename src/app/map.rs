@@ -0,0 +1,260 @@
+use super::{ground, scene, settings, state, AppError};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// a custom farm is read from this file in the working directory, when it exists.
+pub const MAP: &str = "map.json";
+
+/// characters for each kind of ground in a map's rows, and whether grass grows there.
+const TILES: [(char, ground::Ground, bool); 6] = [
+    ('.', ground::Ground::Dirt, false),
+    (',', ground::Ground::Moss, false),
+    ('=', ground::Ground::Path, false),
+    ('~', ground::Ground::Water, false),
+    ('"', ground::Ground::Dirt, true),
+    (';', ground::Ground::Moss, true),
+];
+
+/// a farm's layout: its ground, where grass grows, the dome, and where everything starts out.
+/// the default farm is laid out from the game's seed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Map {
+    /// the ground a row of tiles at a time, a character per tile of 8 by 4 cells: '.' dirt,
+    /// ',' moss, '=' path and '~' water, with '"' and ';' for dirt and moss grown over with
+    /// grass.
+    pub ground: Vec<String>,
+    /// besides the grass drawn in, one in this many dirt and moss tiles grows grass, picked
+    /// at random. zero for none.
+    #[serde(default)]
+    pub grass_odds: u32,
+    /// the habitat dome. a farm without one is all open air.
+    #[serde(default)]
+    pub dome: Option<state::Dome>,
+    /// where the player, the tools and the machines start out, and where K turns up.
+    pub things: Vec<Placement>,
+    /// the edges that lead into other areas.
+    #[serde(default)]
+    pub exits: Vec<Exit>,
+}
+
+/// a thing on the map, and the cell its sprite's top left corner starts on.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Placement {
+    pub thing: Thing,
+    pub at: (i64, i64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Thing {
+    /// where the player starts, and wakes up after running out of oxygen.
+    Player,
+    Pod,
+    Terminal,
+    Compost,
+    Robot,
+    Tap,
+    Shovel,
+    Packet,
+    Watercan,
+    /// where K wanders to after arriving from the top of the farm.
+    K,
+    /// where K leaves their seed packet.
+    Gift,
+}
+
+/// a side of an area.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Edge {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+/// a way out of an area: walking off 'edge' leads into 'to', arriving at its opposite edge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Exit {
+    pub edge: Edge,
+    pub to: scene::Area,
+}
+
+impl Map {
+    /// the default farm: dirt with patches of moss and grass, the dome two thirds of the way
+    /// across with an airlock near the middle, and everything else close to the cryopod.
+    pub fn generate(world: (u16, u16), rng: &mut impl Rng) -> Map {
+        let dome = state::Dome {
+            x: (world.0 as i64 * 2 / 3) / 8 * 8,
+            airlock: (
+                (world.1 as i64 / 2 - 4) / 4 * 4,
+                (world.1 as i64 / 2 - 4) / 4 * 4 + 8,
+            ),
+        };
+        let tiles = ground::GroundMap::generate(world, &dome, rng);
+        let ground = (0..tiles.rows)
+            .map(|row| {
+                (0..tiles.cols)
+                    .map(|col| {
+                        let ground = tiles.get(col, row);
+                        TILES.iter().find(|t| t.1 == ground).map_or('.', |t| t.0)
+                    })
+                    .collect()
+            })
+            .collect();
+        let (w, h) = (world.0 as i64, world.1 as i64);
+        let things = [
+            (Thing::Tap, (dome.x + 8, dome.airlock.1)),
+            (Thing::Pod, (1, 0)),
+            (Thing::Terminal, (11, 2)),
+            (Thing::Compost, (20, 2)),
+            (Thing::Robot, (30, 3)),
+            (Thing::Player, (5, h / 2 - 2)),
+            (Thing::Shovel, (2, h - 7)),
+            (Thing::Packet, (8, h - 4)),
+            (Thing::Watercan, (8, h - 8)),
+            (Thing::K, (w / 2, h / 2)),
+            (Thing::Gift, (w / 2, h / 2)),
+        ];
+        Map {
+            ground,
+            grass_odds: 4,
+            dome: Some(dome),
+            things: things
+                .into_iter()
+                .map(|(thing, at)| Placement { thing, at })
+                .collect(),
+            exits: vec![Exit {
+                edge: Edge::Left,
+                to: scene::Area::Habitat,
+            }],
+        }
+    }
+
+    /// the habitat's living quarters, through the farm's left edge: a floor of paths around a
+    /// mossy bed with a water tank, and the way back out on the right.
+    pub fn habitat() -> Map {
+        let ground = [
+            "========", "==,,,,==", "=,,~~,,=", "=,,~~,,=", "==,,,,==", "========",
+        ];
+        Map {
+            ground: ground.iter().map(|row| row.to_string()).collect(),
+            grass_odds: 0,
+            dome: None,
+            things: vec![Placement {
+                thing: Thing::Player,
+                at: (52, 10),
+            }],
+            exits: vec![Exit {
+                edge: Edge::Right,
+                to: scene::Area::Farm,
+            }],
+        }
+    }
+
+    /// load the map at 'path', or None if there isn't one.
+    pub fn load(path: &str) -> Result<Option<Map>, AppError> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let map: Map = serde_json::from_str(&text)?;
+        map.check().map_err(AppError::Map)?;
+        Ok(Some(map))
+    }
+
+    /// what's wrong with the map, if anything: rows of different lengths, unknown tiles, a
+    /// farm smaller than settings::MIN_WORLD, or no player.
+    pub fn check(&self) -> Result<(), String> {
+        let cols = self.ground.first().map_or(0, |row| row.chars().count());
+        if let Some(row) = self.ground.iter().position(|r| r.chars().count() != cols) {
+            return Err(format!("row {} isn't {} tiles long", row + 1, cols));
+        }
+        for (row, line) in self.ground.iter().enumerate() {
+            if let Some(c) = line.chars().find(|c| TILES.iter().all(|t| t.0 != *c)) {
+                return Err(format!("row {} has an unknown tile '{}'", row + 1, c));
+            }
+        }
+        let size = self.size();
+        if size.0 < settings::MIN_WORLD.0 || size.1 < settings::MIN_WORLD.1 {
+            return Err(format!(
+                "the farm is {}x{} cells, smaller than {}x{}",
+                size.0,
+                size.1,
+                settings::MIN_WORLD.0,
+                settings::MIN_WORLD.1
+            ));
+        }
+        if self.place(Thing::Player).is_none() {
+            return Err("there's no player".to_string());
+        }
+        Ok(())
+    }
+
+    /// the farm's size in cells.
+    pub fn size(&self) -> (u16, u16) {
+        let cols = self.ground.first().map_or(0, |row| row.chars().count());
+        (
+            (cols as i64 * ground::TILE.0) as u16,
+            (self.ground.len() as i64 * ground::TILE.1) as u16,
+        )
+    }
+
+    /// the ground under the whole farm.
+    pub fn ground_map(&self) -> ground::GroundMap {
+        let cols = self.ground.first().map_or(0, |row| row.chars().count()) as i64;
+        let tiles = self
+            .ground
+            .iter()
+            .flat_map(|row| row.chars())
+            .map(|c| {
+                TILES
+                    .iter()
+                    .find(|t| t.0 == c)
+                    .map_or(ground::Ground::Dirt, |t| t.1)
+            })
+            .collect();
+        ground::GroundMap::new(cols, tiles)
+    }
+
+    /// whether grass is drawn in on a tile.
+    pub fn grass(&self, col: i64, row: i64) -> bool {
+        let c = usize::try_from(row)
+            .ok()
+            .and_then(|row| self.ground.get(row))
+            .zip(usize::try_from(col).ok())
+            .and_then(|(line, col)| line.chars().nth(col));
+        TILES.iter().any(|t| Some(t.0) == c && t.2)
+    }
+
+    /// the dome, or one past the edge of the farm when there isn't one, so everywhere is inside.
+    pub fn dome(&self) -> state::Dome {
+        self.dome.unwrap_or(state::Dome {
+            x: self.size().0 as i64 + ground::TILE.0,
+            airlock: (0, 0),
+        })
+    }
+
+    /// where 'thing' starts out, if it's on the map.
+    pub fn place(&self, thing: Thing) -> Option<(i64, i64)> {
+        self.things.iter().find(|p| p.thing == thing).map(|p| p.at)
+    }
+}
+
+/// print the default farm for a seed as a map, to start a custom one from:
+/// `qffp map [seed] > map.json`.
+pub fn run(args: &[String]) -> Result<(), AppError> {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    let seed = match args.first() {
+        Some(seed) => seed
+            .parse()
+            .map_err(|_| AppError::Usage("qffp map [seed]".to_string()))?,
+        None => rand::random(),
+    };
+    let world = settings::Settings::load(settings::SETTINGS)?.world_size();
+    let map = Map::generate(world, &mut StdRng::seed_from_u64(seed));
+    println!("{}", serde_json::to_string_pretty(&map)?);
+    Ok(())
+}
@@ -1,19 +1,73 @@
-use rand::Rng;
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use std::io::{self, Write as IOWrite};
 use std::path;
-use std::time::SystemTime;
-use std::{str, time::Duration};
+use std::str;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
 
-use crossterm::event::{poll, read, Event, KeyCode, KeyModifiers};
-use crossterm::{cursor, event, style, terminal, QueueableCommand};
 use specs::{Builder, Dispatcher, DispatcherBuilder, World, WorldExt};
 use thiserror::Error;
 
-use anathema::display;
+use serde::{Deserialize, Serialize};
 
-mod render;
-mod sprite;
-mod state;
+mod assets;
+#[cfg(feature = "audio")]
+mod audio;
+pub mod challenge;
+pub mod cues;
+mod daycycle;
+mod dialogue;
+pub mod display;
+mod ending;
+pub mod export;
+mod grid;
+pub mod ground;
+mod irrigation;
+mod keys;
+pub mod map;
+pub mod net;
+#[cfg(feature = "notifications")]
+mod notifications;
+pub mod pack;
+mod pathfinding;
+mod power;
+#[cfg(feature = "discord")]
+mod presence;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod preview;
+pub mod render;
+pub mod replay;
+mod robot;
+pub mod routine;
+mod savefile;
+pub mod scene;
+#[cfg(not(target_arch = "wasm32"))]
+mod session;
+pub mod settings;
+mod shop;
+pub mod sounds;
+pub mod sprite;
+#[cfg(feature = "ssh")]
+pub mod ssh;
+pub mod state;
+mod story;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod telnet;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod terminal;
+pub mod timer;
+mod ui;
+pub mod validate;
+mod weather;
+#[cfg(target_arch = "wasm32")]
+mod web;
+
+/// the game updates in fixed steps of this many milliseconds of real time, however often
+/// frames come, so everything timed plays out the same on any terminal.
+pub const STEP: u64 = 20;
+
+/// the most steps run at once to catch up, so a stall doesn't snowball into a longer one.
+const MAX_STEPS: u64 = 10;
 
 #[derive(Error, Debug)]
 pub enum AppError {
@@ -25,11 +79,33 @@ pub enum AppError {
     Serde(#[from] serde_json::Error),
     #[error("image error")]
     Image(#[from] image::ImageError),
-    #[error("spritestore error")]
+    #[error("{0}")]
     SpriteStore(#[from] sprite::SpriteStoreError),
+    #[cfg(feature = "dev")]
+    #[error("file watch error")]
+    Notify(#[from] notify::Error),
+    #[cfg(feature = "ssh")]
+    #[error("ssh error")]
+    Ssh(#[from] russh::Error),
+    #[error("usage: {0}")]
+    Usage(String),
+    #[error("{0} problem(s) found")]
+    Validation(usize),
+    #[error("routines line {0}: {1}")]
+    Routine(usize, String),
+    #[error("map: {0}")]
+    Map(String),
+    #[error("dialogue: {0}")]
+    Dialogue(String),
+    #[error("replay doesn't match: {0}")]
+    Mismatch(String),
+    #[error("{0} is damaged")]
+    Damaged(String),
+    #[error("this console can't show escape sequences; try windows terminal")]
+    Console,
 }
 
-#[derive(PartialEq, PartialOrd, Clone, Copy)]
+#[derive(PartialEq, PartialOrd, Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum InputState {
     Up,
     Down,
@@ -42,128 +118,644 @@ pub enum InputState {
     Pickup,
     Action,
     ToggleHelp,
+    CycleSpeed,
+    /// turn the music, sound effects and bell cues off, or back on.
+    Mute,
+    Undo,
+    Craft,
+    Hop,
+    /// pick out the next tool in reach, when several are close together.
+    NextTarget,
+    /// a left click on the world cell at (x, y): walking there, or picking up the tool or item
+    /// clicked on. frontends report the screen cell, and set_input moves it into the world.
+    Click(i64, i64),
     Quit,
     Clear,
+    /// a character typed into a text box, and a deletion from it. keys only arrive as text
+    /// while a text box has focus; otherwise they're the commands above.
+    Char(char),
+    Backspace,
     None,
 }
 
-pub struct Input(pub InputState);
+/// input for each player slot, passed to specs systems. slot 0 is always the local player.
+#[derive(Default)]
+pub struct Input(pub Vec<InputState>);
 
-impl Default for Input {
-    fn default() -> Self {
-        Input(InputState::None)
+impl Input {
+    pub fn slot(&self, slot: usize) -> InputState {
+        self.0.get(slot).copied().unwrap_or(InputState::None)
     }
 }
 
+/// a terminal a game can be played on: the one this process runs in, a remote session, or a
+/// browser. the game loop reads input through it, and draws to the writer given to the App.
+pub trait Frontend {
+    /// wait up to 'ms' milliseconds for one input. a resize is reported as InputState::Clear.
+    /// while 'typing', keys are read as text for a text box: enter is Action, escape is Pickup.
+    fn read_input(&mut self, ms: u64, typing: bool) -> Result<InputState, AppError>;
+
+    /// the terminal's size in cells.
+    fn size(&self) -> Result<(u16, u16), AppError>;
+}
+
 pub struct App<'a> {
-    time_start: SystemTime,
+    #[cfg(not(target_arch = "wasm32"))]
+    last_update: Option<Instant>,
+    /// real time not yet played out in a step.
+    accumulator: u64,
     time: u64,
+    time_fraction: f64,
     world: World,
     dispatcher: Dispatcher<'a, 'a>,
+    #[cfg(feature = "dev")]
+    sheet_watcher: Option<sprite::SheetWatcher>,
+    host: Option<net::Host>,
+    guest: Option<specs::Entity>,
+    guest_input: InputState,
+    #[cfg(feature = "discord")]
+    presence: Option<presence::PresenceSystem>,
+    #[cfg(feature = "notifications")]
+    notifications: Option<notifications::NotificationSystem>,
+    #[cfg(feature = "audio")]
+    audio: Option<audio::AudioSystem>,
+    recording: Option<replay::Replay>,
+    /// the seed the farm was laid out from, kept with its save.
+    seed: u64,
     pub input: InputState,
 }
 
 impl<'a> App<'a> {
-    /// gather key input. 'ms' is the poll duration in milliseconds.
-    pub fn process_input(&mut self, ms: u64) -> Result<InputState, AppError> {
-        let mut input: InputState = InputState::None;
-        if poll(Duration::from_millis(ms))? {
-            match read()? {
-                Event::FocusGained => {}
-                Event::FocusLost => {}
-                Event::Key(event) => 'key: {
-                    if event.kind != event::KeyEventKind::Press {
-                        input = InputState::None;
-                        break 'key;
-                    }
-                    let code = event.code;
-                    let mods = event.modifiers;
-
-                    // quit command
-                    if (code == KeyCode::Char('c') && mods == KeyModifiers::CONTROL)
-                        || code == KeyCode::Char('q')
-                    {
-                        input = InputState::Quit;
-                    }
-                    // movement commands
-                    else if code == KeyCode::Left
-                        || code == KeyCode::Char('h')
-                        || code == KeyCode::Char('H')
-                    {
-                        if mods == KeyModifiers::SHIFT {
-                            input = InputState::ShiftLeft;
-                        } else {
-                            input = InputState::Left;
-                        }
-                    } else if code == KeyCode::Right
-                        || code == KeyCode::Char('l')
-                        || code == KeyCode::Char('L')
-                    {
-                        if mods == KeyModifiers::SHIFT {
-                            input = InputState::ShiftRight;
-                        } else {
-                            input = InputState::Right;
-                        }
-                    } else if code == KeyCode::Down
-                        || code == KeyCode::Char('j')
-                        || code == KeyCode::Char('J')
-                    {
-                        if mods == KeyModifiers::SHIFT {
-                            input = InputState::ShiftDown;
-                        } else {
-                            input = InputState::Down;
-                        }
-                    } else if code == KeyCode::Up
-                        || code == KeyCode::Char('k')
-                        || code == KeyCode::Char('K')
-                    {
-                        if mods == KeyModifiers::SHIFT {
-                            input = InputState::ShiftUp;
-                        } else {
-                            input = InputState::Up;
-                        }
-                    }
-                    // other commands
-                    else if code == KeyCode::Char('u') {
-                        input = InputState::Action;
-                    } else if code == KeyCode::Char(' ') {
-                        input = InputState::Pickup;
-                    } else if code == KeyCode::Char('?') {
-                        input = InputState::ToggleHelp;
-                    } else {
-                        input = InputState::None;
-                    }
-                }
-                Event::Mouse(_event) => {}
-                Event::Paste(_data) => {}
-                Event::Resize(_width, _height) => {
-                    input = InputState::Clear;
+    /// gather key input from 'frontend'. 'ms' is the poll duration in milliseconds.
+    pub fn process_input(
+        &mut self,
+        frontend: &mut impl Frontend,
+        ms: u64,
+    ) -> Result<InputState, AppError> {
+        let typing = self.world.read_resource::<state::Game>().reply.is_some();
+        let input = frontend.read_input(ms, typing)?;
+        if input == InputState::Clear {
+            self.resize(frontend.size()?);
+        }
+        self.set_input(input);
+        Ok(input)
+    }
+
+    /// play until the player quits, reading input from 'frontend'.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn run(&mut self, frontend: &mut impl Frontend) -> Result<(), AppError> {
+        'main: loop {
+            // wait for input until the next step is due. a key read early waits for that step
+            let since = self
+                .last_update
+                .map_or(0, |last| last.elapsed().as_millis() as u64);
+            let wait = STEP.saturating_sub(self.accumulator + since);
+            let input = if self.input == InputState::None {
+                self.process_input(frontend, wait)?
+            } else {
+                std::thread::sleep(std::time::Duration::from_millis(wait));
+                self.input
+            };
+            if input == InputState::Quit {
+                break 'main;
+            }
+            self.update()?;
+        }
+        Ok(())
+    }
+
+    /// advance the game by one tick of 'dt' milliseconds, with 'input' from the local player,
+    /// and return the frame drawn. lets other programs drive the game: bots, tutorials,
+    /// fuzzers, or frontends of their own.
+    pub fn step(&mut self, input: InputState, dt: u64) -> Result<display::Frame, AppError> {
+        if !self.world.has_value::<display::Frame>() {
+            self.world.insert(display::Frame::default());
+        }
+        self.set_input(input);
+        self.tick(dt)?;
+        Ok((*self.world.read_resource::<display::Frame>()).clone())
+    }
+
+    /// the specs world the game runs on: its entities, with components from state, and
+    /// resources such as the sprite::SpriteStore and the state::Game.
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    /// the world, to change between steps.
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    /// set the local player's input for the next update, for input that doesn't come from
+    /// this process's terminal. a click is given in screen cells.
+    pub fn set_input(&mut self, input: InputState) {
+        self.input = match input {
+            InputState::Click(x, y) => {
+                let camera = self.world.read_resource::<state::Camera>();
+                InputState::Click(x + camera.0, y + camera.1)
+            }
+            input => input,
+        };
+    }
+
+    /// write the sound map's terminal cues, by default ringing the bell for new messages and
+    /// blooming flowers.
+    pub fn enable_bell(&mut self) {
+        let cues = cues::Cues::from_sounds(&self.world.read_resource::<sounds::SoundMap>());
+        self.world.insert(cues);
+    }
+
+    /// play the daily challenge for 'date'. the app should be seeded with challenge::seed.
+    pub fn enable_challenge(&mut self, date: challenge::Date) {
+        self.world.insert(challenge::Challenge::new(date));
+    }
+
+    /// show a speedrun timer, comparing splits to the best run in the profile. a damaged
+    /// profile starts the timer with no records, and asks the player whether to restore it.
+    pub fn enable_timer(&mut self) -> Result<(), AppError> {
+        let profile = match timer::Profile::load(timer::PROFILE) {
+            Err(AppError::Damaged(path)) => {
+                self.ask_to_recover(&path);
+                timer::Profile::default()
+            }
+            profile => profile?,
+        };
+        self.world.insert(timer::Timer::new(profile));
+        Ok(())
+    }
+
+    /// ask the local player what to do with the damaged save file at 'path', after any asked
+    /// about before it.
+    fn ask_to_recover(&mut self, path: &str) {
+        self.world
+            .write_resource::<savefile::Recovery>()
+            .0
+            .push(savefile::Damaged::new(path));
+    }
+
+    /// whether the save file at 'path' is damaged, waiting on the local player's choice.
+    fn recovering(&self, path: &str) -> bool {
+        self.world
+            .read_resource::<savefile::Recovery>()
+            .0
+            .iter()
+            .any(|damaged| damaged.path == path)
+    }
+
+    /// record the local player's input from now on, for a game started with 'seed'.
+    pub fn record(&mut self, seed: u64) {
+        let size = *self.world.read_resource::<state::ScreenSize>();
+        self.recording = Some(replay::Replay {
+            seed,
+            size: (size.0, size.1),
+            settings: (*self.world.read_resource::<settings::Settings>()).clone(),
+            routines: std::fs::read_to_string(routine::ROUTINES).unwrap_or_default(),
+            ticks: vec![],
+            time: None,
+            hash: None,
+        });
+    }
+
+    /// the session recorded since record was called, ending with the speedrun timer's time and
+    /// the world's state as it is now.
+    pub fn take_recording(&mut self) -> Option<replay::Replay> {
+        let mut recording = self.recording.take()?;
+        recording.time = self
+            .world
+            .try_fetch::<timer::Timer>()
+            .and_then(|timer| timer.finished);
+        recording.hash = Some(replay::state_hash(&self.world));
+        Some(recording)
+    }
+
+    /// save the farm to state::SAVE: the story so far, the pocket, the weather, the local
+    /// player and every sprite with an id. the rest is laid out again from the seed on loading.
+    /// a damaged save is left as it is until the player has decided what to do with it.
+    pub fn save(&self) -> Result<(), AppError> {
+        use specs::Join;
+
+        if self.recovering(state::SAVE) {
+            return Ok(());
+        }
+
+        let game = self.world.read_resource::<state::Game>();
+        // messages are saved by id, so the dialogue file can change between sessions
+        let id = |index: usize| game.dialogue.messages[index].id.clone();
+        let weather = self.world.read_resource::<weather::Weather>();
+        let scenes = self.world.read_resource::<scene::SceneManager>();
+        // the farm's size, even when the players are elsewhere
+        let world = match scenes.away.get(&scene::Area::Farm) {
+            Some(farm) => farm.size,
+            None => *self.world.read_resource::<state::WorldSize>(),
+        };
+        let sprites = self.world.read_storage::<state::Sprite>();
+        let positions = self.world.read_storage::<state::Position>();
+        let players = self.world.read_storage::<state::Player>();
+
+        let player = (&sprites, &positions, &players)
+            .join()
+            .find(|(_, _, player)| player.slot == 0)
+            .map(|(sprite, pos, player)| state::SavedPlayer {
+                pos: (pos.x, pos.y),
+                flip: sprite.flip,
+                holding: player.holding,
+            });
+        let saved = scene::save_sprites(&self.world, |_, sprite| {
+            sprite.id != 0
+                && !matches!(
+                    sprite.sprite_type,
+                    state::SpriteType::Player | state::SpriteType::Particle
+                )
+        });
+
+        state::SaveGame {
+            seed: self.seed,
+            world: (world.0, world.1),
+            next_id: self.world.read_resource::<state::SpriteIndexer>().0,
+            day: game.day,
+            terminal_message: id(game.terminal_message_index),
+            terminal_read: game.terminal_read,
+            history: game.history.iter().map(|i| id(*i)).collect(),
+            read: game.read.iter().map(|i| id(*i)).collect(),
+            replies: game
+                .replies
+                .iter()
+                .map(|(i, reply)| (id(*i), reply.clone()))
+                .collect(),
+            flags: game.flags.clone(),
+            hearts: game.hearts,
+            blooms: game.blooms,
+            ignored: game.ignored,
+            seeds: game.seeds,
+            seeds2: game.seeds2,
+            sowing: game.sowing,
+            fiber: game.fiber,
+            fertilizer: game.fertilizer,
+            leaves: game.leaves,
+            flowers: game.flowers,
+            money: self.world.read_resource::<shop::Money>().0,
+            weather: (weather.today, weather.tomorrow, weather.dry_days),
+            power: self.world.read_resource::<power::Power>().stored,
+            player,
+            sprites: saved,
+            area: scenes.area,
+            away: scenes.saved_away(),
+        }
+        .save(state::SAVE)
+    }
+
+    /// pick the farm up from 'save', replacing every sprite with an id with the saved ones.
+    /// the app should have been laid out from the save's seed. sprites whose sheet is gone
+    /// are left out.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn load(&mut self, save: &state::SaveGame) {
+        use specs::Join;
+
+        {
+            let game = &mut *self.world.write_resource::<state::Game>();
+            game.day = save.day;
+            // messages no longer in the dialogue file are taken for the first one
+            let index = |id: &String| game.dialogue.index(id).unwrap_or(0);
+            game.terminal_message_index = index(&save.terminal_message);
+            game.terminal_read = save.terminal_read;
+            game.history = save.history.iter().map(index).collect();
+            game.read = save.read.iter().map(index).collect();
+            game.replies = save
+                .replies
+                .iter()
+                .map(|(saved, reply)| (index(saved), reply.clone()))
+                .collect();
+            game.flags = save.flags.clone();
+            game.hearts = save.hearts;
+            game.blooms = save.blooms;
+            game.ignored = save.ignored;
+            game.seeds = save.seeds;
+            game.seeds2 = save.seeds2;
+            game.sowing = save.sowing;
+            game.fiber = save.fiber;
+            game.fertilizer = save.fertilizer;
+            game.leaves = save.leaves;
+            game.flowers = save.flowers;
+        }
+        self.world.insert(shop::Money(save.money));
+        // the day's morning has already been seen to, so nothing runs again on loading
+        {
+            let mut weather = self.world.write_resource::<weather::Weather>();
+            (weather.today, weather.tomorrow, weather.dry_days) = save.weather;
+            weather.day = save.day;
+        }
+        {
+            let mut power = self.world.write_resource::<power::Power>();
+            power.stored = save.power;
+            power.day = save.day;
+        }
+        self.world.write_resource::<irrigation::Irrigation>().day = save.day;
+
+        // a save made away from the farm is picked up in that area, with the farm laid out
+        // from the seed waiting behind it
+        if save.area != scene::Area::Farm {
+            scene::enter(&mut self.world, save.area, self.seed);
+        }
+        scene::replace_sprites(&mut self.world, &save.sprites);
+        self.world
+            .write_resource::<scene::SceneManager>()
+            .load_away(&save.away);
+
+        if let Some(saved) = &save.player {
+            let (mut sprites, mut positions, mut players) = (
+                self.world.write_storage::<state::Sprite>(),
+                self.world.write_storage::<state::Position>(),
+                self.world.write_storage::<state::Player>(),
+            );
+            for (sprite, pos, player) in (&mut sprites, &mut positions, &mut players).join() {
+                if player.slot == 0 {
+                    sprite.flip = saved.flip;
+                    (pos.x, pos.y) = saved.pos;
+                    player.holding = saved.holding;
+                    player.air_until = self.time + state::OXYGEN_TIME;
                 }
             }
         }
-        self.input = input;
-        Ok(input)
+        let mut si = self.world.write_resource::<state::SpriteIndexer>();
+        si.0 = si.0.max(save.next_id);
+    }
+
+    /// pick up the save once the player has decided what to do with it damaged. a save
+    /// restored from its backup replaces the farm, laid out again from the save's seed with the
+    /// players staying where they are; after starting over, the farm laid out already is kept.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn recover(&mut self) -> Result<(), AppError> {
+        use specs::Join;
+
+        let save = match state::SaveGame::load(state::SAVE) {
+            Ok(Some(save)) => save,
+            Ok(None) => return Ok(()),
+            // the backup couldn't be put in its place, so the player is asked again
+            Err(AppError::Damaged(path)) => {
+                self.ask_to_recover(&path);
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        };
+        let map = map::Map::load(map::MAP)?;
+        let left: Vec<specs::Entity> = (
+            &self.world.entities(),
+            &self.world.read_storage::<state::Sprite>(),
+            !&self.world.read_storage::<state::Player>(),
+        )
+            .join()
+            .map(|(e, _, _)| e)
+            .collect();
+        let _ = self.world.delete_entities(&left);
+        self.world.insert(scene::SceneManager::default());
+        self.world.insert(state::Undo::default());
+        self.world.insert(state::Game::new(dialogue::Dialogue::load(
+            dialogue::DIALOGUE,
+        )?));
+
+        self.seed = save.seed;
+        let mut rng = StdRng::seed_from_u64(save.seed);
+        let map = map.unwrap_or_else(|| map::Map::generate(save.world, &mut rng));
+        scene::lay_out(&mut self.world, map.size(), &map, &mut rng);
+        self.world
+            .insert(state::GameRng(StdRng::seed_from_u64(rng.gen())));
+        self.load(&save);
+
+        // the players were laid out before the save's sprites, so their ids move clear of them
+        let mut si = self.world.write_resource::<state::SpriteIndexer>();
+        let (mut sprites, players) = (
+            self.world.write_storage::<state::Sprite>(),
+            self.world.read_storage::<state::Player>(),
+        );
+        for (sprite, _) in (&mut sprites, &players).join() {
+            sprite.id = si.new_index();
+        }
+        self.world.write_resource::<render::Changes>().redraw = true;
+        Ok(())
+    }
+
+    /// the keyboard layout in the settings, for the frontend reading keys.
+    pub fn layout(&self) -> settings::Layout {
+        self.world.read_resource::<settings::Settings>().layout
     }
 
-    // create a new App instance.
-    pub fn new() -> Result<App<'a>, AppError> {
-        let sz = terminal::size()?;
+    /// draw with 'mode' from now on.
+    pub fn set_render_mode(&mut self, mode: display::RenderMode) {
+        self.world.insert(mode);
+    }
+
+    /// change the size of the screen the game is rendered to. only the view changes: the camera
+    /// is fitted to the new size right away, and nothing in the world moves.
+    pub fn resize(&mut self, size: (u16, u16)) {
+        let screen = state::ScreenSize(size.0, size.1);
+        *self.world.write_resource::<state::ScreenSize>() = screen;
+        let world = *self.world.read_resource::<state::WorldSize>();
+        let focus = state::player_focus(
+            0,
+            &self.world.read_storage::<state::Player>(),
+            &self.world.read_storage::<state::Position>(),
+            &self.world.read_storage::<state::Motion>(),
+        );
+        if let Some(focus) = focus {
+            *self.world.write_resource::<state::Camera>() =
+                state::Camera::follow(focus, screen, world);
+        }
+    }
+
+    // create a new App instance, playing in this process's terminal. the mouse is only captured
+    // if both 'mouse' and the settings allow it.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn new(seed: Option<u64>, mouse: bool) -> Result<App<'a>, AppError> {
+        let mut settings = settings::Settings::load(settings::SETTINGS)?;
+        settings.mouse &= mouse;
+        let mouse = settings.mouse;
+        // a seeded game is played from scratch on the default farm, without touching the save.
+        // a damaged save is kept until the player decides whether to restore its backup, with a
+        // new farm laid out for the meantime
+        let mut damaged = None;
+        let (save, map) = match seed {
+            Some(_) => (None, None),
+            None => {
+                let save = match state::SaveGame::load(state::SAVE) {
+                    Err(AppError::Damaged(path)) => {
+                        damaged = Some(path);
+                        None
+                    }
+                    save => save?,
+                };
+                (save, map::Map::load(map::MAP)?)
+            }
+        };
+        let seed = save.as_ref().map_or(seed, |save| Some(save.seed));
+        let mut app = match &map {
+            Some(map) => App::with_map(terminal::size()?, map, Box::new(io::stdout()), seed)?,
+            None => App::with_world(
+                terminal::size()?,
+                save.as_ref()
+                    .map_or(settings.world_size(), |save| save.world),
+                Box::new(io::stdout()),
+                seed,
+            )?,
+        };
+        if let Some(save) = &save {
+            app.load(save);
+        }
+        if let Some(path) = damaged {
+            app.ask_to_recover(&path);
+        }
+        if settings.bell {
+            app.enable_bell();
+        }
+        if settings.timer {
+            app.enable_timer()?;
+        }
+        app.world.insert(settings);
+        app.world
+            .insert(routine::Routines::load(routine::ROUTINES)?);
 
+        // desktop integrations follow only the local player's own game, not sessions served
+        // to others
+        #[cfg(feature = "discord")]
+        {
+            app.presence = presence::PresenceSystem::from_env();
+        }
+        #[cfg(feature = "notifications")]
+        {
+            app.notifications = Some(notifications::NotificationSystem);
+        }
+        #[cfg(feature = "audio")]
+        {
+            app.audio = audio::AudioSystem::new(&app.world.read_resource::<sounds::SoundMap>());
+        }
+        terminal::enter_terminal(mouse)?;
+        Ok(app)
+    }
+
+    /// create a new App instance that renders to 'out', a terminal of size 'sz'. the caller is
+    /// responsible for preparing that terminal and feeding input with set_input. a 'seed' lays
+    /// the farm out the same way every time; None picks a random one.
+    pub fn with_output(
+        sz: (u16, u16),
+        out: Box<dyn IOWrite + Send>,
+        seed: Option<u64>,
+    ) -> Result<App<'a>, AppError> {
+        let world = settings::Settings::default().world_size();
+        App::with_world(sz, world, out, seed)
+    }
+
+    /// like with_output, with a farm of 'world' cells instead of the default size.
+    pub fn with_world(
+        sz: (u16, u16),
+        world: (u16, u16),
+        out: Box<dyn IOWrite + Send>,
+        seed: Option<u64>,
+    ) -> Result<App<'a>, AppError> {
+        let seed = seed.unwrap_or_else(rand::random);
+        let mut rng = StdRng::seed_from_u64(seed);
+        let map = map::Map::generate(world, &mut rng);
+        App::build(sz, world, &map, out, seed, rng)
+    }
+
+    /// like with_output, on the farm laid out in 'map'. the 'seed' still picks the looks of the
+    /// dirt and grass, and everything else left to chance.
+    pub fn with_map(
+        sz: (u16, u16),
+        map: &map::Map,
+        out: Box<dyn IOWrite + Send>,
+        seed: Option<u64>,
+    ) -> Result<App<'a>, AppError> {
+        let seed = seed.unwrap_or_else(rand::random);
+        App::build(sz, map.size(), map, out, seed, StdRng::seed_from_u64(seed))
+    }
+
+    /// an app on 'map', in a world of 'world' cells, carrying on with 'rng' from where laying
+    /// the map out left it.
+    fn build(
+        sz: (u16, u16),
+        world: (u16, u16),
+        map: &map::Map,
+        out: Box<dyn IOWrite + Send>,
+        seed: u64,
+        mut rng: StdRng,
+    ) -> Result<App<'a>, AppError> {
         // create initial app and register specs systems
         let mut app = App {
-            time_start: SystemTime::now(),
+            #[cfg(not(target_arch = "wasm32"))]
+            last_update: None,
+            accumulator: 0,
             time: 0,
+            time_fraction: 0.0,
             world: World::new(),
             dispatcher: DispatcherBuilder::new()
-                .with(state::UpdateGameState, "game_state", &[])
+                .with(savefile::RecoverySystem, "recovery", &[])
+                .with(ending::CutsceneSystem, "cutscene", &[])
+                .with(grid::GridSystem, "grid", &[])
+                .with(
+                    state::UpdateGameState,
+                    "game_state",
+                    &["recovery", "cutscene", "grid"],
+                )
+                .with(challenge::ChallengeSystem, "challenge", &["game_state"])
+                .with(timer::TimerSystem, "timer", &["game_state"])
+                .with(power::PowerSystem, "power", &["game_state"])
+                .with(weather::WeatherSystem, "weather", &["game_state"])
+                .with(daycycle::DayCycleSystem, "day_cycle", &["game_state"])
+                .with(
+                    irrigation::IrrigationSystem,
+                    "irrigation",
+                    &["game_state", "weather"],
+                )
+                .with(robot::RobotSystem, "robot", &["game_state", "power"])
+                .with(ground::ShimmerSystem, "shimmer", &["game_state"])
+                .with(state::TintSystem, "tint", &["game_state", "robot"])
+                .with(state::MotionSystem, "motion", &["game_state", "robot"])
                 .with(
-                    render::RenderBuffer {
-                        screen: display::Screen::new(io::stdout(), sz)?,
-                    },
+                    state::CleanupSystem,
+                    "cleanup",
+                    &[
+                        "game_state",
+                        "challenge",
+                        "timer",
+                        "power",
+                        "robot",
+                        "weather",
+                        "irrigation",
+                        "shimmer",
+                    ],
+                )
+                .with(
+                    render::RenderBuffer::new(sz, out),
                     "render_buffer",
-                    &["game_state"],
+                    &[
+                        "game_state",
+                        "challenge",
+                        "timer",
+                        "power",
+                        "robot",
+                        "weather",
+                        "day_cycle",
+                        "irrigation",
+                        "shimmer",
+                        "tint",
+                        "motion",
+                    ],
                 )
                 .build(),
+            // hot reloading is a convenience for artists, so the game runs fine without it
+            #[cfg(feature = "dev")]
+            sheet_watcher: sprite::SheetWatcher::new().ok(),
+            host: None,
+            guest: None,
+            guest_input: InputState::None,
+            #[cfg(feature = "discord")]
+            presence: None,
+            #[cfg(feature = "notifications")]
+            notifications: None,
+            #[cfg(feature = "audio")]
+            audio: None,
+            recording: None,
+            seed,
             input: InputState::None,
         };
 
@@ -172,301 +764,95 @@ impl<'a> App<'a> {
         app.world.register::<state::Interactible>();
         app.world.register::<state::Position>();
         app.world.register::<state::Npc>();
+        app.world.register::<state::Player>();
+        app.world.register::<state::Compost>();
+        app.world.register::<state::Fertilized>();
+        app.world.register::<state::Thirsty>();
+        app.world.register::<state::Moisture>();
+        app.world.register::<state::Teleporter>();
+        app.world.register::<state::Hop>();
+        app.world.register::<state::RenderLayer>();
+        app.world.register::<state::Falling>();
+        app.world.register::<state::Tint>();
+        app.world.register::<state::Motion>();
+        app.world.register::<state::Animation>();
+        app.world.register::<state::AnimationController>();
+        app.world.register::<state::Machine>();
+        app.world.register::<state::Collider>();
+        app.world.register::<robot::Robot>();
 
         // insert specs resources
+        app.world.insert(render::Changes::new(&app.world));
         app.world.insert(state::SpriteIndexer(0));
         app.world.insert(state::Time(0));
-        app.world.insert(Input(InputState::None));
+        app.world.insert(state::RealTime(0));
+        app.world.insert(state::Interpolation(0.0));
+        app.world.insert(state::ScreenSize(sz.0, sz.1));
+        app.world.insert(state::Camera::default());
+        app.world.insert(display::RenderMode::default());
+        app.world.insert(Input(vec![InputState::None]));
+        app.world.insert(state::Events::default());
+        app.world.insert(weather::Weather::default());
+        app.world.insert(daycycle::DayCycle::default());
+        app.world.insert(grid::Grid::default());
+        app.world.insert(irrigation::Irrigation::default());
+        app.world.insert(state::Undo::default());
+        app.world.insert(power::Power::default());
+        app.world.insert(routine::Routines::default());
+        app.world.insert(savefile::Recovery::default());
+        app.world.insert(scene::SceneManager::default());
+        app.world.insert(ending::Cutscene::default());
+        app.world.insert(cues::Cues::default());
+        app.world.insert(settings::Settings::default());
+        app.world.insert(sounds::SoundMap::load(sounds::SOUNDS)?);
+        app.world.insert(shop::Shop::default());
+        app.world.insert(shop::Prices::load(shop::PRICES)?);
+        app.world.insert(shop::Money::default());
 
         // initialize sprite store with all sprite content
-        let store = sprite::SpriteStore::new(vec![
-            "res/sheets/character-00.json",
-            "res/sheets/character-01.json",
-            "res/sheets/tile-dirt.json",
-            "res/sheets/grass.json",
-            "res/sheets/tool-shovel.json",
-            "res/sheets/tool-watercan.json",
-            "res/sheets/tool-packet.json",
-            "res/sheets/tool-packet2.json",
-            "res/sheets/crop-empty.json",
-            "res/sheets/crop-leaf.json",
-            "res/sheets/crop-flower.json",
-            "res/sheets/cryopod.json",
-            "res/sheets/terminal.json",
-            "res/sheets/transition.json",
-            "res/sheets/particle-dirt.json",
-            "res/sheets/particle-water.json",
-            "res/sheets/particle-heart.json",
-        ])?;
-
-        let messages = vec![
-            "### Welcome to Luna!\nYou've chosen to farm. Feel free to get started.\nYou will find a shovel, watercan, and seed packet nearby.\nPlease rest in the provided sleeping pod as needed.\nPress 'u' again to mark this message as read and proceed.".to_string(),
-            "### Keep up the good work.\nIf you water your crops, they'll grow every day.".to_string(),
-            "New message...\n... > Hey babe! I'll be there soon!\nI can't wait to see your farm. And face. -K".to_string(),
-            "New message...\n... > Hey, I left you something.\nTry planting the seeds. -K".to_string(),
-            "### Unauthorized crops detected.\nCease illegal growth immediately,\nor face farming license revocation.".to_string(),
-            "New message...\n... > Aw, babe... you're actually growing them.\nRemember when we designed these crops together? -K".to_string(),
-            "### Crop authorization granted.\nApologies for our mistake, doctor.\nThe AI responsible has been gently reprimanded.".to_string(),
-            "New message...\n... > Okay, good news. I can't say how, but...\nI'll be there tomorrow! Grow anything nice yet? -K".to_string(),
-            "Special message intercepted...\n... > Hey, it's June. I hope you liked the demo.\nLove, peace, and pleasant farming to all who play this.\nWhatever you're struggling with, I believe in you.\nKeep up the good fight and we'll get through this together!".to_string(),
-            "### Farming sequence completed. Have fun!".to_string(),
-        ];
-
-        app.world.insert(state::Game::new(messages));
-
-        // initialize sprite indexer to give ids to sprites
-        let mut si = state::SpriteIndexer(0);
-
-        // spawn dirt and grass sprites
-        let mut rng = rand::thread_rng();
-        let dirt_frame_count = store.0[store.index_by_name("tile-dirt")?].data.frames.len();
-        let grass_frame_count = store.0[store.index_by_name("grass")?].data.frames.len();
-        for y in (0..sz.1).step_by(4) {
-            for x in (0..(sz.0)).step_by(8) {
-                let x = x as i64;
-                let y = y as i64;
-
-                // dirt tiles
-                app.world
-                    .create_entity()
-                    .with(state::Sprite {
-                        store_index: store.index_by_name("tile-dirt")?,
-                        frame: rng.gen_range(0..dirt_frame_count),
-                        flip: rng.gen_range(0..2) == 0,
-                        ..state::Sprite::default()
-                    })
-                    .with(state::Position {
-                        x,
-                        y,
-                        z: state::DEPTHS.ground,
-                    })
-                    .build();
-
-                if x % 16 == 0 && y % 8 == 0 {
-                    app.world
-                        .create_entity()
-                        .with(state::Sprite {
-                            store_index: store.index_by_name("transition")?,
-                            sprite_type: state::SpriteType::Overlay,
-                            animating: true,
-                            ..state::Sprite::default()
-                        })
-                        .with(state::Position {
-                            x,
-                            y,
-                            z: state::DEPTHS.overlay,
-                        })
-                        .build();
-                }
+        // a packed atlas replaces the individual sheets when present and up to date
+        let store = if pack::use_atlas() {
+            sprite::SpriteStore::new(vec![pack::ATLAS])?
+        } else {
+            sprite::SpriteStore::new(sprite::SHEETS.to_vec())?
+        };
+        let ids = sprite::SpriteIds::new(&store)?;
 
-                // grass tiles have a chance to spawn
-                if x < (sz.0 - 8).into() && y < (sz.1 - 4).into() && rng.gen_range(0..4) == 0 {
-                    let id = si.new_index();
-                    app.world
-                        .create_entity()
-                        .with(state::Sprite {
-                            id, // grass has an id because it's interactive
-                            store_index: store.index_by_name("grass")?,
-                            frame: rng.gen_range(0..grass_frame_count),
-                            flip: rng.gen_range(0..2) == 0,
-                            animating: true,
-                            sprite_type: state::SpriteType::Crop,
-                            ..state::Sprite::default()
-                        })
-                        .with(state::Position {
-                            x,
-                            y,
-                            z: state::DEPTHS.grass + id as i64,
-                        })
-                        .with(state::Interactible {
-                            item_type: state::ItemType::Grass,
-                            hold_to_use: false,
-                        })
-                        .build();
-                }
-            }
-        }
+        app.world.insert(state::Game::new(dialogue::Dialogue::load(
+            dialogue::DIALOGUE,
+        )?));
 
-        // spawn cryopod
-        let mut id = si.new_index();
-        app.world
-            .create_entity()
-            .with(state::Sprite {
-                id,
-                store_index: store.index_by_name("cryopod")?,
-                animating: true,
-                sprite_type: state::SpriteType::Tool,
-                ..state::Sprite::default()
-            })
-            .with(state::Position {
-                x: 1,
-                y: 0,
-                z: state::DEPTHS.player - id as i64,
-            })
-            .with(state::Interactible {
-                item_type: state::ItemType::Pod,
-                hold_to_use: false,
-            })
-            .build();
-
-        // spawn terminal
-        id = si.new_index();
-        app.world
-            .create_entity()
-            .with(state::Sprite {
-                id,
-                store_index: store.index_by_name("terminal")?,
-                sprite_type: state::SpriteType::Tool,
-                animating: true,
-                flip: true,
-                ..state::Sprite::default()
-            })
-            .with(state::Position {
-                x: 11,
-                y: 2,
-                z: state::DEPTHS.player - id as i64,
-            })
-            .with(state::Interactible {
-                item_type: state::ItemType::Terminal,
-                hold_to_use: false,
-            })
-            .build();
-
-        // spawn player
-        id = si.new_index();
-        app.world
-            .create_entity()
-            .with(state::Sprite {
-                id,
-                store_index: store.index_by_name("character-00")?,
-                sprite_type: state::SpriteType::Player,
-                ..state::Sprite::default()
-            })
-            .with(state::Position {
-                x: 5,
-                y: (sz.1 as i64 / 2) - 2,
-                z: state::DEPTHS.player + id as i64,
-            })
-            .build();
-
-        // npc from start, for testing
-        // app.world
-        //     .create_entity()
-        //     .with(state::Sprite {
-        //         id,
-        //         store_index: store.index_by_name("character-01")?,
-        //         sprite_type: state::SpriteType::Tool,
-        //         ..state::Sprite::default()
-        //     })
-        //     .with(state::Position {
-        //         x: sz.0 as i64 / 2,
-        //         y: (sz.1 as i64 / 2),
-        //         z: state::DEPTHS.player + id as i64,
-        //     })
-        //     .with(state::Interactible {
-        //         item_type: state::ItemType::NPC,
-        //         hold_to_use: false,
-        //     })
-        //     .with(state::NPC {
-        //         move_target: (sz.0 as i64 / 2 + 1, sz.1 as i64 / 2),
-        //         last_move: 0,
-        //         move_wait: 200,
-        //         move_stop: 2000,
-        //     })
-        //     .build();
-
-        // spawn tools
-        id = si.new_index();
-        app.world
-            .create_entity()
-            .with(state::Sprite {
-                id,
-                store_index: store.index_by_name("tool-shovel")?,
-                sprite_type: state::SpriteType::Tool,
-                ..state::Sprite::default()
-            })
-            .with(state::Position {
-                x: 2,
-                y: sz.1 as i64 - 7,
-                z: state::DEPTHS.tools + id as i64,
-            })
-            .with(state::Interactible {
-                item_type: state::ItemType::Shovel,
-                hold_to_use: true,
-            })
-            .build();
-
-        id = si.new_index();
-        app.world
-            .create_entity()
-            .with(state::Sprite {
-                id,
-                store_index: store.index_by_name("tool-packet")?,
-                sprite_type: state::SpriteType::Tool,
-                ..state::Sprite::default()
-            })
-            .with(state::Position {
-                x: 8,
-                y: sz.1 as i64 - 4,
-                z: state::DEPTHS.tools + id as i64,
-            })
-            .with(state::Interactible {
-                item_type: state::ItemType::Packet,
-                hold_to_use: true,
-            })
-            .build();
-
-        id = si.new_index();
-        app.world
-            .create_entity()
-            .with(state::Sprite {
-                id,
-                store_index: store.index_by_name("tool-watercan")?,
-                sprite_type: state::SpriteType::Tool,
-                ..state::Sprite::default()
-            })
-            .with(state::Position {
-                x: 8,
-                y: sz.1 as i64 - 8,
-                z: state::DEPTHS.tools + id as i64,
-            })
-            .with(state::Interactible {
-                item_type: state::ItemType::Watercan,
-                hold_to_use: true,
-            })
-            .build();
-
-        // we insert store and sprite indexer late because they're borrowed during sprite initialization
         app.world.insert(store);
-        app.world.insert(si);
-
-        // initialize crossterm settings
-        terminal::enable_raw_mode()?;
-        io::stdout()
-            .queue(terminal::EnterAlternateScreen)?
-            .queue(cursor::Hide)?
-            .queue(cursor::SavePosition)?
-            .queue(event::EnableMouseCapture)?
-            .queue(terminal::Clear(terminal::ClearType::All))?
-            .flush()?;
+        app.world.insert(ids);
+        // the farm is the first area, and the players start out on it
+        scene::lay_out(&mut app.world, world, map, &mut rng);
+        app.world
+            .insert(state::GameRng(StdRng::seed_from_u64(rng.gen())));
+
         Ok(app)
     }
 
     /// can be used to print debug info at the app level
     #[allow(dead_code)]
     pub fn debug_print(&mut self, text: &str) -> Result<(), AppError> {
-        io::stdout()
-            .queue(cursor::MoveTo(0, 0))?
-            .queue(style::Print(text))?;
+        display::move_to(&mut io::stdout(), 0, 0)?;
+        write!(io::stdout(), "{}", text)?;
         Ok(())
     }
 
-    /// update time resources used for input and animation
-    fn update_time(&mut self) -> Result<(), AppError> {
-        let new_time = SystemTime::now()
-            .duration_since(self.time_start)
-            .unwrap()
-            .as_millis() as u64;
+    /// update time resources used for input and animation. game time passes at the game speed
+    /// setting, so everything timed by it speeds up without changing how often frames are drawn.
+    fn update_time(&mut self, dt: u64) -> Result<(), AppError> {
+        if self.input == InputState::CycleSpeed {
+            self.world
+                .write_resource::<settings::Settings>()
+                .cycle_speed();
+        }
+        let speed = self.world.read_resource::<settings::Settings>().speed();
+        // keep the fractions of milliseconds lost to rounding for the next update
+        let scaled = dt as f64 * speed as f64 + self.time_fraction;
+        self.time_fraction = scaled.fract();
+        let new_time = self.time + scaled as u64;
 
         {
             let mut time = self.world.write_resource::<state::Time>();
@@ -474,33 +860,197 @@ impl<'a> App<'a> {
         }
 
         self.time = new_time;
+        self.world.write_resource::<state::RealTime>().0 += dt;
+        Ok(())
+    }
+
+    /// reload any sprite sheets that changed on disk since the last update
+    #[cfg(feature = "dev")]
+    fn reload_sheets(&mut self) {
+        use specs::Join;
+
+        let Some(watcher) = &self.sheet_watcher else {
+            return;
+        };
+        let changed = watcher.changed_files();
+        if changed.is_empty() {
+            return;
+        }
+
+        let mut store = self.world.write_resource::<sprite::SpriteStore>();
+        let mut reloaded: Vec<sprite::SpriteHandle> = vec![];
+        for file in changed {
+            for index in store.indices_by_file(&file) {
+                if !reloaded.contains(&index) && store.reload(index).is_ok() {
+                    reloaded.push(index);
+                }
+            }
+        }
+
+        // a reloaded sheet may have fewer frames than before, and its pixels are all drawn again
+        self.world.write_resource::<render::Changes>().redraw = true;
+        let mut sprites = self.world.write_storage::<state::Sprite>();
+        for sprite in (&mut sprites).join() {
+            if reloaded.contains(&sprite.store_index) {
+                let frame_count = store[sprite.store_index].data.frames.len();
+                sprite.frame = sprite.frame.min(frame_count.saturating_sub(1));
+            }
+        }
+    }
+
+    /// accept a co-op guest who knows 'key' on 'addr'. the guest plays as a second player in
+    /// this world.
+    pub fn host(&mut self, addr: &str, key: &str) -> Result<(), AppError> {
+        self.host = Some(net::Host::bind(addr, key)?);
+        Ok(())
+    }
+
+    /// spawn or remove the guest player as they join and leave, and collect their input
+    fn update_host(&mut self) -> Result<(), AppError> {
+        let Some(host) = &mut self.host else {
+            return Ok(());
+        };
+        self.guest_input = InputState::None;
+        for event in host.poll() {
+            match event {
+                net::HostEvent::Joined => {
+                    let store_index = self.world.read_resource::<sprite::SpriteIds>().character_01;
+                    let id = self
+                        .world
+                        .write_resource::<state::SpriteIndexer>()
+                        .new_index();
+                    let world = *self.world.read_resource::<state::WorldSize>();
+                    let time = self.world.read_resource::<state::Time>().0;
+                    let guest = self
+                        .world
+                        .create_entity()
+                        .with(state::Sprite {
+                            id,
+                            store_index,
+                            sprite_type: state::SpriteType::Player,
+                            ..state::Sprite::default()
+                        })
+                        .with(state::Position {
+                            x: world.0 as i64 / 2,
+                            y: world.1 as i64 / 2 - 2,
+                        })
+                        .with(state::RenderLayer::Player)
+                        .with(state::Motion::default())
+                        .with(state::Animation::new("idle"))
+                        .with(state::AnimationController::default())
+                        .with(state::Collider::FEET)
+                        .with(state::Player {
+                            slot: 1,
+                            holding: state::ItemType::None,
+                            air_until: time + state::OXYGEN_TIME,
+                            last_use: None,
+                            target: None,
+                            walk: None,
+                        })
+                        .build();
+                    self.guest = Some(guest);
+                }
+                net::HostEvent::Left => {
+                    if let Some(guest) = self.guest.take() {
+                        let _ = self.world.delete_entity(guest);
+                    }
+                }
+                net::HostEvent::Input(input) => self.guest_input = input,
+            }
+        }
         Ok(())
     }
 
+    /// advance the game by the real time since the last update.
+    #[cfg(not(target_arch = "wasm32"))]
     pub fn update(&mut self) -> Result<(), AppError> {
-        self.update_time()?;
+        let now = Instant::now();
+        let dt = self
+            .last_update
+            .map_or(0, |last| (now - last).as_millis() as u64);
+        self.last_update = Some(now);
+        self.advance(dt)
+    }
+
+    /// advance the game by 'dt' milliseconds of real time, in as many steps of STEP as it
+    /// covers, drawing a frame each step. the rest carries over to the next call, and how far
+    /// it gets into the next step is kept in state::Interpolation, for drawing between steps.
+    /// frontends without a system clock, like the browser, call this directly. the local
+    /// input is used by the first step.
+    pub fn advance(&mut self, dt: u64) -> Result<(), AppError> {
+        self.accumulator = (self.accumulator + dt).min(STEP * MAX_STEPS);
+        *self.world.write_resource::<state::Interpolation>() =
+            state::Interpolation((self.accumulator % STEP) as f32 / STEP as f32);
+        while self.accumulator >= STEP {
+            self.accumulator -= STEP;
+            self.tick(STEP)?;
+        }
+        Ok(())
+    }
+
+    /// advance the game by one step of 'dt' milliseconds and draw a frame. replays call this
+    /// directly with the steps they recorded.
+    pub fn tick(&mut self, dt: u64) -> Result<(), AppError> {
+        if let Some(recording) = &mut self.recording {
+            recording.ticks.push((dt, self.input));
+        }
+        self.update_time(dt)?;
+        if self.input == InputState::Mute {
+            let mut settings = self.world.write_resource::<settings::Settings>();
+            settings.muted = !settings.muted;
+        }
+        #[cfg(feature = "dev")]
+        self.reload_sheets();
+        self.update_host()?;
 
         {
             let mut input = self.world.write_resource::<Input>();
-            *input = Input(self.input);
+            *input = Input(vec![self.input, self.guest_input]);
         }
 
+        #[cfg(not(target_arch = "wasm32"))]
+        let recovering = self.recovering(state::SAVE);
         self.dispatcher.dispatch(&self.world);
+        #[cfg(not(target_arch = "wasm32"))]
+        if recovering && !self.recovering(state::SAVE) {
+            self.recover()?;
+        }
+        #[cfg(feature = "discord")]
+        if let Some(presence) = &mut self.presence {
+            specs::RunNow::run_now(presence, &self.world);
+        }
+        #[cfg(feature = "notifications")]
+        if let Some(notifications) = &mut self.notifications {
+            specs::RunNow::run_now(notifications, &self.world);
+        }
+        #[cfg(feature = "audio")]
+        if let Some(audio) = &mut self.audio {
+            specs::RunNow::run_now(audio, &self.world);
+        }
         self.world.maintain();
+        let leaving = self
+            .world
+            .write_resource::<scene::SceneManager>()
+            .leaving
+            .take();
+        if let Some(leaving) = leaving {
+            scene::change(&mut self.world, leaving, self.seed);
+            self.world.maintain();
+        }
+
+        if let Some(layout) = self.host.as_ref().and_then(net::Host::guest_layout) {
+            let snapshot = net::snapshot(&self.world, layout);
+            if let Some(host) = &mut self.host {
+                host.send_snapshot(snapshot);
+            }
+        }
 
         self.input = InputState::None;
         Ok(())
     }
 
+    #[cfg(not(target_arch = "wasm32"))]
     pub fn exit(&self) -> Result<(), AppError> {
-        terminal::disable_raw_mode()?;
-        io::stdout()
-            .queue(event::DisableMouseCapture)?
-            .queue(cursor::RestorePosition)?
-            .queue(cursor::Show)?
-            .queue(terminal::LeaveAlternateScreen)?
-            .flush()?;
-
-        Ok(())
+        terminal::leave_terminal(self.world.read_resource::<settings::Settings>().mouse)
     }
 }
@@ -0,0 +1,461 @@
+#[cfg(not(target_arch = "wasm32"))]
+use super::{display, io, render, terminal, Frontend};
+use super::{ground, settings, shop, sprite, state, AppError, InputState};
+use serde::{Deserialize, Serialize};
+use specs::{Join, World, WorldExt};
+use std::io::{BufRead, BufReader, Write as IOWrite};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc;
+
+pub const DEFAULT_PORT: u16 = 7777;
+
+/// one sprite as drawn by the host. sheets are sent by name, since host and guest may have
+/// loaded them in different orders (e.g. one of them using a packed atlas).
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SnapshotSprite {
+    pub sheet: String,
+    pub frame: usize,
+    pub flip: bool,
+    pub highlight: bool,
+    #[serde(default)]
+    pub tint: Option<state::Tint>,
+    pub x: i64,
+    pub y: i64,
+}
+
+/// everything a guest needs to draw one frame of the host's world, sprites in draw order. the
+/// guest fits its own view of the world around 'focus', the center of its player.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Snapshot {
+    pub sprites: Vec<SnapshotSprite>,
+    pub text: Option<String>,
+    /// the frame of K's portrait shown beside the text, if any.
+    #[serde(default)]
+    pub portrait: Option<usize>,
+    pub world: (u16, u16),
+    pub focus: (i64, i64),
+    /// the host's game time, for the sprites' tints.
+    #[serde(default)]
+    pub time: u64,
+}
+
+/// messages are sent as newline-delimited json in both directions.
+#[derive(Serialize, Deserialize, Debug)]
+pub enum Message {
+    /// the guest's first message, with the key the host was started with and the keyboard
+    /// layout to show keys in. the host drops guests that send anything else first, or the
+    /// wrong key.
+    Hello {
+        key: String,
+        layout: settings::Layout,
+    },
+    Input(InputState),
+    Snapshot(Snapshot),
+}
+
+fn send(stream: &mut TcpStream, message: &Message) -> Result<(), AppError> {
+    let mut line = serde_json::to_string(message)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())?;
+    Ok(())
+}
+
+/// read messages on a background thread. the channel disconnects when the peer goes away.
+fn spawn_reader(stream: TcpStream) -> mpsc::Receiver<Message> {
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        for line in BufReader::new(stream).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            let Ok(message) = serde_json::from_str::<Message>(&line) else {
+                continue;
+            };
+            if tx.send(message).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+/// build a snapshot of the world as the renderer would draw it, showing keys as typed on
+/// 'layout'.
+pub fn snapshot(world: &World, layout: settings::Layout) -> Snapshot {
+    let store = world.read_resource::<sprite::SpriteStore>();
+    let game = world.read_resource::<state::Game>();
+    let shop = world.read_resource::<shop::Shop>();
+    let shop_text = shop.text(
+        &game,
+        &world.read_resource::<shop::Prices>(),
+        *world.read_resource::<shop::Money>(),
+        layout,
+    );
+    let sprites = world.read_storage::<state::Sprite>();
+    let positions = world.read_storage::<state::Position>();
+    let players = world.read_storage::<state::Player>();
+    let world_size = world.read_resource::<state::WorldSize>();
+
+    let motions = world.read_storage::<state::Motion>();
+    let bubbles = state::emote_bubbles(
+        (&store, &world.read_resource()),
+        &world.read_storage(),
+        &sprites,
+        &positions,
+        &motions,
+    );
+    let layers = world.read_storage::<state::RenderLayer>();
+    let tints = world.read_storage::<state::Tint>();
+    let mut sorted = (
+        &positions,
+        (&motions).maybe(),
+        &sprites,
+        (&layers).maybe(),
+        (&tints).maybe(),
+    )
+        .join()
+        .filter(|(_, _, sprite, _, _)| !sprite.hidden && !sprite.delete)
+        .map(|(pos, motion, sprite, layer, tint)| {
+            (state::drawn_pos(pos, motion), sprite, layer, tint)
+        })
+        .collect::<Vec<_>>();
+    sorted.sort_by_key(|(pos, sprite, layer, _)| state::draw_order(*layer, pos, sprite));
+    let mut sorted_sprites: Vec<_> = sorted
+        .into_iter()
+        .map(|(pos, sprite, _, tint)| (pos, sprite, tint.copied()))
+        .collect();
+    sorted_sprites.extend(
+        bubbles
+            .iter()
+            .map(|(pos, sprite)| (pos.clone(), sprite, None)),
+    );
+
+    // the ground goes first, under everything
+    let tile_map = world.read_resource::<ground::TileMap>();
+    let tiles = (0..tile_map.rows)
+        .flat_map(|row| (0..tile_map.cols).map(move |col| (col, row)))
+        .filter_map(|(col, row)| {
+            let tile = tile_map.get(col, row)?;
+            Some(SnapshotSprite {
+                sheet: store[tile.store_index].name.clone(),
+                frame: tile.frame,
+                flip: tile.flip,
+                highlight: false,
+                tint: None,
+                x: col * ground::TILE.0,
+                y: row * ground::TILE.1,
+            })
+        });
+
+    Snapshot {
+        sprites: tiles
+            .chain(
+                sorted_sprites
+                    .iter()
+                    .map(|(pos, sprite, tint)| SnapshotSprite {
+                        sheet: store[sprite.store_index].name.clone(),
+                        frame: sprite.frame,
+                        flip: sprite.flip,
+                        highlight: sprite.highlight,
+                        tint: *tint,
+                        x: pos.x,
+                        y: pos.y,
+                    }),
+            )
+            .collect(),
+        text: match (game.show_terminal, shop_text) {
+            (false, _) => None,
+            (true, Some(text)) => Some(text),
+            (true, None) => Some(game.terminal_text(layout)),
+        },
+        portrait: game.portrait().filter(|_| shop.picked.is_none()),
+        world: (world_size.0, world_size.1),
+        focus: state::player_focus(1, &players, &positions, &motions).unwrap_or_default(),
+        time: world.read_resource::<state::Time>().0,
+    }
+}
+
+/// send snapshots on a background thread, so a slow guest never holds up the host's updates.
+/// the channel only holds one snapshot: while it's full, newer ones are dropped rather than
+/// queued, and the guest catches up from the next one that fits. it disconnects once a write
+/// fails.
+fn spawn_writer(mut stream: TcpStream) -> mpsc::SyncSender<Snapshot> {
+    let (tx, rx) = mpsc::sync_channel::<Snapshot>(1);
+    std::thread::spawn(move || {
+        for snapshot in rx {
+            if send(&mut stream, &Message::Snapshot(snapshot)).is_err() {
+                let _ = stream.shutdown(std::net::Shutdown::Both);
+                break;
+            }
+        }
+    });
+    tx
+}
+
+pub enum HostEvent {
+    Joined,
+    Left,
+    Input(InputState),
+}
+
+/// how long a connection has to say hello before it's dropped, so a silent one can't hold the
+/// guest slot.
+const HELLO_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// a connected guest. it only joins the game once it has said hello with the right key.
+struct Guest {
+    stream: TcpStream,
+    rx: mpsc::Receiver<Message>,
+    tx: mpsc::SyncSender<Snapshot>,
+    connected: std::time::Instant,
+    joined: bool,
+    layout: settings::Layout,
+}
+
+/// the authoritative side of a co-op session. accepts one guest at a time, receives its input,
+/// and sends it a snapshot of the world every update.
+pub struct Host {
+    listener: TcpListener,
+    key: String,
+    guest: Option<Guest>,
+}
+
+impl Host {
+    /// listen on 'addr' for a guest who knows 'key'.
+    pub fn bind(addr: &str, key: &str) -> Result<Host, AppError> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Host {
+            listener,
+            key: key.to_string(),
+            guest: None,
+        })
+    }
+
+    pub fn poll(&mut self) -> Vec<HostEvent> {
+        let mut events: Vec<HostEvent> = vec![];
+
+        if self.guest.is_none() {
+            if let Ok((stream, _)) = self.listener.accept() {
+                let reader = stream.try_clone();
+                let writer = stream.try_clone();
+                if let (Ok(reader), Ok(writer), Ok(())) =
+                    (reader, writer, stream.set_nonblocking(false))
+                {
+                    self.guest = Some(Guest {
+                        stream,
+                        rx: spawn_reader(reader),
+                        tx: spawn_writer(writer),
+                        connected: std::time::Instant::now(),
+                        joined: false,
+                        layout: settings::Layout::default(),
+                    });
+                }
+            }
+        }
+
+        let mut dropped = false;
+        if let Some(guest) = &mut self.guest {
+            loop {
+                match guest.rx.try_recv() {
+                    Err(mpsc::TryRecvError::Empty) => break,
+                    Ok(Message::Hello { key, layout }) if !guest.joined && key == self.key => {
+                        guest.joined = true;
+                        guest.layout = layout;
+                        events.push(HostEvent::Joined);
+                    }
+                    // anything else before a good hello, or a second hello, and the guest is out
+                    _ if !guest.joined => {
+                        dropped = true;
+                        break;
+                    }
+                    Ok(Message::Hello { .. }) => {}
+                    // only gameplay input is accepted from guests
+                    Ok(Message::Input(input)) => match input {
+                        InputState::Quit
+                        | InputState::ToggleHelp
+                        | InputState::CycleSpeed
+                        | InputState::Mute
+                        | InputState::Clear => {}
+                        input => events.push(HostEvent::Input(input)),
+                    },
+                    Ok(Message::Snapshot(_)) => {}
+                    Err(mpsc::TryRecvError::Disconnected) => {
+                        dropped = true;
+                        break;
+                    }
+                }
+            }
+            if !guest.joined && guest.connected.elapsed() > HELLO_TIMEOUT {
+                dropped = true;
+            }
+        }
+        if dropped {
+            self.drop_guest(&mut events);
+        }
+        events
+    }
+
+    /// the keyboard layout of the guest, if one has joined.
+    pub fn guest_layout(&self) -> Option<settings::Layout> {
+        self.guest
+            .as_ref()
+            .filter(|guest| guest.joined)
+            .map(|guest| guest.layout)
+    }
+
+    /// hang up on the guest, and let the game know if they had joined it.
+    fn drop_guest(&mut self, events: &mut Vec<HostEvent>) {
+        if let Some(guest) = self.guest.take() {
+            let _ = guest.stream.shutdown(std::net::Shutdown::Both);
+            if guest.joined {
+                events.push(HostEvent::Left);
+            }
+        }
+    }
+
+    /// hand the guest a snapshot to send, without waiting on the network. the guest is dropped
+    /// at the next poll if sending has failed.
+    pub fn send_snapshot(&mut self, snapshot: Snapshot) {
+        let Some(guest) = self.guest.as_ref().filter(|guest| guest.joined) else {
+            return;
+        };
+        if let Err(mpsc::TrySendError::Disconnected(_)) = guest.tx.try_send(snapshot) {
+            let _ = guest.stream.shutdown(std::net::Shutdown::Both);
+        }
+    }
+}
+
+/// join a host's farm: `qffp join <host:port> [--key <key>]`, with the key the host was started
+/// with, if any. the guest only sends input and draws snapshots. like playing alone, the mouse
+/// is only captured if both 'mouse' and the settings allow it, and keys and highlights follow
+/// the keyboard layout and theme in the settings.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn join(addr: &str, key: &str, mode: display::RenderMode, mouse: bool) -> Result<(), AppError> {
+    let settings = settings::Settings::load(settings::SETTINGS)?;
+    let mouse = mouse && settings.mouse;
+    let addr = if addr.contains(':') {
+        addr.to_string()
+    } else {
+        format!("{}:{}", addr, DEFAULT_PORT)
+    };
+    let mut stream = TcpStream::connect(&addr)?;
+    send(
+        &mut stream,
+        &Message::Hello {
+            key: key.to_string(),
+            layout: settings.layout,
+        },
+    )?;
+    let rx = spawn_reader(stream.try_clone()?);
+    let store = sprite::SpriteStore::new(sprite::SHEETS.to_vec())?;
+    let ids = sprite::SpriteIds::new(&store)?;
+
+    let mut sz = terminal::size()?;
+    let mut screen = display::Screen::new(sz);
+    let mut local = terminal::LocalTerminal::new(settings.layout)?;
+    terminal::enter_terminal(mouse)?;
+
+    let result = (|| -> Result<(), AppError> {
+        let mut snapshot = Snapshot::default();
+        let start = std::time::Instant::now();
+        loop {
+            // the guest's clicks are moved into the world by the view they were made on
+            let camera = state::Camera::follow(
+                snapshot.focus,
+                state::ScreenSize(sz.0, sz.1),
+                state::WorldSize(snapshot.world.0, snapshot.world.1),
+            );
+            match local.read_input(20, false)? {
+                InputState::Quit => break,
+                InputState::Click(x, y) => send(
+                    &mut stream,
+                    &Message::Input(InputState::Click(x + camera.0, y + camera.1)),
+                )?,
+                InputState::Clear => {
+                    sz = terminal::size()?;
+                    screen = display::Screen::new(sz);
+                    screen.clear_all(io::stdout())?;
+                }
+                InputState::None
+                | InputState::ToggleHelp
+                | InputState::CycleSpeed
+                | InputState::Mute => {}
+                input => send(&mut stream, &Message::Input(input))?,
+            }
+
+            // only the newest snapshot matters
+            loop {
+                match rx.try_recv() {
+                    Ok(Message::Snapshot(s)) => snapshot = s,
+                    Ok(Message::Hello { .. } | Message::Input(_)) => {}
+                    Err(mpsc::TryRecvError::Empty) => break,
+                    Err(mpsc::TryRecvError::Disconnected) => return Ok(()),
+                }
+            }
+
+            screen.erase();
+            let mut emoji_tiles = vec![];
+            let highlight =
+                render::Highlight::new(&settings.theme, start.elapsed().as_millis() as u64);
+            let camera = state::Camera::follow(
+                snapshot.focus,
+                state::ScreenSize(sz.0, sz.1),
+                state::WorldSize(snapshot.world.0, snapshot.world.1),
+            );
+            for s in snapshot.sprites.iter() {
+                let Ok(info) = store.by_name(s.sheet.clone()) else {
+                    continue;
+                };
+                if s.frame >= info.data.frames.len() {
+                    continue;
+                }
+                let sprite = state::Sprite {
+                    frame: s.frame,
+                    flip: s.flip,
+                    highlight: s.highlight,
+                    ..state::Sprite::default()
+                };
+                let pos = state::Position {
+                    x: s.x - camera.0,
+                    y: s.y - camera.1,
+                };
+                render::render_sprite_at_pos(
+                    &mut screen,
+                    info,
+                    &sprite,
+                    &pos,
+                    highlight,
+                    s.tint,
+                    snapshot.time,
+                );
+                if mode == display::RenderMode::Emoji {
+                    emoji_tiles.extend(render::emoji_tile(info, &sprite, &pos));
+                }
+            }
+            if mode == display::RenderMode::Emoji {
+                render::render_emoji_tiles(&mut screen, &emoji_tiles);
+            }
+            if let Some(text) = &snapshot.text {
+                render::render_text_at_pos(&mut screen, text, 1, 0);
+                if let Some(frame) = snapshot.portrait {
+                    render::render_portrait(
+                        &mut screen,
+                        (&store, &ids),
+                        text,
+                        frame,
+                        snapshot.time,
+                    );
+                }
+            }
+            let status = format!("visiting {} | q: leave ", addr);
+            render::render_text_at_pos(&mut screen, &status, 0, sz.1 - 1);
+            render::apply_mode(&mut screen, mode);
+            screen.render(io::stdout())?;
+        }
+        Ok(())
+    })();
+
+    terminal::leave_terminal(mouse)?;
+    result
+}
@@ -0,0 +1,31 @@
+use super::state;
+use notify_rust::Notification;
+use specs::{Read, System};
+
+/// sends a desktop notification for events worth coming back to the game for, so the game can
+/// be left in a background pane.
+pub struct NotificationSystem;
+
+/// show a notification without holding up the game while the desktop responds.
+fn notify(body: &'static str) {
+    std::thread::spawn(move || {
+        let _ = Notification::new()
+            .summary("queer folk farmpunk")
+            .body(body)
+            .show();
+    });
+}
+
+impl<'a> System<'a> for NotificationSystem {
+    type SystemData = Read<'a, state::Events>;
+
+    fn run(&mut self, events: Self::SystemData) {
+        for event in events.0.iter() {
+            match event {
+                state::GameEvent::Message => notify("New message on the terminal."),
+                state::GameEvent::Bloom => notify("A flower is blooming!"),
+                _ => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use super::{assets, path, sprite, AppError};
+
+/// default output of `qffp pack`, which the game prefers over individual sheets when present.
+pub const ATLAS: &str = "res/atlas.json";
+
+/// whether the game should load the atlas instead of the individual sheets: it's there, and no
+/// sheet was edited after it was packed. a stale atlas is passed over with a warning, so edits
+/// to the sheets aren't lost behind it.
+pub fn use_atlas() -> bool {
+    if !assets::exists(ATLAS) {
+        return false;
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let modified = |path: &path::Path| std::fs::metadata(path).and_then(|m| m.modified());
+        let Ok(packed) = assets::find(ATLAS).and_then(|atlas| modified(&atlas)) else {
+            return true;
+        };
+        for sheet in sprite::SHEETS {
+            let Ok(json) = assets::find(sheet) else {
+                continue;
+            };
+            let image = json.with_extension("png");
+            if [json.as_path(), image.as_path()]
+                .iter()
+                .any(|file| modified(file).is_ok_and(|time| time > packed))
+            {
+                eprintln!(
+                    "{} is older than {}, loading the sheets instead; run `qffp pack` to update it",
+                    ATLAS, sheet
+                );
+                return false;
+            }
+        }
+    }
+    true
+}
+
+/// merge sheets into a single atlas image and json:
+/// `qffp pack [--out res/atlas.json] [sheet.json...]`. with no sheets given, packs every game sheet.
+pub fn run(args: &[String]) -> Result<(), AppError> {
+    let mut out = ATLAS.to_string();
+    let mut paths: Vec<String> = vec![];
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        if arg == "--out" || arg == "-o" {
+            out = args_iter
+                .next()
+                .ok_or(AppError::Usage("qffp pack --out <atlas.json>".to_string()))?
+                .clone();
+        } else {
+            paths.push(arg.clone());
+        }
+    }
+    if paths.is_empty() {
+        paths = sprite::SHEETS.iter().map(|p| p.to_string()).collect();
+    }
+
+    // decode every sheet
+    struct Packed {
+        name: String,
+        frames: Vec<sprite::SpriteSheetJSONFrame>,
+        palette_cycles: Vec<sprite::PaletteCycle>,
+        frame_tags: Vec<sprite::FrameTag>,
+        image: image::RgbaImage,
+        x: u32,
+        y: u32,
+    }
+    let mut packed: Vec<Packed> = vec![];
+    for json_path in paths.iter() {
+        let json_str = std::fs::read_to_string(json_path)?;
+        let json = serde_json::from_str::<sprite::SpriteSheetJSON>(&json_str)?;
+        let image_path = path::Path::new(json_path)
+            .parent()
+            .unwrap_or(path::Path::new(""))
+            .join(&json.meta.image);
+        let Some((name, _)) = json.meta.image.rsplit_once('.') else {
+            return Err(sprite::SpriteStoreError::ImageName {
+                sheet: sprite::sheet_name(json_path),
+                image: json.meta.image,
+            }
+            .into());
+        };
+        packed.push(Packed {
+            name: name.to_string(),
+            frames: json.frames,
+            palette_cycles: json.meta.palette_cycles,
+            frame_tags: json.meta.frame_tags,
+            image: image::open(image_path)?.into_rgba8(),
+            x: 0,
+            y: 0,
+        });
+    }
+
+    // shelf packing: tallest sheets first, filling rows left to right
+    packed.sort_by_key(|p| std::cmp::Reverse(p.image.height()));
+    let max_w = packed.iter().map(|p| p.image.width()).max().unwrap_or(0);
+    let shelf_w = max_w.max(512);
+    let (mut x, mut y, mut shelf_h, mut atlas_w) = (0, 0, 0, 0);
+    for sheet in packed.iter_mut() {
+        if x + sheet.image.width() > shelf_w {
+            x = 0;
+            y += shelf_h;
+            shelf_h = 0;
+        }
+        sheet.x = x;
+        sheet.y = y;
+        x += sheet.image.width();
+        atlas_w = atlas_w.max(x);
+        shelf_h = shelf_h.max(sheet.image.height());
+    }
+    let atlas_h = y + shelf_h;
+
+    let mut atlas_image = image::RgbaImage::new(atlas_w, atlas_h);
+    let mut sheets: Vec<sprite::AtlasSheet> = vec![];
+    for sheet in packed {
+        image::imageops::replace(
+            &mut atlas_image,
+            &sheet.image,
+            sheet.x as i64,
+            sheet.y as i64,
+        );
+        sheets.push(sprite::AtlasSheet {
+            name: sheet.name,
+            rect: sprite::Xywh {
+                x: sheet.x,
+                y: sheet.y,
+                w: sheet.image.width(),
+                h: sheet.image.height(),
+            },
+            frames: sheet.frames,
+            palette_cycles: sheet.palette_cycles,
+            frame_tags: sheet.frame_tags,
+        });
+    }
+
+    let out_path = path::Path::new(&out);
+    let image_name = out_path.with_extension("png");
+    let atlas = sprite::AtlasJSON {
+        sheets,
+        meta: sprite::SpriteSheetJSONMeta {
+            app: "qffp pack".to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            image: image_name
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or_default()
+                .to_string(),
+            format: "RGBA8888".to_string(),
+            size: sprite::WH {
+                w: atlas_w,
+                h: atlas_h,
+            },
+            scale: "1".to_string(),
+            palette_cycles: vec![],
+            frame_tags: vec![],
+        },
+    };
+
+    atlas_image.save(&image_name)?;
+    std::fs::write(out_path, serde_json::to_string_pretty(&atlas)?)?;
+    println!(
+        "packed {} sheets into {} ({}x{})",
+        atlas.sheets.len(),
+        out,
+        atlas_w,
+        atlas_h
+    );
+    Ok(())
+}
@@ -0,0 +1,133 @@
+use super::{ground, sprite, state};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// the cells a standing character takes up, and the column and row of their feet within them.
+const CHARACTER: (i64, i64) = (10, 5);
+const FEET: (i64, i64) = (5, 4);
+
+/// the ground tile a character standing at 'pos' has their feet in.
+pub fn feet(pos: (i64, i64)) -> (i64, i64) {
+    (
+        (pos.0 + FEET.0).div_euclid(ground::TILE.0),
+        (pos.1 + FEET.1).div_euclid(ground::TILE.1),
+    )
+}
+
+/// where a character stands to have their feet in the middle of 'tile'.
+pub fn standing(tile: (i64, i64)) -> (i64, i64) {
+    (
+        tile.0 * ground::TILE.0 + ground::TILE.0 / 2 - FEET.0,
+        tile.1 * ground::TILE.1 + ground::TILE.1 / 2 - FEET.1,
+    )
+}
+
+/// which ground tiles of the world a character can walk through, for npcs finding their way
+/// around the crops, tools, machines, water and the dome wall in their way.
+pub struct Walkable {
+    cols: i64,
+    rows: i64,
+    open: Vec<bool>,
+}
+
+impl Walkable {
+    /// the tiles of a world of 'size' cells that a character can stand in: inside the world,
+    /// clear of the dome wall and the water, and not under the bottom row of any of the
+    /// 'obstacles', given as (x, y, width, height) in cells.
+    pub fn new(
+        size: (u16, u16),
+        dome: &state::Dome,
+        (tile_map, water): (&ground::TileMap, sprite::SpriteHandle),
+        obstacles: impl IntoIterator<Item = (i64, i64, i64, i64)>,
+    ) -> Walkable {
+        let (cols, rows) = (
+            size.0 as i64 / ground::TILE.0,
+            size.1 as i64 / ground::TILE.1,
+        );
+        let fits = |(x, y): (i64, i64)| {
+            (0..=size.0 as i64 - CHARACTER.0).contains(&x)
+                && (-2..=size.1 as i64 - CHARACTER.1).contains(&y)
+        };
+        let mut open: Vec<bool> = (0..rows)
+            .flat_map(|row| (0..cols).map(move |col| (col, row)))
+            .map(|tile| {
+                let at = standing(tile);
+                let wet = tile_map
+                    .get(tile.0, tile.1)
+                    .is_some_and(|t| t.store_index == water);
+                fits(at) && dome.passable(at) && !wet
+            })
+            .collect();
+        for (x, y, w, h) in obstacles {
+            let row = (y + h - 1).div_euclid(ground::TILE.1);
+            let (first, last) = (
+                x.div_euclid(ground::TILE.0),
+                (x + w - 1).div_euclid(ground::TILE.0),
+            );
+            for col in first..=last {
+                if let Some(i) = Walkable::index(cols, rows, (col, row)) {
+                    open[i] = false;
+                }
+            }
+        }
+        Walkable { cols, rows, open }
+    }
+
+    fn index(cols: i64, rows: i64, (col, row): (i64, i64)) -> Option<usize> {
+        ((0..cols).contains(&col) && (0..rows).contains(&row))
+            .then_some((row * cols + col) as usize)
+    }
+
+    /// whether a character can't stand in 'tile'.
+    pub fn blocked(&self, tile: (i64, i64)) -> bool {
+        Walkable::index(self.cols, self.rows, tile).is_none_or(|i| !self.open[i])
+    }
+
+    /// the tiles along a shortest walk from 'from' to 'to', a step up, down, left or right at a
+    /// time, starting with 'from' itself. it's fine to start out in a blocked tile, so as to
+    /// walk out of it. None if there's no way there.
+    pub fn path(&self, from: (i64, i64), to: (i64, i64)) -> Option<Vec<(i64, i64)>> {
+        let start = Walkable::index(self.cols, self.rows, from)?;
+        let goal = Walkable::index(self.cols, self.rows, to).filter(|i| self.open[*i])?;
+        let tile = |i: usize| (i as i64 % self.cols, i as i64 / self.cols);
+        let guess = |i: usize| {
+            let (col, row) = tile(i);
+            (col - to.0).abs() + (row - to.1).abs()
+        };
+
+        // A*, by steps taken so far plus the steps left if nothing were in the way
+        let mut came_from: Vec<Option<usize>> = vec![None; self.open.len()];
+        let mut steps: Vec<i64> = vec![i64::MAX; self.open.len()];
+        let mut open = BinaryHeap::from([Reverse((guess(start), start))]);
+        steps[start] = 0;
+        while let Some(Reverse((_, i))) = open.pop() {
+            if i == goal {
+                let mut path = vec![tile(goal)];
+                let mut at = goal;
+                while let Some(previous) = came_from[at] {
+                    path.push(tile(previous));
+                    at = previous;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            let (col, row) = tile(i);
+            for next in [
+                (col + 1, row),
+                (col - 1, row),
+                (col, row + 1),
+                (col, row - 1),
+            ] {
+                let Some(n) = Walkable::index(self.cols, self.rows, next) else {
+                    continue;
+                };
+                if self.open[n] && steps[i] + 1 < steps[n] {
+                    steps[n] = steps[i] + 1;
+                    came_from[n] = Some(i);
+                    open.push(Reverse((steps[n] + guess(n), n)));
+                }
+            }
+        }
+        None
+    }
+}
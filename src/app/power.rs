@@ -0,0 +1,117 @@
+use super::{sprite, state};
+use specs::{Join, Read, ReadStorage, System, Write, WriteStorage};
+
+/// power a solar panel makes per second of daylight, and how much of it the bank can hold for
+/// each panel.
+pub const PANEL_OUTPUT: u32 = 1;
+pub const PANEL_STORAGE: u32 = 60;
+
+/// power a sprinkler draws each morning, and how many cells away it reaches.
+pub const SPRINKLER_DRAW: u32 = 20;
+pub const SPRINKLER_RANGE: i64 = 12;
+
+/// specs resource for the power bank shared by every machine.
+#[derive(Default)]
+pub struct Power {
+    pub stored: u32,
+    pub capacity: u32,
+    /// set when the bank couldn't run every sprinkler this morning, so none of them ran.
+    pub brownout: bool,
+    /// the day the sprinklers last ran.
+    pub day: u32,
+    /// the time panels last charged up to, in milliseconds.
+    pub charged: u64,
+}
+
+impl Power {
+    /// a line describing the bank, or nothing before any panels are built.
+    pub fn status(&self) -> Option<String> {
+        if self.capacity == 0 {
+            return None;
+        }
+        let brownout = if self.brownout { " brownout!" } else { "" };
+        Some(format!(
+            "power {}/{}{}",
+            self.stored, self.capacity, brownout
+        ))
+    }
+}
+
+/// charges the bank from solar panels while the player is up, and runs the sprinklers each
+/// morning. the player sleeps through the night, so panels charge whenever time passes.
+pub struct PowerSystem;
+
+impl<'a> System<'a> for PowerSystem {
+    type SystemData = (
+        Write<'a, Power>,
+        Read<'a, state::Game>,
+        Read<'a, state::Time>,
+        Read<'a, sprite::SpriteStore>,
+        Read<'a, sprite::SpriteIds>,
+        ReadStorage<'a, state::Machine>,
+        ReadStorage<'a, state::Interactible>,
+        ReadStorage<'a, state::Position>,
+        WriteStorage<'a, state::Sprite>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (mut power, game, time, store, ids, machines, interactibles, positions, mut sprites) =
+            data;
+
+        let count = |kind| machines.join().filter(|m| m.kind == kind).count() as u32;
+        let panels = count(state::MachineKind::Solar);
+        let sprinklers = count(state::MachineKind::Sprinkler);
+        power.capacity = panels * PANEL_STORAGE;
+
+        // charge by whole seconds, keeping the remainder for later
+        let seconds = time.0.saturating_sub(power.charged) / 1000;
+        power.charged += seconds * 1000;
+        power.stored =
+            (power.stored + (seconds as u32) * panels * PANEL_OUTPUT).min(power.capacity);
+
+        if game.day == power.day {
+            return;
+        }
+        power.day = game.day;
+        let draw = sprinklers * SPRINKLER_DRAW;
+        power.brownout = draw > power.stored;
+        if power.brownout {
+            for (machine, sprite) in (&machines, &mut sprites).join() {
+                if machine.kind == state::MachineKind::Sprinkler {
+                    sprite.frame = 1;
+                }
+            }
+            return;
+        }
+        power.stored -= draw;
+
+        let heads: Vec<(i64, i64)> = (&machines, &positions)
+            .join()
+            .filter(|(m, _)| m.kind == state::MachineKind::Sprinkler)
+            .map(|(_, pos)| (pos.x + 2, pos.y))
+            .collect();
+        let crop_empty = ids.crop_empty;
+        for (item, pos, sprite) in (&interactibles, &positions, &mut sprites).join() {
+            // dry plots are left alone, since watering them grows grass back
+            if item.item_type != state::ItemType::Crop || sprite.store_index == crop_empty {
+                continue;
+            }
+            let info = &store[sprite.store_index];
+            let Some(watered) = info.retag(sprite.frame, "dry", "watered") else {
+                continue;
+            };
+            let center = (pos.x + 4, pos.y + 1);
+            if heads.iter().any(|head| {
+                (head.0 - center.0).abs() <= SPRINKLER_RANGE
+                    && (head.1 - center.1).abs() * 2 <= SPRINKLER_RANGE
+            }) {
+                sprite.frame = watered;
+            }
+        }
+        for (machine, sprite) in (&machines, &mut sprites).join() {
+            if machine.kind == state::MachineKind::Sprinkler {
+                sprite.frame = 0;
+            }
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use super::{sprite, state};
+use discord_rich_presence::{activity::Activity, DiscordIpc, DiscordIpcClient};
+use specs::{Read, ReadStorage, System};
+
+/// environment variable holding the id of the Discord application to publish presence as.
+pub const CLIENT_ID_VAR: &str = "QFFP_DISCORD_CLIENT_ID";
+
+// discord rate limits activity updates, and looking for a client costs a socket connect
+const UPDATE_INTERVAL: u64 = 15_000;
+
+/// publishes what's happening on the farm to a running Discord client. nothing is published
+/// while Discord isn't running; the connection is retried every update interval.
+pub struct PresenceSystem {
+    client: DiscordIpcClient,
+    connected: bool,
+    last_update: Option<u64>,
+    last_text: String,
+}
+
+impl PresenceSystem {
+    /// returns None when no application id is configured.
+    pub fn from_env() -> Option<PresenceSystem> {
+        let client_id = std::env::var(CLIENT_ID_VAR).ok()?;
+        Some(PresenceSystem {
+            client: DiscordIpcClient::new(client_id),
+            connected: false,
+            last_update: None,
+            last_text: String::new(),
+        })
+    }
+}
+
+fn presence_text(day: u32, flowers: usize) -> String {
+    match flowers {
+        0 => format!("Day {} on Luna", day),
+        1 => format!("Day {} on Luna — 1 flower blooming", day),
+        n => format!("Day {} on Luna — {} flowers blooming", day, n),
+    }
+}
+
+impl<'a> System<'a> for PresenceSystem {
+    type SystemData = (
+        Read<'a, state::Game>,
+        Read<'a, state::Time>,
+        Read<'a, sprite::SpriteStore>,
+        ReadStorage<'a, state::Sprite>,
+        ReadStorage<'a, state::Interactible>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (game, time, store, sprites, interactibles) = data;
+        if self
+            .last_update
+            .is_some_and(|last| last + UPDATE_INTERVAL > time.0)
+        {
+            return;
+        }
+        self.last_update = Some(time.0);
+
+        let flowers = state::blooming_flowers(&store, &interactibles, &sprites);
+        let text = presence_text(game.day, flowers);
+
+        if !self.connected {
+            self.connected = self.client.connect().is_ok();
+            self.last_text.clear();
+        }
+        if !self.connected || text == self.last_text {
+            return;
+        }
+        if self
+            .client
+            .set_activity(Activity::new().details(text.as_str()))
+            .is_ok()
+        {
+            self.last_text = text;
+        } else {
+            let _ = self.client.close();
+            self.connected = false;
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use super::{display, io, render, sprite, state, terminal, AppError};
+use crossterm::event::{poll, read, Event, KeyCode, KeyEventKind};
+use std::time::{Duration, SystemTime};
+
+/// loop the frames of a single sprite sheet in the terminal, outside of the game. used to check
+/// new art and frame durations: `qffp preview res/sheets/crop-flower.json`.
+/// keys: space pauses, h/l step frames while paused, f flips, q quits.
+pub fn run(json_path: &str) -> Result<(), AppError> {
+    let store = sprite::SpriteStore::new(vec![json_path])?;
+    let sheet = &store.0[0];
+    let frame_count = sheet.data.frames.len();
+    if frame_count == 0 {
+        return Err(AppError::Usage(format!("{} has no frames", json_path)));
+    }
+
+    let sz = terminal::size()?;
+    let mut screen = display::Screen::new(sz);
+    terminal::enter_terminal(true)?;
+
+    let mut sprite = state::Sprite::default();
+    let mut paused = false;
+    let mut frame_start = SystemTime::now();
+    let start = SystemTime::now();
+    let result = (|| -> Result<(), AppError> {
+        loop {
+            if poll(Duration::from_millis(20))? {
+                if let Event::Key(event) = read()? {
+                    if event.kind == KeyEventKind::Press {
+                        match event.code {
+                            KeyCode::Char('q') | KeyCode::Esc => break,
+                            KeyCode::Char(' ') => paused = !paused,
+                            KeyCode::Char('f') => sprite.flip = !sprite.flip,
+                            KeyCode::Char('h') | KeyCode::Left => {
+                                sprite.frame = (sprite.frame + frame_count - 1) % frame_count;
+                            }
+                            KeyCode::Char('l') | KeyCode::Right => {
+                                sprite.frame = (sprite.frame + 1) % frame_count;
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+
+            // advance by the frame's own duration, like the game's animation system
+            let duration = sheet.data.frames[sprite.frame].duration as u128;
+            let elapsed = frame_start.elapsed().unwrap_or_default().as_millis();
+            if paused {
+                frame_start = SystemTime::now();
+            } else if elapsed >= duration {
+                sprite.frame = (sprite.frame + 1) % frame_count;
+                frame_start = SystemTime::now();
+            }
+
+            let source = &sheet.data.frames[sprite.frame].source_size;
+            let pos = state::Position {
+                x: (sz.0 as i64 - source.w as i64) / 2,
+                y: (sz.1 as i64 - source.h as i64 / 2) / 2,
+            };
+            screen.erase();
+            render::render_sprite_at_pos(
+                &mut screen,
+                sheet,
+                &sprite,
+                &pos,
+                Default::default(),
+                None,
+                start.elapsed().unwrap_or_default().as_millis() as u64,
+            );
+            let status = format!(
+                "{} | frame {}/{} | {}ms{}",
+                sheet.name,
+                sprite.frame + 1,
+                frame_count,
+                sheet.data.frames[sprite.frame].duration,
+                if paused { " | paused" } else { "" }
+            );
+            render::render_text_at_pos(&mut screen, &status, 0, 0);
+            let help = "space: pause | h/l: step | f: flip | q: quit";
+            render::render_text_at_pos(&mut screen, help, 0, sz.1 - 1);
+            screen.render(io::stdout())?;
+        }
+        Ok(())
+    })();
+
+    terminal::leave_terminal(true)?;
+    result
+}
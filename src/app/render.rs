@@ -1,8 +1,82 @@
-use super::{display, io, sprite, state};
-use specs::{Read, ReadStorage, System};
+use super::{
+    challenge, cues, daycycle, display, ending, ground, power, robot, routine, savefile, settings,
+    shop, sprite, state, timer, weather, IOWrite,
+};
+use specs::storage::ComponentEvent;
+use specs::{
+    BitSet, Entities, Entity, Read, ReadStorage, ReaderId, System, World, WorldExt, Write,
+};
+use std::collections::HashMap;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
+/// the render system. sprites are composited on a layer kept between frames, and only the cells
+/// of sprites that changed are composited again.
 pub struct RenderBuffer {
     pub screen: display::Screen,
+    pub out: Box<dyn IOWrite + Send>,
+    // the window title last written, so it is only sent when it changes
+    pub title: String,
+    // every sprite on screen composited, and a scratch screen to composite changed cells on
+    layer: display::Screen,
+    scratch: display::Screen,
+    // how each sprite in the layer was drawn, and the ground tiles under them
+    drawn: HashMap<Entity, Drawn>,
+    tiles: Vec<ground::Tile>,
+    // the screen size, camera and render mode the layer was composited for
+    view: Option<(display::Size, (i64, i64), display::RenderMode)>,
+}
+
+impl RenderBuffer {
+    pub fn new(sz: (u16, u16), out: Box<dyn IOWrite + Send>) -> RenderBuffer {
+        RenderBuffer {
+            screen: display::Screen::new(sz),
+            out,
+            title: String::new(),
+            layer: display::Screen::new(sz),
+            scratch: display::Screen::new(sz),
+            drawn: HashMap::new(),
+            tiles: vec![],
+            view: None,
+        }
+    }
+}
+
+/// how a sprite was last drawn into the layer. it's composited again only when this changes.
+#[derive(Clone, Copy, PartialEq)]
+struct Drawn {
+    rect: display::Rect,
+    store_index: sprite::SpriteHandle,
+    frame: usize,
+    flip: bool,
+    order: (state::RenderLayer, i64, usize),
+    outline: Option<display::Color>,
+    underline: bool,
+    // the game time, for sprites whose tint or palette cycles change every frame
+    tick: Option<u64>,
+    // whether it was gliding, hopping, tinted, highlighted or palette cycling, any of which can
+    // change or stop without its sprite or position being written
+    live: bool,
+}
+
+/// readers of the sprites and positions written since the last frame, which the render system
+/// looks at for what to composite again. a specs resource, made once their storages exist.
+pub struct Changes {
+    sprites: ReaderId<ComponentEvent>,
+    positions: ReaderId<ComponentEvent>,
+    /// composite every sprite again next frame, after something they're drawn from changed
+    /// outside their components, like a reloaded sheet.
+    pub redraw: bool,
+}
+
+impl Changes {
+    pub fn new(world: &World) -> Changes {
+        Changes {
+            sprites: world.write_storage::<state::Sprite>().register_reader(),
+            positions: world.write_storage::<state::Position>().register_reader(),
+            redraw: false,
+        }
+    }
 }
 
 const HB_CHARS: &[char] = &['▀', '▄'];
@@ -33,145 +107,586 @@ fn render_bg_checkerboard(scr: &mut display::Screen) {
     }
 }
 
-fn render_sprite_at_pos(
+const BLACK: display::Color = display::Color::Rgb { r: 0, g: 0, b: 0 };
+
+/// milliseconds a blinking highlight stays on, then off.
+const BLINK_TIME: u64 = 400;
+
+/// how highlighted sprites are drawn this frame: the color their black outline takes, if it
+/// changes, and whether a line is drawn under them.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Highlight {
+    outline: Option<display::Color>,
+    underline: bool,
+}
+
+impl Highlight {
+    /// highlights the way 'theme' draws them, 'time' milliseconds into a blink.
+    pub fn new(theme: &settings::Theme, time: u64) -> Highlight {
+        let outline = Some(theme_color(theme));
+        match theme.highlight {
+            settings::HighlightStyle::Outline => Highlight {
+                outline,
+                underline: false,
+            },
+            settings::HighlightStyle::Blink => Highlight {
+                outline: outline.filter(|_| (time / BLINK_TIME).is_multiple_of(2)),
+                underline: false,
+            },
+            settings::HighlightStyle::Underline => Highlight {
+                outline: None,
+                underline: true,
+            },
+        }
+    }
+}
+
+/// the theme's highlight color.
+fn theme_color(theme: &settings::Theme) -> display::Color {
+    let [r, g, b] = theme.highlight_color;
+    display::Color::Rgb { r, g, b }
+}
+
+/// characters from dark to light, for shading cells in the ASCII render mode.
+const ASCII_SHADES: &[char] = &[' ', '.', ':', '-', '=', '+', '*', '#', '%', '@'];
+
+/// a stand-in for 'c' in the ASCII render mode.
+fn ascii_char(c: char) -> char {
+    match c {
+        c if c.is_ascii() => c,
+        '\u{2010}'..='\u{2015}' => '-',
+        '\u{2665}' => '*',
+        '\u{2661}' => '.',
+        '\u{250c}' | '\u{2510}' | '\u{2514}' | '\u{2518}' => '+',
+        _ => '?',
+    }
+}
+
+/// the sixteen standard colors as the windows console draws them, in SGR order.
+const ANSI_COLORS: [[u32; 3]; 16] = [
+    [0, 0, 0],
+    [128, 0, 0],
+    [0, 128, 0],
+    [128, 128, 0],
+    [0, 0, 128],
+    [128, 0, 128],
+    [0, 128, 128],
+    [192, 192, 192],
+    [128, 128, 128],
+    [255, 0, 0],
+    [0, 255, 0],
+    [255, 255, 0],
+    [0, 0, 255],
+    [255, 0, 255],
+    [0, 255, 255],
+    [255, 255, 255],
+];
+
+/// the standard color closest to 'color'.
+fn ansi_color(color: display::Color) -> display::Color {
+    let display::Color::Rgb { r, g, b } = color else {
+        return color;
+    };
+    let rgb = [r, g, b].map(u32::from);
+    let distance = |c: &[u32; 3]| (0..3).map(|i| rgb[i].abs_diff(c[i]).pow(2)).sum::<u32>();
+    let nearest = (0..ANSI_COLORS.len())
+        .min_by_key(|&i| distance(&ANSI_COLORS[i]))
+        .unwrap_or(0);
+    display::Color::Ansi(nearest as u8)
+}
+
+/// redraw a finished frame for 'mode'. in the ASCII mode, each half block cell becomes the
+/// average of its two pixels as a background, shaded by how bright it is, and any other
+/// unicode is replaced with an ASCII stand-in. the legacy mode keeps the half blocks, which
+/// every console codepage has, but takes colors down to the standard sixteen.
+pub fn apply_mode(scr: &mut display::Screen, mode: display::RenderMode) {
+    match mode {
+        display::RenderMode::Ascii => apply_ascii(scr),
+        display::RenderMode::Legacy => apply_legacy(scr),
+        display::RenderMode::Blocks | display::RenderMode::Emoji => {}
+    }
+}
+
+fn apply_legacy(scr: &mut display::Screen) {
+    let size = scr.size();
+    for y in 0..size.height as u16 {
+        for x in 0..size.width as u16 {
+            let pos = display::ScreenPos { x, y };
+            let Some((c, style)) = scr.get(pos) else {
+                continue;
+            };
+            let c = if HB_CHARS.contains(&c) {
+                c
+            } else {
+                ascii_char(c)
+            };
+            let style = display::Style {
+                fg: style.fg.map(ansi_color),
+                bg: style.bg.map(ansi_color),
+                ..style
+            };
+            scr.put(c, style, pos);
+        }
+    }
+}
+
+fn apply_ascii(scr: &mut display::Screen) {
+    let rgb = |color: Option<display::Color>| match color {
+        Some(display::Color::Rgb { r, g, b }) => [r, g, b].map(u32::from),
+        _ => [0, 0, 0],
+    };
+    let size = scr.size();
+    for y in 0..size.height as u16 {
+        for x in 0..size.width as u16 {
+            let pos = display::ScreenPos { x, y };
+            let Some((c, style)) = scr.get(pos) else {
+                continue;
+            };
+            if !HB_CHARS.contains(&c) {
+                if !c.is_ascii() {
+                    scr.put(ascii_char(c), style, pos);
+                }
+                continue;
+            }
+            let (a, b) = (rgb(style.fg), rgb(style.bg));
+            let [r, g, b] = [0, 1, 2].map(|i| (a[i] + b[i]) / 2);
+            let brightness = (r * 299 + g * 587 + b * 114) / 1000;
+            let shade = ASCII_SHADES[brightness as usize * ASCII_SHADES.len() / 256];
+            // shades stand out a little from the background: darker on bright cells, and
+            // lighter on dark ones
+            let fg = if brightness > 127 {
+                [r, g, b].map(|c| c * 3 / 5)
+            } else {
+                [r, g, b].map(|c| c + (255 - c) * 2 / 5)
+            };
+            let color = |[r, g, b]: [u32; 3]| display::Color::Rgb {
+                r: r as u8,
+                g: g as u8,
+                b: b as u8,
+            };
+            let shaded = display::Style {
+                underline: style.underline,
+                ..cell_style(color(fg), color([r, g, b]))
+            };
+            scr.put(shade, shaded, pos);
+        }
+    }
+}
+
+/// builds the style for one half-block cell from its foreground and background colors.
+fn cell_style(fg: display::Color, bg: display::Color) -> display::Style {
+    let mut style = display::Style::new();
+    style.set_fg(fg);
+    style.set_bg(bg);
+    style
+}
+
+/// the emoji standing in for a sprite in the emoji render mode, by sheet and frame. crops show
+/// their growth stage; ground, particles and the like have none and are left as color.
+fn emoji_for(sheet: &str, frame: usize) -> Option<&'static str> {
+    let emoji = match sheet {
+        "character-00" | "character-01" => "\u{1f9d1}\u{200d}\u{1f33e}",
+        "crop-leaf" => ["\u{1f331}", "\u{1f33f}", "\u{1f33f}", "\u{1f96c}"][frame % 4],
+        "crop-flower" => ["\u{1f331}", "\u{1f33f}", "\u{1f337}", "\u{1f33b}"][frame % 4],
+        "grass" => "\u{1f33e}",
+        "cryopod" => "\u{1f6cf}\u{fe0f}",
+        "terminal" => "\u{1f6f0}\u{fe0f}",
+        "compost-bin" => "\u{267b}\u{fe0f}",
+        "teleporter" => "\u{1f300}",
+        "solar-panel" => "\u{1f506}",
+        "sprinkler" => "\u{26f2}",
+        "tap" => "\u{1f6b0}",
+        "channel" if frame >= 16 => "\u{1f4a7}",
+        "robot" => "\u{1f916}",
+        "dome-wall" if frame == 1 => "\u{1f6aa}",
+        "dome-wall" => "\u{1f9f1}",
+        "tool-shovel" => "\u{26cf}\u{fe0f}",
+        "tool-watercan" => "\u{1f6bf}",
+        "tool-packet" | "tool-seed" => "\u{1f330}",
+        "tool-packet2" => "\u{1f338}",
+        "tool-fiber" => "\u{1f9f5}",
+        "emote" => ["\u{2757}", "\u{2753}", "\u{1f496}", "\u{1f4a4}"][frame % 4],
+        _ => return None,
+    };
+    Some(emoji)
+}
+
+/// where a sprite's emoji goes in the emoji render mode: the tile under its center, on a grid
+/// two cells wide so every emoji lines up.
+pub fn emoji_tile(
+    info: &sprite::LoadedSprite,
+    sprite: &state::Sprite,
+    pos: &state::Position,
+) -> Option<(i64, i64, &'static str)> {
+    let emoji = emoji_for(&info.name, sprite.frame)?;
+    let size = &info.data.frames[sprite.frame].source_size;
+    let x = pos.x + size.w as i64 / 2;
+    let y = pos.y + (size.h as i64 + 1) / 4;
+    Some((x - x.rem_euclid(2), y, emoji))
+}
+
+/// redraw a frame of sprites as a coarse grid for the emoji render mode: each tile, two cells
+/// wide, becomes a flat block of its average color, then the emoji in 'tiles' are laid over it
+/// in order.
+pub fn render_emoji_tiles(scr: &mut display::Screen, tiles: &[(i64, i64, &'static str)]) {
+    let rgb = |color: Option<display::Color>| match color {
+        Some(display::Color::Rgb { r, g, b }) => [r, g, b].map(u32::from),
+        _ => [0, 0, 0],
+    };
+    let size = scr.size();
+    for y in 0..size.height as u16 {
+        for x in (0..size.width as u16).step_by(2) {
+            let cells = [x, x + 1].map(|x| scr.get(display::ScreenPos { x, y }));
+            let colors: Vec<[u32; 3]> = cells
+                .iter()
+                .flatten()
+                .filter(|(c, _)| HB_CHARS.contains(c))
+                .flat_map(|(_, style)| [rgb(style.fg), rgb(style.bg)])
+                .collect();
+            if colors.is_empty() {
+                continue;
+            }
+            let n = colors.len() as u32;
+            let [r, g, b] = [0, 1, 2].map(|i| (colors.iter().map(|c| c[i]).sum::<u32>() / n) as u8);
+            let color = display::Color::Rgb { r, g, b };
+            for x in [x, x + 1]
+                .into_iter()
+                .filter(|&x| (x as usize) < size.width)
+            {
+                scr.put(' ', cell_style(color, color), display::ScreenPos { x, y });
+            }
+        }
+    }
+    for &(x, y, emoji) in tiles {
+        if x < 0 || y < 0 || x + 1 >= size.width as i64 || y >= size.height as i64 {
+            continue;
+        }
+        let pos = display::ScreenPos {
+            x: x as u16,
+            y: y as u16,
+        };
+        let style = scr
+            .get(pos)
+            .map_or(display::Style::new(), |(_, style)| style);
+        scr.put_grapheme(emoji, style, pos);
+    }
+}
+
+pub fn render_sprite_at_pos(
     scr: &mut display::Screen,
     info: &sprite::LoadedSprite,
     sprite: &state::Sprite,
     pos: &state::Position,
+    highlight: Highlight,
+    tint: Option<state::Tint>,
+    time: u64,
 ) {
     let sz = {
         let scr_size = scr.size();
         (scr_size.width as u16, scr_size.height as u16)
     };
 
-    let active_frame_number = sprite.frame;
-    let active_frame_data = &info.data.frames[active_frame_number];
-    let frame = &active_frame_data.frame;
-    let image = &info.image;
-
-    let flip = sprite.flip;
-    let mut rows_vec: Vec<Vec<sprite::Rgba>> = vec![];
-    for y in frame.y..(frame.y + frame.h) {
-        let y = y as usize;
-        let mut row_vec: Vec<sprite::Rgba> = vec![];
-        if flip {
-            for x in (frame.x..(frame.x + frame.w)).rev() {
-                let x = x as usize;
-                row_vec.push(image[y][x]);
-            }
-        } else {
-            for x in frame.x..(frame.x + frame.w) {
-                let x = x as usize;
-                row_vec.push(image[y][x]);
-            }
-        }
-        rows_vec.push(row_vec);
-    }
+    let size = &info.data.frames[sprite.frame].source_size;
+
+    // fetch a pixel relative to the untrimmed frame, honoring flip and rotation
+    let pixel_at = |x: usize, y: usize| -> sprite::Pixel {
+        info.pixel(sprite.frame, x as u32, y as u32, sprite.flip)
+    };
+
+    // step the sheet's palette cycles, then if highlighting this sprite, change black outlines
+    // to bright, then tint it
+    let outline = highlight.outline.filter(|_| sprite.highlight);
+    let color_of = |px: sprite::Pixel| -> display::Color {
+        let color = match outline {
+            Some(color) if px.color == BLACK => color,
+            _ => info.cycled(px.color, time),
+        };
+        tint.map_or(color, |tint| tint.apply(color, time))
+    };
 
     // iterate over rows, plotting unicode characters to scr.put
-    'outer: for y in (0..rows_vec.len()).step_by(2) {
+    'outer: for y in (0..size.h as usize).step_by(2) {
         if (pos.y + y as i64) < 0 {
             continue 'outer;
         }
-        let scr_y = pos.y as u16 + (y / 2) as u16;
+        let scr_y = (pos.y + (y / 2) as i64) as u16;
         if scr_y > sz.1 - 1 {
             continue 'outer;
         }
 
-        let row = &rows_vec[y];
-        'inner: for (x, _item) in row.iter().enumerate() {
+        'inner: for x in 0..size.w as usize {
             if (pos.x + x as i64) < 0 {
                 continue 'inner;
             }
-            let scr_x = pos.x as u16 + x as u16;
+            let scr_x = (pos.x + x as i64) as u16;
             if scr_x > sz.0 - 1 {
                 continue 'inner;
             }
 
             // px2 is the pixel beneath px in the sprite image
-            let px = row[x];
-            let px2 = rows_vec[y + 1][x];
-
-            let (mut r, mut g, mut b, a) = (px.r, px.g, px.b, px.a);
-            let (mut r2, mut g2, mut b2, a2) = (px2.r, px2.g, px2.b, px2.a);
-
-            // if highlighting this sprite, change black outlines to bright
-            if sprite.highlight {
-                if r == 0 && g == 0 && b == 0 && a > 0 {
-                    (r, g, b) = (127, 255, 255);
-                }
-                if r2 == 0 && g2 == 0 && b2 == 0 && a2 > 0 {
-                    (r2, g2, b2) = (127, 255, 255);
-                }
+            let px = pixel_at(x, y);
+            let px2 = pixel_at(x, y + 1);
+            if !px.is_visible() && !px2.is_visible() {
+                continue 'inner;
             }
 
             // get the underlying pixel colors
-            let (u1, u2) = {
-                let black = display::Color::Rgb { r: 0, g: 0, b: 0 };
-                let (mut u1, mut u2) = (black, black);
-                let scr_get_result = scr.get(display::ScreenPos { x: scr_x, y: scr_y });
-                if let Some(under) = scr_get_result {
-                    let ch = under.0;
-                    let st = under.1;
-                    if ch == HB_CHARS[0] {
-                        u1 = st.fg.unwrap();
-                        u2 = st.bg.unwrap();
-                    } else if ch == HB_CHARS[1] {
-                        u1 = st.bg.unwrap();
-                        u2 = st.fg.unwrap();
-                    } else {
-                        u1 = display::Color::Rgb {
-                            r: px.r,
-                            g: px.g,
-                            b: px.b,
-                        };
-                        u2 = display::Color::Rgb {
-                            r: px2.r,
-                            g: px2.g,
-                            b: px2.b,
-                        };
-                    }
+            let screen_pos = display::ScreenPos { x: scr_x, y: scr_y };
+            let (u1, u2) = match scr.get(screen_pos) {
+                Some((ch, st)) if ch == HB_CHARS[0] => {
+                    (st.fg.unwrap_or(BLACK), st.bg.unwrap_or(BLACK))
+                }
+                Some((ch, st)) if ch == HB_CHARS[1] => {
+                    (st.bg.unwrap_or(BLACK), st.fg.unwrap_or(BLACK))
                 }
-                (u1, u2)
+                Some(_) => (px.color, px2.color),
+                None => (BLACK, BLACK),
             };
 
             // draw pixels to screen positions using HB_CHARS
-            // use style.set_fg and style.set_bg based on a, a2, etc
-            let mut style = display::Style::new();
-            if a > 0 {
-                style.set_fg(display::Color::Rgb { r, g, b });
-                if a2 > 0 {
-                    style.set_bg(display::Color::Rgb {
-                        r: r2,
-                        g: g2,
-                        b: b2,
-                    });
+            if px.is_visible() {
+                let bg = if px2.is_visible() { color_of(px2) } else { u2 };
+                scr.put(HB_CHARS[0], cell_style(color_of(px), bg), screen_pos);
+            } else {
+                scr.put(HB_CHARS[1], cell_style(color_of(px2), u1), screen_pos);
+            }
+        }
+    }
+
+    // or underline the sprite's bottom row, all the way across
+    if sprite.highlight && highlight.underline {
+        let y = pos.y + (size.h as i64 + 1) / 2 - 1;
+        for x in pos.x..pos.x + size.w as i64 {
+            if !(0..sz.0 as i64).contains(&x) || !(0..sz.1 as i64).contains(&y) {
+                continue;
+            }
+            let screen_pos = display::ScreenPos {
+                x: x as u16,
+                y: y as u16,
+            };
+            let (c, style) = scr.get(screen_pos).unwrap_or((' ', display::Style::new()));
+            let style = display::Style {
+                underline: true,
+                ..style
+            };
+            scr.put(c, style, screen_pos);
+        }
+    }
+}
+
+/// the dust storm's color, how much of it hazes everything, how rare its motes are, and the
+/// milliseconds the wind takes to carry them a cell.
+const DUST: (u8, u8, u8) = (170, 150, 120);
+const DUST_HAZE: u16 = 96;
+const DUST_MOTES: u64 = 17;
+const DUST_DRIFT: u64 = 80;
+
+const RAIN: (u8, u8, u8) = (150, 180, 230);
+/// one in this many streaks of falling pixels is a raindrop.
+const RAIN_DROPS: u64 = 23;
+/// game time for a raindrop to fall a pixel.
+const RAIN_FALL: u64 = 20;
+
+/// 'color' seen through the dust storm's haze.
+fn haze(color: display::Color) -> display::Color {
+    let display::Color::Rgb { r, g, b } = color else {
+        return color;
+    };
+    let mix = |c: u8, d: u8| ((c as u16 * (256 - DUST_HAZE) + d as u16 * DUST_HAZE) / 256) as u8;
+    display::Color::Rgb {
+        r: mix(r, DUST.0),
+        g: mix(g, DUST.1),
+        b: mix(b, DUST.2),
+    }
+}
+
+/// 'color' in a light letting through 'light' out of 256 of each of red, green and blue.
+fn light(color: display::Color, light: [u16; 3]) -> display::Color {
+    let display::Color::Rgb { r, g, b } = color else {
+        return color;
+    };
+    let shade = |c: u8, l: u16| (c as u16 * l / 256).min(255) as u8;
+    display::Color::Rgb {
+        r: shade(r, light[0]),
+        g: shade(g, light[1]),
+        b: shade(b, light[2]),
+    }
+}
+
+/// the time of day's light over everything drawn so far.
+fn render_daylight(scr: &mut display::Screen, daylight: [u16; 3]) {
+    if daylight == [256; 3] {
+        return;
+    }
+    let size = scr.size();
+    for y in 0..size.height as u16 {
+        for x in 0..size.width as u16 {
+            let pos = display::ScreenPos { x, y };
+            let Some((c, style)) = scr.get(pos) else {
+                continue;
+            };
+            let style = display::Style {
+                fg: style.fg.map(|color| light(color, daylight)),
+                bg: style.bg.map(|color| light(color, daylight)),
+                ..style
+            };
+            scr.put(c, style, pos);
+        }
+    }
+}
+
+/// corner marks in 'color' around the ground tile with its top left at 'tile' on screen.
+fn render_placement(scr: &mut display::Screen, tile: (i64, i64), color: display::Color) {
+    let (w, h) = ground::TILE;
+    let size = scr.size();
+    let mut style = display::Style::new();
+    style.set_fg(color);
+    for (dx, dy, c) in [
+        (0, 0, '┌'),
+        (w - 1, 0, '┐'),
+        (0, h - 1, '└'),
+        (w - 1, h - 1, '┘'),
+    ] {
+        let (x, y) = (tile.0 + dx, tile.1 + dy);
+        if (0..size.width as i64).contains(&x) && (0..size.height as i64).contains(&y) {
+            let pos = display::ScreenPos {
+                x: x as u16,
+                y: y as u16,
+            };
+            scr.put(c, style, pos);
+        }
+    }
+}
+
+/// haze over everything drawn so far, with motes of dust blowing across it. motes belong to
+/// world cells, so they drift with the wind rather than the camera.
+fn render_dust(scr: &mut display::Screen, time: u64, camera: (i64, i64)) {
+    let size = scr.size();
+    let drift = (time / DUST_DRIFT) as i64;
+    let (r, g, b) = DUST;
+    let mote = display::Color::Rgb { r, g, b };
+    for y in 0..size.height as u16 {
+        for x in 0..size.width as u16 {
+            let pos = display::ScreenPos { x, y };
+            let Some((ch, style)) = scr.get(pos).filter(|(ch, _)| HB_CHARS.contains(ch)) else {
+                continue;
+            };
+            let (fg, bg) = (style.fg.unwrap_or(BLACK), style.bg.unwrap_or(BLACK));
+            let (mut top, mut bottom) = if ch == HB_CHARS[0] {
+                (haze(fg), haze(bg))
+            } else {
+                (haze(bg), haze(fg))
+            };
+            let cell = (x as i64 + camera.0 - drift, y as i64 + camera.1 + drift / 5);
+            let hash = (cell.0 as u64)
+                .wrapping_mul(0x9e3779b97f4a7c15)
+                .wrapping_add((cell.1 as u64).wrapping_mul(0xc2b2ae3d27d4eb4f))
+                .rotate_left(29)
+                .wrapping_mul(0x94d049bb133111eb);
+            if (hash >> 32).is_multiple_of(DUST_MOTES) {
+                if hash & 1 == 0 {
+                    top = mote;
                 } else {
-                    style.set_bg(u2);
+                    bottom = mote;
                 }
-                scr.put(
-                    HB_CHARS[0],
-                    style,
-                    display::ScreenPos { x: scr_x, y: scr_y },
-                );
-            } else if a2 > 0 {
-                style.set_bg(u1);
-                style.set_fg(display::Color::Rgb {
-                    r: r2,
-                    g: g2,
-                    b: b2,
-                });
-                scr.put(
-                    HB_CHARS[1],
-                    style,
-                    display::ScreenPos { x: scr_x, y: scr_y },
-                );
             }
+            scr.put(HB_CHARS[0], cell_style(top, bottom), pos);
+        }
+    }
+}
+
+/// raindrops falling over everything drawn so far outside the dome, whose wall is at world
+/// column 'dome'. like dust motes, drops belong to world cells rather than the screen.
+fn render_rain(scr: &mut display::Screen, time: u64, camera: (i64, i64), dome: i64) {
+    let size = scr.size();
+    let fall = (time / RAIN_FALL) as i64;
+    let (r, g, b) = RAIN;
+    let drop = display::Color::Rgb { r, g, b };
+    let start = (dome - camera.0).clamp(0, size.width as i64) as u16;
+    for y in 0..size.height as u16 {
+        for x in start..size.width as u16 {
+            let pos = display::ScreenPos { x, y };
+            let Some((ch, style)) = scr.get(pos).filter(|(ch, _)| HB_CHARS.contains(ch)) else {
+                continue;
+            };
+            let (fg, bg) = (style.fg.unwrap_or(BLACK), style.bg.unwrap_or(BLACK));
+            let (mut top, mut bottom) = if ch == HB_CHARS[0] {
+                (fg, bg)
+            } else {
+                (bg, fg)
+            };
+            // each drop is a streak two pixels long, falling a pixel at a time
+            let col = x as i64 + camera.0;
+            let row = (y as i64 + camera.1) * 2;
+            let falling = |pixel: i64| {
+                let streak = (pixel - fall).div_euclid(2);
+                let hash = (col as u64)
+                    .wrapping_mul(0x9e3779b97f4a7c15)
+                    .wrapping_add((streak as u64).wrapping_mul(0xc2b2ae3d27d4eb4f))
+                    .rotate_left(29)
+                    .wrapping_mul(0x94d049bb133111eb);
+                (hash >> 32).is_multiple_of(RAIN_DROPS)
+            };
+            if falling(row) {
+                top = drop;
+            }
+            if falling(row + 1) {
+                bottom = drop;
+            }
+            scr.put(HB_CHARS[0], cell_style(top, bottom), pos);
         }
     }
 }
 
-fn render_text_at_pos(scr: &mut display::Screen, text: &str, start_x: u16, start_y: u16) {
+/// draw frame 'frame' of K's portrait beside 'text', the terminal text drawn at (1, 0), at game
+/// time 'time'.
+pub fn render_portrait(
+    scr: &mut display::Screen,
+    (store, ids): (&sprite::SpriteStore, &sprite::SpriteIds),
+    text: &str,
+    frame: usize,
+    time: u64,
+) {
+    let store_index = ids.portrait_k;
+    let width = text.lines().map(text_width).max().unwrap_or(0);
+    let sprite = state::Sprite {
+        store_index,
+        frame,
+        ..state::Sprite::default()
+    };
+    let pos = state::Position {
+        x: width as i64 + 2,
+        y: 0,
+    };
+    render_sprite_at_pos(
+        scr,
+        &store[store_index],
+        &sprite,
+        &pos,
+        Highlight::default(),
+        None,
+        time,
+    );
+}
+
+/// draw 'text' centered on a blank screen, for the ending.
+fn render_cutscene(scr: &mut display::Screen, text: &str) {
+    let size = scr.size();
+    let style = cell_style(BLACK, BLACK);
+    for y in 0..size.height as u16 {
+        for x in 0..size.width as u16 {
+            scr.put(' ', style, display::ScreenPos { x, y });
+        }
+    }
+    let lines: Vec<&str> = text.lines().collect();
+    let top = (size.height as u16).saturating_sub(lines.len() as u16) / 2;
+    for (i, line) in lines.iter().enumerate() {
+        let x = (size.width as u16).saturating_sub(text_width(line)) / 2;
+        render_text_at_pos(scr, line, x, top + i as u16);
+    }
+}
+
+pub fn render_text_at_pos(scr: &mut display::Screen, text: &str, start_x: u16, start_y: u16) {
     let sz = {
         let scr_size = scr.size();
         (scr_size.width, scr_size.height)
@@ -185,42 +700,118 @@ fn render_text_at_pos(scr: &mut display::Screen, text: &str, start_x: u16, start
     });
     style.set_bg(display::Color::Rgb { r: 0, g: 0, b: 95 });
 
+    // text is placed by grapheme, so combining marks stay with their base and wide chars
+    // like CJK and emoji take two columns
     let (mut x, mut y) = (start_x, start_y);
-    for ch in text.chars() {
-        if x > sz.0 as u16 - 1 {
-            break;
-        }
-
-        if ch == '\n' {
+    for grapheme in text.graphemes(true) {
+        if grapheme == "\n" || grapheme == "\r\n" {
             y += 1;
             x = start_x;
             continue;
         }
+        if x > sz.0 as u16 - 1 {
+            continue;
+        }
 
-        scr.put(ch, style, display::ScreenPos { x, y });
-        x += 1;
+        x += scr.put_grapheme(grapheme, style, display::ScreenPos { x, y });
     }
 }
 
+/// the columns 'text' takes on one line.
+pub fn text_width(text: &str) -> u16 {
+    text.width() as u16
+}
+
 impl<'a> System<'a> for RenderBuffer {
     type SystemData = (
-        Read<'a, state::Game>,
-        Read<'a, sprite::SpriteStore>,
+        (
+            Read<'a, state::Game>,
+            Read<'a, shop::Shop>,
+            Read<'a, shop::Prices>,
+            Read<'a, shop::Money>,
+        ),
+        Option<Read<'a, challenge::Challenge>>,
+        Option<Read<'a, timer::Timer>>,
+        Read<'a, state::RealTime>,
+        Read<'a, power::Power>,
+        Read<'a, state::Events>,
+        Read<'a, cues::Cues>,
+        Read<'a, settings::Settings>,
+        Read<'a, state::ScreenSize>,
+        Read<'a, state::Camera>,
+        Read<'a, display::RenderMode>,
+        (
+            Read<'a, sprite::SpriteStore>,
+            Read<'a, sprite::SpriteIds>,
+            Read<'a, ground::TileMap>,
+        ),
         ReadStorage<'a, state::Sprite>,
         ReadStorage<'a, state::Position>,
+        (
+            ReadStorage<'a, state::Hop>,
+            ReadStorage<'a, state::RenderLayer>,
+            ReadStorage<'a, state::Tint>,
+            ReadStorage<'a, state::Motion>,
+        ),
+        ReadStorage<'a, state::Player>,
+        ReadStorage<'a, state::Npc>,
+        ReadStorage<'a, robot::Robot>,
+        Read<'a, routine::Routines>,
+        Read<'a, savefile::Recovery>,
+        Read<'a, ending::Cutscene>,
+        Read<'a, state::Time>,
+        (
+            Read<'a, weather::Weather>,
+            Read<'a, daycycle::DayCycle>,
+            Read<'a, state::Dome>,
+        ),
+        Option<Write<'a, display::Frame>>,
+        (Entities<'a>, Option<Write<'a, Changes>>),
     );
 
     fn run(&mut self, data: Self::SystemData) {
         use specs::Join;
 
-        let (game, store, sprites, positions) = data;
+        let (
+            (game, shop, prices, money),
+            challenge,
+            timer,
+            real_time,
+            power,
+            events,
+            cues,
+            settings,
+            screen_size,
+            camera,
+            mode,
+            (store, ids, tile_map),
+            sprites,
+            positions,
+            (hops, layers, tints, motions),
+            players,
+            npcs,
+            robots,
+            routines,
+            recovery,
+            cutscene,
+            time,
+            (weather, day_cycle, dome),
+            frame,
+            (entities, mut changes),
+        ) = data;
         let scr = &mut self.screen;
+        let out = &mut self.out;
+
+        // follow the terminal's size, redrawing everything when it changes
+        let new_size = display::Size::new(screen_size.0 as usize, screen_size.1 as usize);
+        if scr.size() != new_size {
+            scr.resize(new_size);
+            scr.clear_all(&mut *out).expect("scr clear all error");
+        }
 
         if game.clear_screen {
-            scr.clear_all(io::stdout()).expect("scr clear all error");
-            scr.render(io::stdout()).expect("scr render error");
-        } else {
-            scr.erase();
+            scr.clear_all(&mut *out).expect("scr clear all error");
+            scr.render(&mut *out).expect("scr render error");
         }
 
         let sz = {
@@ -236,17 +827,188 @@ impl<'a> System<'a> for RenderBuffer {
         }
         let mut debug_numbers: Vec<DebugFrameNumber> = vec![];
 
-        // get sorted sprites by 'z' on position
-        let mut sorted_sprites = (&positions, &sprites).join().collect::<Vec<_>>();
-        sorted_sprites.sort_by(|a, b| {
-            a.0.z
-                .partial_cmp(&b.0.z)
-                .unwrap_or(std::cmp::Ordering::Equal)
-        });
+        // get sprites in drawing order, leaving out hidden and deleted ones and any entirely off
+        // screen. hopping players are drawn above where they stand, and everything is drawn
+        // relative to the camera
+        let mut sorted_sprites = (
+            &entities,
+            &positions,
+            (&motions).maybe(),
+            &sprites,
+            (&hops).maybe(),
+            (&layers).maybe(),
+            (&tints).maybe(),
+        )
+            .join()
+            .filter(|(_, _, _, sprite, _, _, _)| !sprite.hidden && !sprite.delete)
+            .map(|(entity, pos, motion, sprite, hop, layer, tint)| {
+                let pos = state::drawn_pos(pos, motion);
+                let order = state::draw_order(layer, &pos, sprite);
+                let height = hop.map_or(0, |hop| hop.height(time.0));
+                let pos = state::Position {
+                    x: pos.x - camera.0,
+                    y: pos.y - height - camera.1,
+                };
+                let cycling = !store[sprite.store_index]
+                    .data
+                    .meta
+                    .palette_cycles
+                    .is_empty();
+                let live = motion.is_some()
+                    || hop.is_some()
+                    || tint.is_some()
+                    || sprite.highlight
+                    || cycling;
+                (entity, pos, sprite, order, tint, live)
+            })
+            .filter(|(_, pos, sprite, _, _, _)| {
+                let size = &store[sprite.store_index].data.frames[sprite.frame].source_size;
+                pos.x + (size.w as i64) > 0
+                    && pos.x < sz.0 as i64
+                    && pos.y + (size.h as i64 + 1) / 2 > 0
+                    && pos.y < sz.1 as i64
+            })
+            .collect::<Vec<_>>();
+        sorted_sprites.sort_by_key(|(_, _, _, order, _, _)| *order);
+
+        // sprites and positions written since the last frame may need compositing again
+        let mut written = BitSet::new();
+        let mut redraw = true;
+        if let Some(changes) = changes.as_deref_mut() {
+            for event in sprites.channel().read(&mut changes.sprites) {
+                let (ComponentEvent::Inserted(id)
+                | ComponentEvent::Modified(id)
+                | ComponentEvent::Removed(id)) = event;
+                written.add(*id);
+            }
+            for event in positions.channel().read(&mut changes.positions) {
+                let (ComponentEvent::Inserted(id)
+                | ComponentEvent::Modified(id)
+                | ComponentEvent::Removed(id)) = event;
+                written.add(*id);
+            }
+            redraw = std::mem::take(&mut changes.redraw);
+        }
+
+        // everything is composited again when the view changes, and otherwise just the cells
+        // where a sprite is drawn differently than last frame, or was drawn and now isn't
+        let view = (new_size, (camera.0, camera.1), *mode);
+        let full = redraw
+            || game.clear_screen
+            || self.view != Some(view)
+            || self.tiles.len() != tile_map.tiles.len();
+        self.view = Some(view);
+        let highlight = Highlight::new(&settings.theme, real_time.0);
+        let mut dirty: Vec<display::Rect> = vec![];
+        let mut drawn = HashMap::with_capacity(sorted_sprites.len());
+        for (entity, pos, sprite, order, tint, live) in sorted_sprites.iter() {
+            let last = self.drawn.remove(entity);
+            let now = match last {
+                Some(last) if !full && !last.live && !*live && !written.contains(entity.id()) => {
+                    last
+                }
+                _ => {
+                    let info = &store[sprite.store_index];
+                    let size = &info.data.frames[sprite.frame].source_size;
+                    let tick = tint.is_some() || !info.data.meta.palette_cycles.is_empty();
+                    Drawn {
+                        rect: display::Rect {
+                            x: pos.x,
+                            y: pos.y,
+                            w: size.w as i64,
+                            h: (size.h as i64 + 1) / 2,
+                        },
+                        store_index: sprite.store_index,
+                        frame: sprite.frame,
+                        flip: sprite.flip,
+                        order: *order,
+                        outline: highlight.outline.filter(|_| sprite.highlight),
+                        underline: sprite.highlight && highlight.underline,
+                        tick: tick.then_some(time.0),
+                        live: *live,
+                    }
+                }
+            };
+            if last != Some(now) {
+                dirty.push(now.rect);
+                dirty.extend(last.map(|last| last.rect));
+            }
+            drawn.insert(*entity, now);
+        }
+        dirty.extend(self.drawn.values().map(|gone| gone.rect));
+        self.drawn = drawn;
+
+        // ground tiles in view, and the ones drawn differently than last frame
+        let (tile_w, tile_h) = ground::TILE;
+        let tiles: Vec<(usize, display::Rect)> = tile_map
+            .in_view((camera.0, camera.1), sz)
+            .map(|(col, row)| {
+                let rect = display::Rect {
+                    x: col * tile_w - camera.0,
+                    y: row * tile_h - camera.1,
+                    w: tile_w,
+                    h: tile_h,
+                };
+                ((row * tile_map.cols + col) as usize, rect)
+            })
+            .collect();
+        if !full {
+            dirty.extend(
+                tiles
+                    .iter()
+                    .filter(|(i, _)| self.tiles[*i] != tile_map.tiles[*i])
+                    .map(|(_, rect)| *rect),
+            );
+        }
+        self.tiles.clone_from(&tile_map.tiles);
 
-        for (pos, sprite) in sorted_sprites.iter_mut() {
-            let info = &store.0[sprite.store_index];
-            render_sprite_at_pos(scr, info, sprite, pos);
+        // a changed cell is composited from every sprite over it on the scratch screen, then
+        // copied to the layer
+        if self.layer.size() != new_size {
+            self.layer.resize(new_size);
+            self.scratch.resize(new_size);
+        }
+        if full || !dirty.is_empty() {
+            let target = if full {
+                &mut self.layer
+            } else {
+                &mut self.scratch
+            };
+            target.erase();
+            for (i, rect) in tiles.iter() {
+                if !full && !dirty.iter().any(|dirty| dirty.overlaps(rect)) {
+                    continue;
+                }
+                let tile = &tile_map.tiles[*i];
+                let pos = state::Position {
+                    x: rect.x,
+                    y: rect.y,
+                };
+                let info = &store[tile.store_index];
+                render_sprite_at_pos(target, info, &tile.sprite(), &pos, highlight, None, time.0);
+            }
+            for (entity, pos, sprite, _, tint, _) in sorted_sprites.iter() {
+                let rect = self.drawn[entity].rect;
+                if !full && !dirty.iter().any(|dirty| dirty.overlaps(&rect)) {
+                    continue;
+                }
+                let info = &store[sprite.store_index];
+                render_sprite_at_pos(target, info, sprite, pos, highlight, tint.copied(), time.0);
+            }
+            if !full {
+                for rect in dirty.iter() {
+                    self.layer.copy_rect(&self.scratch, *rect);
+                }
+            }
+        }
+        scr.copy_from(&self.layer);
+
+        let mut emoji_tiles = vec![];
+        for (_, pos, sprite, _, _, _) in sorted_sprites.iter() {
+            let info = &store[sprite.store_index];
+            if *mode == display::RenderMode::Emoji {
+                emoji_tiles.extend(emoji_tile(info, sprite, pos));
+            }
 
             // collect debug information
             if cfg!(debug_assertions)
@@ -262,20 +1024,135 @@ impl<'a> System<'a> for RenderBuffer {
                 });
             }
         }
+        for (pos, sprite) in
+            state::emote_bubbles((&store, &ids), &npcs, &sprites, &positions, &motions)
+        {
+            let info = &store[sprite.store_index];
+            let pos = state::Position {
+                x: pos.x - camera.0,
+                y: pos.y - camera.1,
+            };
+            render_sprite_at_pos(scr, info, &sprite, &pos, Highlight::default(), None, time.0);
+            if *mode == display::RenderMode::Emoji {
+                emoji_tiles.extend(emoji_tile(info, &sprite, &pos));
+            }
+        }
+        if let Some((x, y)) = game.placement {
+            render_placement(
+                scr,
+                (x - camera.0, y - camera.1),
+                theme_color(&settings.theme),
+            );
+        }
+        if weather.today == weather::Sky::Rain {
+            render_rain(scr, time.0, (camera.0, camera.1), dome.x);
+        }
+        render_daylight(scr, day_cycle.light(time.0));
+        if weather.today == weather::Sky::Dust {
+            render_dust(scr, time.0, (camera.0, camera.1));
+        }
+        if *mode == display::RenderMode::Emoji {
+            render_emoji_tiles(scr, &emoji_tiles);
+        }
+
+        // K's heart meter, over their bubble for a moment after being greeted
+        for (npc, pos) in (&npcs, &positions).join() {
+            if npc.shows_hearts(time.0) {
+                let (x, y) = (pos.x - camera.0, pos.y - camera.1 - 5);
+                if x >= 0 && y >= 0 {
+                    render_text_at_pos(scr, &game.heart_meter(), x as u16, y as u16);
+                }
+            }
+        }
 
         if game.show_terminal {
-            let no_messages = "No new messages.".to_string();
-            let message = if game.terminal_read {
-                &no_messages
+            if let Some(text) = shop.text(&game, &prices, *money, settings.layout) {
+                render_text_at_pos(scr, &text, 1, 0);
             } else {
-                &game.terminal_messages[game.terminal_message_index]
-            };
-            render_text_at_pos(scr, message.as_str(), 1, 0);
+                let text = game.terminal_text(settings.layout);
+                render_text_at_pos(scr, &text, 1, 0);
+                if let Some(frame) = game.portrait() {
+                    render_portrait(scr, (&store, &ids), &text, frame, time.0);
+                }
+            }
+        }
+
+        // the robot's console, for the local player
+        if let Some(robot) = robots
+            .join()
+            .find(|r| r.console.is_some_and(|(slot, _)| slot == 0))
+        {
+            render_text_at_pos(scr, &robot.console_text(&routines, settings.layout), 1, 0);
+        }
+
+        if let Some(damaged) = recovery.0.first() {
+            render_text_at_pos(scr, &damaged.prompt(settings.layout), 1, 0);
+        }
+
+        if let Some(timer) = &timer {
+            let status = timer.status(real_time.0);
+            let x = sz.0.saturating_sub(text_width(&status) + 1);
+            render_text_at_pos(scr, &status, x, 0);
+        }
+
+        // the local player's oxygen, while it's draining outside the dome
+        if let Some(player) = players.join().find(|p| p.slot == 0) {
+            let oxygen = player.oxygen(time.0);
+            if oxygen < 1.0 {
+                let status = format!("oxygen {:.0}%", oxygen * 100.0);
+                let x = sz.0.saturating_sub(text_width(&status) + 1);
+                render_text_at_pos(scr, &status, x, 1);
+            }
+        }
+
+        let pocket = state::SEEDS
+            .iter()
+            .map(|(seed, name)| (*name, game.seed_count(*seed)))
+            .chain([("fiber", game.fiber), ("fertilizer", game.fertilizer)])
+            .chain(
+                state::PRODUCE
+                    .iter()
+                    .map(|(item, name)| (*name, game.pocket_count(*item))),
+            )
+            .chain([("money", money.0)])
+            .filter(|(_, count)| *count > 0)
+            .map(|(name, count)| format!("{} {}", name, count))
+            .chain(power.status())
+            .collect::<Vec<_>>()
+            .join(" | ");
+        if !pocket.is_empty() {
+            let x = sz.0.saturating_sub(text_width(&pocket) + 1);
+            render_text_at_pos(scr, &pocket, x, sz.1 - 2);
+        }
+
+        // which seeds the packet plants, while the local player holds it
+        let holding = players.join().find(|p| p.slot == 0).map(|p| p.holding);
+        if let (Some(state::ItemType::Packet), Some(selector)) =
+            (holding, game.seed_selector(settings.layout))
+        {
+            let x = sz.0.saturating_sub(text_width(&selector) + 1);
+            render_text_at_pos(scr, &selector, x, sz.1 - 3);
+        }
+
+        if let Some(challenge) = &challenge {
+            render_text_at_pos(scr, &challenge.status(game.day), 0, sz.1 - 2);
         }
 
         if game.show_help {
-            let tooltip = "arrows/hjkl: move | q: quit | space: pickup | u: use | ?: hide help ";
-            render_text_at_pos(scr, tooltip, 0, sz.1 - 1);
+            let key = |qwerty| settings.layout.from_qwerty(qwerty);
+            let tooltip = format!(
+                "arrows/{}{}{}{}: move | {}: quit | space: pickup | {}: use | {}: speed {}x | {}: mute | ?: hide help ",
+                key('h'),
+                key('j'),
+                key('k'),
+                key('l'),
+                key('q'),
+                key('u'),
+                key('f'),
+                settings.speed(),
+                key('m')
+            );
+            render_text_at_pos(scr, &tooltip, 0, sz.1 - 1);
 
             if cfg!(debug_assertions) {
                 for debug in debug_numbers {
@@ -284,6 +1161,34 @@ impl<'a> System<'a> for RenderBuffer {
             }
         }
 
-        scr.render(io::stdout()).expect("scr render error");
+        // an ending covers everything else
+        if let Some(text) = cutscene.text(settings.layout) {
+            render_cutscene(scr, &text);
+        }
+
+        apply_mode(scr, *mode);
+        if let Some(mut frame) = frame {
+            *frame = scr.frame();
+        }
+        scr.render(&mut *out).expect("scr render error");
+
+        let cue = cues.for_events(&events.0);
+        if !cue.is_empty() && !settings.muted {
+            write!(out, "{}", cue).expect("cue error");
+            out.flush().expect("cue error");
+        }
+
+        let mut title = game.title();
+        if matches!(
+            *mode,
+            display::RenderMode::Ascii | display::RenderMode::Legacy
+        ) {
+            title = title.chars().map(ascii_char).collect();
+        }
+        if title != self.title {
+            display::set_title(out, &title).expect("set title error");
+            out.flush().expect("set title error");
+            self.title = title;
+        }
     }
 }
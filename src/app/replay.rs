@@ -0,0 +1,272 @@
+use super::{
+    display, routine, savefile, settings, state, timer, App, AppError, IOWrite, InputState,
+};
+use serde::{Deserialize, Serialize};
+use specs::{Join, World, WorldExt};
+use std::sync::{Arc, Mutex};
+
+/// a recorded session: the seed, screen size, settings and robot routines it started with, then
+/// the local player's input each tick. the game plays out the same way from these every time,
+/// so a replay also proves a speedrun: it ends with the timer's time and a hash of the world.
+/// a guest's input isn't recorded, so co-op sessions don't play back the same.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Replay {
+    pub seed: u64,
+    pub size: (u16, u16),
+    pub settings: settings::Settings,
+    #[serde(default)]
+    pub routines: String,
+    /// milliseconds since the last tick, and the input handled in it.
+    #[serde(with = "ticks")]
+    pub ticks: Vec<(u64, InputState)>,
+    /// the speedrun timer's time for the finished story, if the session was timed.
+    #[serde(default)]
+    pub time: Option<u64>,
+    /// state_hash of the world when the recording stopped.
+    #[serde(default)]
+    pub hash: Option<u64>,
+}
+
+/// inputs as single characters, mostly their keys, so a long session stays small.
+const INPUT_CODES: [(InputState, char); 20] = [
+    (InputState::Up, 'k'),
+    (InputState::Down, 'j'),
+    (InputState::Left, 'h'),
+    (InputState::Right, 'l'),
+    (InputState::ShiftUp, 'K'),
+    (InputState::ShiftDown, 'J'),
+    (InputState::ShiftLeft, 'H'),
+    (InputState::ShiftRight, 'L'),
+    (InputState::Pickup, 'p'),
+    (InputState::Action, 'u'),
+    (InputState::ToggleHelp, '?'),
+    (InputState::CycleSpeed, 'f'),
+    (InputState::Mute, 'm'),
+    (InputState::Undo, 'z'),
+    (InputState::Craft, 'c'),
+    (InputState::Hop, 'x'),
+    (InputState::NextTarget, 't'),
+    (InputState::Quit, 'q'),
+    (InputState::Clear, 'r'),
+    (InputState::Backspace, '<'),
+];
+
+/// ticks are written as one string, each tick its milliseconds followed by its input's code,
+/// if any: "70 71l 70u". a typed character is ' and its code point in hex: "70'61", and a
+/// click is @ and its world cell: "70@12,-2".
+mod ticks {
+    use super::{InputState, INPUT_CODES};
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(ticks: &[(u64, InputState)], s: S) -> Result<S::Ok, S::Error> {
+        let codes = ticks.iter().map(|&(dt, input)| match input {
+            InputState::Char(c) => format!("{}'{:x}", dt, c as u32),
+            InputState::Click(x, y) => format!("{}@{},{}", dt, x, y),
+            _ => match INPUT_CODES.iter().find(|(i, _)| *i == input) {
+                Some((_, code)) => format!("{}{}", dt, code),
+                None => dt.to_string(),
+            },
+        });
+        s.serialize_str(&codes.collect::<Vec<_>>().join(" "))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        d: D,
+    ) -> Result<Vec<(u64, InputState)>, D::Error> {
+        let text = String::deserialize(d)?;
+        text.split_whitespace()
+            .map(|tick| {
+                let digits = tick
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(tick.len());
+                let dt = tick[..digits].parse().map_err(D::Error::custom)?;
+                let bad = || D::Error::custom(format!("bad tick '{}'", tick));
+                let input = match &tick[digits..] {
+                    "" => InputState::None,
+                    code if code.starts_with('\'') => u32::from_str_radix(&code[1..], 16)
+                        .ok()
+                        .and_then(char::from_u32)
+                        .map(InputState::Char)
+                        .ok_or_else(bad)?,
+                    code if code.starts_with('@') => code[1..]
+                        .split_once(',')
+                        .and_then(|(x, y)| {
+                            Some(InputState::Click(x.parse().ok()?, y.parse().ok()?))
+                        })
+                        .ok_or_else(bad)?,
+                    code => INPUT_CODES
+                        .iter()
+                        .find(|(_, c)| code.chars().eq([*c]))
+                        .map(|(input, _)| *input)
+                        .ok_or_else(bad)?,
+                };
+                Ok((dt, input))
+            })
+            .collect()
+    }
+}
+
+/// a hash of everything that makes up the game's progress: the time, the pocket and story,
+/// every sprite, and what the players hold. two playbacks of a replay hash the same.
+pub fn state_hash(world: &World) -> u64 {
+    let game = world.read_resource::<state::Game>();
+    let mut text = format!(
+        "{} {} {} {} {} {} {} {}",
+        world.read_resource::<state::Time>().0,
+        game.day,
+        game.seeds,
+        game.seeds2,
+        game.fiber,
+        game.fertilizer,
+        game.terminal_message_index,
+        game.hearts,
+    );
+    for flag in &game.flags {
+        text.push_str(&format!(" {}", flag));
+    }
+    for (i, reply) in &game.replies {
+        text.push_str(&format!(" {}:{}", i, reply));
+    }
+    let (sprites, positions) = (
+        world.read_storage::<state::Sprite>(),
+        world.read_storage::<state::Position>(),
+    );
+    let mut placed: Vec<_> = (&sprites, &positions)
+        .join()
+        .map(|(s, p)| (s.id, s.store_index, s.frame, p.x, p.y))
+        .collect();
+    placed.sort();
+    for sprite in placed {
+        text.push_str(&format!(" {:?}", sprite));
+    }
+    for player in world.read_storage::<state::Player>().join() {
+        text.push_str(&format!(" {} {:?}", player.slot, player.holding));
+    }
+    savefile::checksum(text.as_bytes())
+}
+
+impl Replay {
+    pub fn load(path: &str) -> Result<Replay, AppError> {
+        Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), AppError> {
+        std::fs::write(path, serde_json::to_vec(self)?)?;
+        Ok(())
+    }
+}
+
+/// output kept in memory so each tick's bytes can be taken as one cast event.
+#[derive(Clone, Default)]
+struct Captured(Arc<Mutex<Vec<u8>>>);
+
+impl IOWrite for Captured {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// a game set up the way 'replay' started, drawing to 'out'. a timed replay gets a timer that
+/// doesn't touch the player's profile.
+fn playback<'a>(replay: &Replay, out: Box<dyn IOWrite + Send>) -> Result<App<'a>, AppError> {
+    let mut app = App::with_world(
+        replay.size,
+        replay.settings.world_size(),
+        out,
+        Some(replay.seed),
+    )?;
+    app.world.insert(replay.settings.clone());
+    app.world
+        .insert(routine::Routines::parse(&replay.routines)?);
+    if replay.time.is_some() {
+        let mut timer = timer::Timer::new(timer::Profile::default());
+        timer.save = false;
+        app.world.insert(timer);
+    }
+    Ok(app)
+}
+
+/// play 'replay' back without a terminal, writing what it draws to 'out' as an asciinema v2
+/// cast. the cast keeps the size the recording started at, so resizes only redraw it.
+pub fn write_cast(replay: &Replay, out: &mut impl IOWrite) -> Result<(), AppError> {
+    let captured = Captured::default();
+    let mut app = playback(replay, Box::new(captured.clone()))?;
+
+    let header = serde_json::json!({
+        "version": 2,
+        "width": replay.size.0,
+        "height": replay.size.1,
+        "title": "queer folk farmpunk",
+    });
+    writeln!(out, "{}", header)?;
+    let start = format!("{}{}", display::HIDE_CURSOR, display::CLEAR);
+    writeln!(out, "{}", serde_json::json!([0.0, "o", start]))?;
+
+    let mut time = 0;
+    for &(dt, input) in &replay.ticks {
+        time += dt;
+        app.set_input(input);
+        app.tick(dt)?;
+        let bytes = std::mem::take(&mut *captured.0.lock().unwrap());
+        if !bytes.is_empty() {
+            let text = String::from_utf8_lossy(&bytes);
+            let event = serde_json::json!([time as f64 / 1000.0, "o", text]);
+            writeln!(out, "{}", event)?;
+        }
+    }
+    Ok(())
+}
+
+/// play 'replay' back and check it ends where it says it did, returning the timer's time.
+pub fn verify(replay: &Replay) -> Result<Option<u64>, AppError> {
+    let hash = replay
+        .hash
+        .ok_or(AppError::Mismatch("no final state".to_string()))?;
+    let mut app = playback(replay, Box::new(std::io::sink()))?;
+    for &(dt, input) in &replay.ticks {
+        app.set_input(input);
+        app.tick(dt)?;
+    }
+    let time = app
+        .world
+        .try_fetch::<timer::Timer>()
+        .and_then(|timer| timer.finished);
+    if time != replay.time {
+        let format = |time: Option<u64>| time.map_or("unfinished".to_string(), timer::format_time);
+        return Err(AppError::Mismatch(format!(
+            "timed {} but claims {}",
+            format(time),
+            format(replay.time)
+        )));
+    }
+    if state_hash(&app.world) != hash {
+        return Err(AppError::Mismatch("the final state differs".to_string()));
+    }
+    Ok(time)
+}
+
+pub fn run_verify(args: &[String]) -> Result<(), AppError> {
+    let usage = || AppError::Usage("qffp verify <replay.json>".to_string());
+    let replay = Replay::load(args.first().ok_or_else(usage)?)?;
+    match verify(&replay)? {
+        Some(time) => println!("verified: finished in {}", timer::format_time(time)),
+        None => println!("verified: the story wasn't finished"),
+    }
+    Ok(())
+}
+
+pub fn run_cast(args: &[String]) -> Result<(), AppError> {
+    let usage = || AppError::Usage("qffp cast <replay.json> <out.cast>".to_string());
+    let replay_path = args.first().ok_or_else(usage)?;
+    let cast_path = args.get(1).ok_or_else(usage)?;
+    let replay = Replay::load(replay_path)?;
+    let mut out = std::io::BufWriter::new(std::fs::File::create(cast_path)?);
+    write_cast(&replay, &mut out)?;
+    out.flush()?;
+    Ok(())
+}
@@ -0,0 +1,293 @@
+use super::{routine, settings, sprite, state, InputState};
+use specs::{
+    Component, Entities, Join, Read, ReadStorage, System, VecStorage, Write, WriteStorage,
+};
+use std::fmt;
+use std::str::FromStr;
+
+/// lines in a robot's program.
+pub const PROGRAM_LEN: usize = 4;
+
+/// milliseconds between the robot's steps, and how close it gets to a crop before working on it.
+const STEP_TIME: u64 = 150;
+const REACH: i64 = 2;
+
+/// the tint of a robot being programmed, to pick it out from any others.
+const PROGRAMMING: state::Tint = state::Tint::Multiply([255, 220, 140]);
+
+/// something the robot can do every morning. rows are the 4-cell bands of the dirt tiles,
+/// counted from 1 at the top of the farm.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    WaterAll,
+    WaterRow(u32),
+    HarvestLeaves,
+    HarvestFlowers,
+}
+
+impl Command {
+    /// every command on a farm 'rows' high, in the order the console cycles through them.
+    pub fn choices(rows: u32) -> Vec<Command> {
+        let mut choices = vec![Command::WaterAll];
+        choices.extend((1..=rows).map(Command::WaterRow));
+        choices.extend([Command::HarvestLeaves, Command::HarvestFlowers]);
+        choices
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Command::WaterAll => write!(f, "water all"),
+            Command::WaterRow(row) => write!(f, "water row {}", row),
+            Command::HarvestLeaves => write!(f, "harvest leaves"),
+            Command::HarvestFlowers => write!(f, "harvest flowers"),
+        }
+    }
+}
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Command, String> {
+        let words: Vec<&str> = s.split_whitespace().collect();
+        match words.as_slice() {
+            ["water", "all"] => Ok(Command::WaterAll),
+            ["water", "row", row] => match row.parse() {
+                Ok(row) if row > 0 => Ok(Command::WaterRow(row)),
+                _ => Err(format!("bad row number '{}'", row)),
+            },
+            ["harvest", "leaves"] => Ok(Command::HarvestLeaves),
+            ["harvest", "flowers"] => Ok(Command::HarvestFlowers),
+            _ => Err(format!("unknown command '{}'", s.trim())),
+        }
+    }
+}
+
+/// specs component for the robot helper, which runs its program every morning.
+#[derive(Component, Debug, Default)]
+#[storage(VecStorage)]
+pub struct Robot {
+    pub program: [Option<Command>; PROGRAM_LEN],
+    /// the player slot editing the program at the robot's console, and the line they're on.
+    pub console: Option<(usize, usize)>,
+    /// the day the program last ran.
+    pub day: u32,
+    /// crops left to work on this morning, by sprite id.
+    pub tasks: Vec<(usize, Command)>,
+    pub last_move: u64,
+}
+
+impl Robot {
+    /// handle a player's input at the console: up and down pick a line, left and right cycle
+    /// its command, and use or pickup closes the console.
+    pub fn edit(&mut self, input: InputState, rows: u32) {
+        let Some((slot, line)) = self.console else {
+            return;
+        };
+        let choices = Command::choices(rows);
+        // an empty line comes before the first command and after the last
+        let index = self.program[line]
+            .and_then(|c| choices.iter().position(|&choice| choice == c))
+            .map_or(0, |i| i + 1);
+        let cycle = |step: usize| {
+            let index = (index + step) % (choices.len() + 1);
+            index.checked_sub(1).map(|i| choices[i])
+        };
+        match input {
+            InputState::Up | InputState::ShiftUp => {
+                self.console = Some((slot, line.saturating_sub(1)));
+            }
+            InputState::Down | InputState::ShiftDown => {
+                self.console = Some((slot, (line + 1).min(PROGRAM_LEN - 1)));
+            }
+            InputState::Left | InputState::ShiftLeft => {
+                self.program[line] = cycle(choices.len());
+            }
+            InputState::Right | InputState::ShiftRight => {
+                self.program[line] = cycle(1);
+            }
+            InputState::Action | InputState::Pickup => self.console = None,
+            _ => {}
+        }
+    }
+
+    /// the console's text, for the player editing it, followed by any routines from the script.
+    /// keys are shown as typed on 'layout'.
+    pub fn console_text(&self, routines: &routine::Routines, layout: settings::Layout) -> String {
+        let line = self.console.map_or(0, |(_, line)| line);
+        let mut text = format!(
+            "### Robot program, run every morning.\n{}: line | {}: command | {}: done",
+            layout.keys("j/k"),
+            layout.keys("h/l"),
+            layout.keys("u")
+        );
+        for (i, command) in self.program.iter().enumerate() {
+            let cursor = if i == line { ">" } else { " " };
+            let command = command.map_or("-".to_string(), |c| c.to_string());
+            text.push_str(&format!("\n{} {}. {}", cursor, i + 1, command));
+        }
+        if !routines.0.is_empty() {
+            text.push_str(&format!("\n### Routines from {}", routine::ROUTINES));
+            for routine in &routines.0 {
+                text.push_str(&format!("\n  {}", routine));
+            }
+        }
+        text
+    }
+}
+
+/// queues the robot's program and any routines due each morning, then walks it to each crop in
+/// turn to water or harvest it. a robot being carried doesn't work.
+pub struct RobotSystem;
+
+impl<'a> System<'a> for RobotSystem {
+    type SystemData = (
+        Entities<'a>,
+        Write<'a, state::Game>,
+        Write<'a, state::Events>,
+        Read<'a, state::Time>,
+        Read<'a, sprite::SpriteStore>,
+        Read<'a, sprite::SpriteIds>,
+        Read<'a, routine::Routines>,
+        WriteStorage<'a, Robot>,
+        WriteStorage<'a, state::Sprite>,
+        WriteStorage<'a, state::Position>,
+        ReadStorage<'a, state::Interactible>,
+        ReadStorage<'a, state::Player>,
+        WriteStorage<'a, state::Tint>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            mut game,
+            mut events,
+            time,
+            store,
+            ids,
+            routines,
+            mut robots,
+            mut sprites,
+            mut positions,
+            interactibles,
+            players,
+            mut tints,
+        ) = data;
+        for (entity, robot) in (&entities, &robots).join() {
+            if robot.console.is_some() {
+                let _ = tints.insert(entity, PROGRAMMING);
+            } else if tints.get(entity) == Some(&PROGRAMMING) {
+                tints.remove(entity);
+            }
+        }
+
+        let (crop_empty, crop_leaf, crop_flower) = (ids.crop_empty, ids.crop_leaf, ids.crop_flower);
+        // a crop wants water while it's dry, and harvesting once it's at its last stage
+        let dry =
+            |store_index: sprite::SpriteHandle, frame| store[store_index].tagged(frame, "dry");
+        let ripe =
+            |store_index: sprite::SpriteHandle, frame| state::ripe(&store[store_index], frame);
+        if players.join().any(|p| p.holding == state::ItemType::Robot) {
+            for (_, sprite) in (&robots, &mut sprites).join() {
+                sprite.animating = false;
+            }
+            return;
+        }
+
+        // crops as (sprite id, position, store index, frame)
+        let crops: Vec<(usize, (i64, i64), sprite::SpriteHandle, usize)> =
+            (&interactibles, &sprites, &positions)
+                .join()
+                .filter(|(item, _, _)| item.item_type == state::ItemType::Crop)
+                .map(|(_, sprite, pos)| {
+                    (sprite.id, (pos.x, pos.y), sprite.store_index, sprite.frame)
+                })
+                .collect();
+
+        let mut work = None;
+        for (robot, sprite, pos) in (&mut robots, &mut sprites, &mut positions).join() {
+            if robot.day != game.day {
+                robot.day = game.day;
+                robot.tasks.clear();
+                let program = robot.program.iter().flatten().copied();
+                let commands: Vec<Command> = program.chain(routines.due(game.day)).collect();
+                for command in &commands {
+                    for &(id, (_, y), store_index, frame) in &crops {
+                        let wanted = match command {
+                            Command::WaterAll => {
+                                store_index != crop_empty && dry(store_index, frame)
+                            }
+                            Command::WaterRow(row) => {
+                                store_index != crop_empty
+                                    && dry(store_index, frame)
+                                    && (y + 1) / 4 + 1 == *row as i64
+                            }
+                            Command::HarvestLeaves => {
+                                store_index == crop_leaf && ripe(store_index, frame)
+                            }
+                            Command::HarvestFlowers => {
+                                store_index == crop_flower && ripe(store_index, frame)
+                            }
+                        };
+                        if wanted && !robot.tasks.iter().any(|(task, _)| *task == id) {
+                            robot.tasks.push((id, *command));
+                        }
+                    }
+                }
+            }
+
+            // walk to the next crop that's still there, standing just left of it
+            robot
+                .tasks
+                .retain(|(id, _)| crops.iter().any(|crop| crop.0 == *id));
+            let Some(&(id, command)) = robot.tasks.first() else {
+                sprite.animating = false;
+                continue;
+            };
+            sprite.animating = true;
+            if robot.last_move + STEP_TIME > time.0 {
+                continue;
+            }
+            robot.last_move = time.0;
+            let (_, (x, y), _, _) = crops.iter().find(|crop| crop.0 == id).expect("crop");
+            let target = (x - 6, y - 1);
+            let (dx, dy) = (target.0 - pos.x, target.1 - pos.y);
+            if dx.abs() <= REACH && dy.abs() <= REACH / 2 {
+                robot.tasks.remove(0);
+                work = Some((id, command));
+                continue;
+            }
+            sprite.flip = dx < 0;
+            pos.x += dx.clamp(-2, 2);
+            pos.y += dy.clamp(-1, 1);
+        }
+
+        let Some((id, command)) = work else {
+            return;
+        };
+        for sprite in (&mut sprites).join().filter(|s| s.id == id) {
+            match command {
+                Command::WaterAll | Command::WaterRow(_) => {
+                    let info = &store[sprite.store_index];
+                    if let Some(watered) = info.retag(sprite.frame, "dry", "watered") {
+                        sprite.frame = watered;
+                        events.0.push(state::GameEvent::Water);
+                    }
+                }
+                Command::HarvestLeaves | Command::HarvestFlowers => {
+                    // a harvested crop leaves a dry plot, and its produce and seed go in the pocket
+                    let harvest = state::produce(&ids, sprite.store_index);
+                    for item_type in harvest.into_iter().flat_map(|(p, s)| [p, s]) {
+                        if let Some(count) = game.pocket(item_type) {
+                            *count += 1;
+                        }
+                    }
+                    sprite.store_index = crop_empty;
+                    sprite.frame = 0;
+                    events.0.push(state::GameEvent::Dig);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use super::{robot::Command, AppError};
+use std::fmt;
+use std::str::FromStr;
+
+/// daily routines for the robot, kept in this file in the working directory. each line is a
+/// schedule and a robot command separated by a colon, like "every 2 days: water all".
+/// blank lines and lines starting with '#' are ignored.
+pub const ROUTINES: &str = "routines.txt";
+
+/// which mornings a routine runs on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Schedule {
+    /// every n days, starting on the first day.
+    Every(u32),
+    /// only on this day.
+    Day(u32),
+}
+
+impl Schedule {
+    pub fn due(&self, day: u32) -> bool {
+        match self {
+            Schedule::Every(n) => day.saturating_sub(1).is_multiple_of(*n),
+            Schedule::Day(d) => day == *d,
+        }
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Schedule::Every(1) => write!(f, "every day"),
+            Schedule::Every(n) => write!(f, "every {} days", n),
+            Schedule::Day(d) => write!(f, "day {}", d),
+        }
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Schedule, String> {
+        let words: Vec<&str> = s.split_whitespace().collect();
+        let number = |n: &str| match n.parse() {
+            Ok(n) if n > 0 => Ok(n),
+            _ => Err(format!("bad number '{}'", n)),
+        };
+        match words.as_slice() {
+            ["every", "day"] => Ok(Schedule::Every(1)),
+            ["every", "other", "day"] => Ok(Schedule::Every(2)),
+            ["every", n, "days"] => number(n).map(Schedule::Every),
+            ["day", n] => number(n).map(Schedule::Day),
+            _ => Err(format!("unknown schedule '{}'", s.trim())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Routine {
+    pub schedule: Schedule,
+    pub command: Command,
+}
+
+impl fmt::Display for Routine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.schedule, self.command)
+    }
+}
+
+/// specs resource with the routines the robot runs alongside its own program.
+#[derive(Debug, Default)]
+pub struct Routines(pub Vec<Routine>);
+
+impl Routines {
+    /// parse a script, reporting the first bad line by number.
+    pub fn parse(script: &str) -> Result<Routines, AppError> {
+        let mut routines = vec![];
+        for (i, line) in script.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let routine = line
+                .split_once(':')
+                .ok_or("expected '<schedule>: <command>'".to_string())
+                .and_then(|(schedule, command)| {
+                    Ok(Routine {
+                        schedule: schedule.parse()?,
+                        command: command.parse()?,
+                    })
+                })
+                .map_err(|e| AppError::Routine(i + 1, e))?;
+            routines.push(routine);
+        }
+        Ok(Routines(routines))
+    }
+
+    /// load routines from 'path', with none if the file doesn't exist.
+    pub fn load(path: &str) -> Result<Routines, AppError> {
+        match std::fs::read_to_string(path) {
+            Ok(script) => Routines::parse(&script),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Routines::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// the commands due on the morning of 'day', in the order they're written.
+    pub fn due(&self, day: u32) -> impl Iterator<Item = Command> + '_ {
+        self.0
+            .iter()
+            .filter(move |r| r.schedule.due(day))
+            .map(|r| r.command)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_script() {
+        let script = "# the robot's week\n\nevery day: water all\n  every 3 days: water row 2\nevery other day: harvest leaves\nday 5: harvest flowers\n";
+        let routines = Routines::parse(script).unwrap();
+        assert_eq!(
+            routines.0,
+            vec![
+                Routine {
+                    schedule: Schedule::Every(1),
+                    command: Command::WaterAll,
+                },
+                Routine {
+                    schedule: Schedule::Every(3),
+                    command: Command::WaterRow(2),
+                },
+                Routine {
+                    schedule: Schedule::Every(2),
+                    command: Command::HarvestLeaves,
+                },
+                Routine {
+                    schedule: Schedule::Day(5),
+                    command: Command::HarvestFlowers,
+                },
+            ]
+        );
+        let due: Vec<Command> = routines.due(5).collect();
+        assert_eq!(
+            due,
+            vec![
+                Command::WaterAll,
+                Command::HarvestLeaves,
+                Command::HarvestFlowers
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_commands() {
+        let error = Routines::parse("every day: water all\nevery day: dance").unwrap_err();
+        assert!(
+            matches!(error, AppError::Routine(2, ref e) if e == "unknown command 'dance'"),
+            "{:?}",
+            error
+        );
+        assert!(matches!(
+            Routines::parse("day 1: water row 0"),
+            Err(AppError::Routine(1, _))
+        ));
+    }
+
+    #[test]
+    fn rejects_bad_schedules() {
+        for (script, expected) in [
+            ("every 0 days: water all", "bad number '0'"),
+            ("every two days: water all", "bad number 'two'"),
+            ("day -1: water all", "bad number '-1'"),
+            ("weekly: water all", "unknown schedule 'weekly'"),
+            ("water all", "expected '<schedule>: <command>'"),
+        ] {
+            let error = Routines::parse(script).unwrap_err();
+            assert!(
+                matches!(error, AppError::Routine(1, ref e) if e == expected),
+                "{}: {:?}",
+                script,
+                error
+            );
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use super::{settings, timer, AppError, Input, InputState};
+use specs::{System, Write};
+use std::fs;
+use std::io::ErrorKind;
+
+/// how many older versions of a save file are kept, from 'path.1', the newest, to 'path.3'.
+pub const BACKUPS: usize = 3;
+
+/// the first line of a save file, followed by the checksum of everything after it.
+const HEADER: &str = "qffp-checksum ";
+
+/// 64-bit FNV-1a, enough to notice a truncated or garbled file.
+pub fn checksum(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn backup_path(path: &str, n: usize) -> String {
+    format!("{}.{}", path, n)
+}
+
+/// write 'contents' to 'path' with a checksum, moving what was there into the backups. the new
+/// file is written beside the old one and renamed over it, so a crash never leaves half a file.
+pub fn write(path: &str, contents: &[u8]) -> Result<(), AppError> {
+    for n in (1..BACKUPS).rev() {
+        match fs::rename(backup_path(path, n), backup_path(path, n + 1)) {
+            Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
+    match fs::copy(path, backup_path(path, 1)) {
+        Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
+    }
+    let temp = format!("{}.new", path);
+    fs::write(&temp, encode(contents))?;
+    fs::rename(&temp, path)?;
+    Ok(())
+}
+
+/// 'contents' as written to a save file, after its checksum line.
+fn encode(contents: &[u8]) -> Vec<u8> {
+    let mut bytes = format!("{}{:016x}\n", HEADER, checksum(contents)).into_bytes();
+    bytes.extend_from_slice(contents);
+    bytes
+}
+
+/// the contents of the save file 'path' that held 'bytes', checked against its checksum.
+fn decode(path: &str, bytes: Vec<u8>) -> Result<Vec<u8>, AppError> {
+    if !bytes.starts_with(HEADER.as_bytes()) {
+        return Ok(bytes);
+    }
+    let damaged = || AppError::Damaged(path.to_string());
+    let newline = bytes.iter().position(|&b| b == b'\n').ok_or_else(damaged)?;
+    let sum = std::str::from_utf8(&bytes[HEADER.len()..newline])
+        .ok()
+        .and_then(|sum| u64::from_str_radix(sum, 16).ok());
+    let contents = &bytes[newline + 1..];
+    if sum != Some(checksum(contents)) {
+        return Err(damaged());
+    }
+    Ok(contents.to_vec())
+}
+
+/// read what was written to 'path', or None if there's no such file. a file that doesn't
+/// match its checksum is reported as damaged. files from before checksums are read as they are.
+pub fn read(path: &str) -> Result<Option<Vec<u8>>, AppError> {
+    match fs::read(path) {
+        Ok(bytes) => decode(path, bytes).map(Some),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// the newest backup of 'path' that's still intact.
+pub fn newest_backup(path: &str) -> Option<String> {
+    (1..=BACKUPS)
+        .map(|n| backup_path(path, n))
+        .find(|backup| matches!(read(backup), Ok(Some(_))))
+}
+
+/// a save file found damaged, and the backup it could be restored from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Damaged {
+    pub path: String,
+    pub backup: Option<String>,
+}
+
+impl Damaged {
+    pub fn new(path: &str) -> Damaged {
+        Damaged {
+            path: path.to_string(),
+            backup: newest_backup(path),
+        }
+    }
+
+    /// the prompt shown until the player decides what to do, with keys as typed on 'layout'.
+    pub fn prompt(&self, layout: settings::Layout) -> String {
+        let mut text = format!("### {} is damaged.", self.path);
+        match &self.backup {
+            Some(backup) => text.push_str(&format!(
+                "\n{}: restore the backup in {} | space: start over",
+                layout.keys("u"),
+                backup
+            )),
+            None => text.push_str("\nno backups were found. space: start over"),
+        }
+        text
+    }
+}
+
+/// specs resource with the damaged save files waiting on the local player's choice, asked
+/// about one at a time in the order they were found.
+#[derive(Debug, Default)]
+pub struct Recovery(pub Vec<Damaged>);
+
+/// asks the local player whether to restore a damaged save file from its backup or start over,
+/// holding back their other input until they choose. starting over keeps the damaged file
+/// beside the original as 'path.damaged'.
+pub struct RecoverySystem;
+
+impl<'a> System<'a> for RecoverySystem {
+    type SystemData = (
+        Write<'a, Recovery>,
+        Write<'a, Input>,
+        Option<Write<'a, timer::Timer>>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (mut recovery, mut input, timer) = data;
+        let Some(damaged) = recovery.0.first() else {
+            return;
+        };
+        let choice = input.slot(0);
+        if matches!(choice, InputState::Clear | InputState::Quit) {
+            return;
+        }
+        if let Some(i) = input.0.first_mut() {
+            *i = InputState::None;
+        }
+        // files that can't be moved are left for the player to sort out; the game goes on
+        // either way with whatever can be read
+        match (choice, &damaged.backup) {
+            (InputState::Action, Some(backup)) => {
+                let _ = fs::copy(backup, &damaged.path);
+            }
+            (InputState::Pickup, _) => {
+                let _ = fs::rename(&damaged.path, format!("{}.damaged", damaged.path));
+            }
+            _ => return,
+        }
+        if damaged.path == timer::PROFILE {
+            if let Some(mut timer) = timer {
+                timer.profile = timer::Profile::load(timer::PROFILE).unwrap_or_default();
+            }
+        }
+        recovery.0.remove(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn damaged(result: Result<Vec<u8>, AppError>) -> bool {
+        matches!(result, Err(AppError::Damaged(path)) if path == "save.json")
+    }
+
+    #[test]
+    fn reads_back_what_was_written() {
+        let contents = b"{\"day\": 3}\nmore\n".to_vec();
+        let bytes = encode(&contents);
+        assert!(bytes.starts_with(HEADER.as_bytes()));
+        assert_eq!(decode("save.json", bytes).unwrap(), contents);
+        assert_eq!(decode("save.json", encode(b"")).unwrap(), b"");
+    }
+
+    #[test]
+    fn reads_files_without_a_checksum_as_they_are() {
+        let old = b"{\"day\": 3}".to_vec();
+        assert_eq!(decode("save.json", old.clone()).unwrap(), old);
+    }
+
+    #[test]
+    fn reports_bad_checksums() {
+        // a changed byte
+        let mut bytes = encode(b"{\"day\": 3}");
+        *bytes.last_mut().unwrap() = b']';
+        assert!(damaged(decode("save.json", bytes)));
+        // cut short
+        let bytes = encode(b"{\"day\": 3}");
+        assert!(damaged(decode(
+            "save.json",
+            bytes[..bytes.len() - 2].to_vec()
+        )));
+        // no line after the header
+        assert!(damaged(decode("save.json", b"qffp-checksum 0123".to_vec())));
+        // a checksum that isn't a number
+        assert!(damaged(decode(
+            "save.json",
+            b"qffp-checksum not-hex\n{}".to_vec()
+        )));
+        // a missing one
+        assert!(damaged(decode("save.json", b"qffp-checksum \n{}".to_vec())));
+    }
+
+    #[test]
+    fn reads_missing_files_as_none() {
+        assert!(matches!(read("no/such/save.json"), Ok(None)));
+    }
+}
@@ -0,0 +1,623 @@
+use super::{ground, map, render, robot, sprite, state};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use specs::{Builder, Entity, Join, World, WorldExt};
+use std::collections::{BTreeMap, HashMap};
+
+/// the places the players can be, each laid out from its own map.
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Area {
+    /// the farm the game starts on, laid out from the seed or map::MAP.
+    #[default]
+    Farm,
+    /// the living quarters through the farm's left edge.
+    Habitat,
+}
+
+impl Area {
+    /// the map the area is laid out from on the first visit. the farm is laid out as the app
+    /// is built, so it has none here.
+    pub fn map(self) -> Option<map::Map> {
+        match self {
+            Area::Farm => None,
+            Area::Habitat => Some(map::Map::habitat()),
+        }
+    }
+}
+
+/// an area the players aren't in, kept as they left it.
+pub struct Scene {
+    pub size: state::WorldSize,
+    pub tiles: ground::TileMap,
+    pub dome: state::Dome,
+    pub landmarks: state::Landmarks,
+    pub exits: Vec<map::Exit>,
+    pub sprites: Vec<state::SavedSprite>,
+}
+
+/// specs resource with the area the players are in and its ways out, along with every other
+/// area as it was left. walking off an edge only asks for a change of area, which the app makes
+/// between updates.
+#[derive(Default)]
+pub struct SceneManager {
+    pub area: Area,
+    pub exits: Vec<map::Exit>,
+    pub away: HashMap<Area, Scene>,
+    /// the sprites of areas picked up from a save but not visited yet, put in place of the
+    /// ones laid out on the first visit.
+    pub saved: HashMap<Area, Vec<state::SavedSprite>>,
+    /// the way out a player walked off this update, and how far along the edge.
+    pub leaving: Option<(map::Exit, i64)>,
+}
+
+impl SceneManager {
+    /// a player walked off 'edge', 'along' cells from its start. leave through it if it's a
+    /// way out of the area.
+    pub fn walk_off(&mut self, edge: map::Edge, along: i64) {
+        if let Some(exit) = self.exits.iter().find(|exit| exit.edge == edge) {
+            self.leaving = Some((*exit, along));
+        }
+    }
+
+    /// the sprites with an id in every area the players aren't in, for saving.
+    pub fn saved_away(&self) -> BTreeMap<Area, Vec<state::SavedSprite>> {
+        let away = self.away.iter().map(|(area, scene)| {
+            let sprites = scene.sprites.iter().filter(|s| s.id != 0).cloned();
+            (*area, sprites.collect())
+        });
+        let saved = self
+            .saved
+            .iter()
+            .map(|(area, sprites)| (*area, sprites.clone()));
+        away.chain(saved).collect()
+    }
+
+    /// pick up the areas the players weren't in from a save, replacing every sprite with an id
+    /// in those already laid out.
+    pub fn load_away(&mut self, away: &BTreeMap<Area, Vec<state::SavedSprite>>) {
+        for (area, sprites) in away {
+            match self.away.get_mut(area) {
+                Some(scene) => {
+                    scene.sprites.retain(|s| s.id == 0);
+                    scene.sprites.extend(sprites.iter().cloned());
+                }
+                None => {
+                    self.saved.insert(*area, sprites.clone());
+                }
+            }
+        }
+    }
+}
+
+/// take the players out of their area through 'exit', arriving 'along' cells down the opposite
+/// edge of the next one. players keep hold of what they carry, and the rest stays behind.
+pub fn change(world: &mut World, (exit, along): (map::Exit, i64), seed: u64) {
+    if !enter(world, exit.to, seed) {
+        return;
+    }
+    let size = *world.read_resource::<state::WorldSize>();
+    let (right, bottom) = (size.0 as i64 - 10, size.1 as i64 - 5);
+    let at = match exit.edge {
+        map::Edge::Left => (right, along.clamp(-2, bottom)),
+        map::Edge::Right => (0, along.clamp(-2, bottom)),
+        map::Edge::Top => (along.clamp(0, right), bottom),
+        map::Edge::Bottom => (along.clamp(0, right), -2),
+    };
+    let (mut positions, mut players) = (
+        world.write_storage::<state::Position>(),
+        world.write_storage::<state::Player>(),
+    );
+    for (pos, player) in (&mut positions, &mut players).join() {
+        (pos.x, pos.y) = at;
+        player.walk = None;
+    }
+}
+
+/// move the players into 'area', keeping the one they're in as it is. an area left before is
+/// picked up as it was, and one visited for the first time is laid out from its map, with
+/// 'seed' picking its looks. false if there's no way to lay the area out.
+pub fn enter(world: &mut World, area: Area, seed: u64) -> bool {
+    let next = world.write_resource::<SceneManager>().away.remove(&area);
+    let map = match (&next, area.map()) {
+        (Some(_), _) => None,
+        (None, Some(map)) => Some(map),
+        (None, None) => return false,
+    };
+
+    // players and whatever they hold go along
+    let going: Vec<Entity> = {
+        let players = world.read_storage::<state::Player>();
+        let interactibles = world.read_storage::<state::Interactible>();
+        let held: Vec<state::ItemType> = (&players)
+            .join()
+            .map(|player| player.holding)
+            .filter(|holding| *holding != state::ItemType::None)
+            .collect();
+        (
+            &world.entities(),
+            (&players).maybe(),
+            (&interactibles).maybe(),
+        )
+            .join()
+            .filter(|(_, player, item)| {
+                player.is_some() || item.is_some_and(|item| held.contains(&item.item_type))
+            })
+            .map(|(e, _, _)| e)
+            .collect()
+    };
+    let sprites = save_sprites(world, |e, sprite| {
+        !going.contains(&e) && sprite.sprite_type != state::SpriteType::Particle
+    });
+    let left: Vec<Entity> = (&world.entities(), &world.read_storage::<state::Sprite>())
+        .join()
+        .map(|(e, _)| e)
+        .filter(|e| !going.contains(e))
+        .collect();
+    let _ = world.delete_entities(&left);
+    let left = Scene {
+        size: *world.read_resource::<state::WorldSize>(),
+        tiles: std::mem::take(&mut *world.write_resource::<ground::TileMap>()),
+        dome: *world.read_resource::<state::Dome>(),
+        landmarks: *world.read_resource::<state::Landmarks>(),
+        exits: std::mem::take(&mut world.write_resource::<SceneManager>().exits),
+        sprites,
+    };
+
+    if let Some(scene) = next {
+        world.insert(scene.size);
+        world.insert(scene.tiles);
+        world.insert(scene.dome);
+        world.insert(scene.landmarks);
+        world.write_resource::<SceneManager>().exits = scene.exits;
+        spawn_sprites(world, &scene.sprites);
+    } else if let Some(map) = map {
+        let mut rng = StdRng::seed_from_u64(seed.wrapping_add(area as u64));
+        lay_out(world, map.size(), &map, &mut rng);
+        let saved = world.write_resource::<SceneManager>().saved.remove(&area);
+        if let Some(saved) = saved {
+            replace_sprites(world, &saved);
+        }
+    }
+
+    let mut manager = world.write_resource::<SceneManager>();
+    let from = manager.area;
+    manager.away.insert(from, left);
+    manager.area = area;
+    // digs can't be put back in another area, and the ground under everything changed
+    world.write_resource::<state::Undo>().0.clear();
+    world.write_resource::<render::Changes>().redraw = true;
+    true
+}
+
+/// lay out 'map' in a world of 'size' cells: the ground tiles, the grass, the dome wall and
+/// everything placed on the map, along with the resources describing them. players are only
+/// placed in the first area laid out, and go along from there.
+pub fn lay_out(world: &mut World, size: (u16, u16), map: &map::Map, rng: &mut StdRng) {
+    let ids = *world.read_resource::<sprite::SpriteIds>();
+    let (dirt_frame_count, grass_frame_count, base) = {
+        let store = world.read_resource::<sprite::SpriteStore>();
+        // the cryopod and terminal can't be walked through, only in front of and behind
+        let base = |sheet: sprite::SpriteHandle| {
+            let size = &store[sheet].data.frames[0].source_size;
+            state::Collider::base((size.w as i64, size.h as i64 / 2))
+        };
+        (
+            store[ids.tile_dirt].data.frames.len(),
+            store[ids.grass].data.frames.len(),
+            (base(ids.cryopod), base(ids.terminal)),
+        )
+    };
+    let mut si = std::mem::take(&mut *world.write_resource::<state::SpriteIndexer>());
+    let has_player = (&world.read_storage::<state::Player>())
+        .join()
+        .next()
+        .is_some();
+    world.insert(state::WorldSize(size.0, size.1));
+    world.write_resource::<SceneManager>().exits = map.exits.clone();
+
+    // lay the ground tiles, and spawn grass sprites
+    let ground = map.ground_map();
+    let mut tile_map = ground::TileMap {
+        cols: (size.0 as i64 + ground::TILE.0 - 1) / ground::TILE.0,
+        rows: (size.1 as i64 + ground::TILE.1 - 1) / ground::TILE.1,
+        tiles: vec![],
+    };
+    for y in (0..size.1).step_by(4) {
+        for x in (0..(size.0)).step_by(8) {
+            let x = x as i64;
+            let y = y as i64;
+            let (col, row) = (x / ground::TILE.0, y / ground::TILE.1);
+
+            // ground tiles: plain dirt in any of its looks, or moss, path and water with
+            // edges matched to the tiles around them
+            tile_map.tiles.push(match ground.frame(&ids, col, row) {
+                Some((sheet, frame)) => ground::Tile {
+                    store_index: sheet,
+                    frame,
+                    flip: false,
+                },
+                None => ground::Tile {
+                    store_index: ids.tile_dirt,
+                    frame: rng.gen_range(0..dirt_frame_count),
+                    flip: rng.gen_range(0..2) == 0,
+                },
+            });
+
+            if x % 16 == 0 && y % 8 == 0 {
+                world
+                    .create_entity()
+                    .with(state::Sprite {
+                        store_index: ids.transition,
+                        sprite_type: state::SpriteType::Overlay,
+                        animating: true,
+                        ..state::Sprite::default()
+                    })
+                    .with(state::Position { x, y })
+                    .with(state::RenderLayer::Overlay)
+                    .build();
+            }
+
+            // grass grows where the map draws it in, and has a chance to spawn elsewhere,
+            // except on the path and in the pond
+            if map.grass(col, row)
+                || (x < (size.0 - 8).into()
+                    && y < (size.1 - 4).into()
+                    && map.grass_odds > 0
+                    && matches!(
+                        ground.get(col, row),
+                        ground::Ground::Dirt | ground::Ground::Moss
+                    )
+                    && rng.gen_range(0..map.grass_odds) == 0)
+            {
+                let id = si.new_index();
+                world
+                    .create_entity()
+                    .with(state::Sprite {
+                        id, // grass has an id because it's interactive
+                        store_index: ids.grass,
+                        frame: rng.gen_range(0..grass_frame_count),
+                        flip: rng.gen_range(0..2) == 0,
+                        animating: true,
+                        anim_speed: state::GRASS_ANIM_SPEED,
+                        sprite_type: state::SpriteType::Crop,
+                        ..state::Sprite::default()
+                    })
+                    .with(state::Position { x, y })
+                    .with(state::RenderLayer::Grass)
+                    .with(state::Interactible {
+                        item_type: state::ItemType::Grass,
+                        hold_to_use: false,
+                    })
+                    .build();
+            }
+        }
+    }
+    world.insert(tile_map);
+
+    // spawn the dome wall, if there is one
+    let dome = map.dome();
+    world.insert(dome);
+    for y in (0..size.1 as i64).step_by(4).filter(|_| map.dome.is_some()) {
+        let id = si.new_index();
+        world
+            .create_entity()
+            .with(state::Sprite {
+                id,
+                store_index: ids.dome_wall,
+                frame: usize::from((dome.airlock.0..dome.airlock.1).contains(&y)),
+                ..state::Sprite::default()
+            })
+            .with(state::Position { x: dome.x, y })
+            .with(state::RenderLayer::Grass)
+            .build();
+    }
+
+    // spawn everything placed on the map, in the order it's listed
+    for placement in &map.things {
+        let pos = state::Position {
+            x: placement.at.0,
+            y: placement.at.1,
+        };
+        // K and their gift only turn up later in the story
+        if matches!(placement.thing, map::Thing::K | map::Thing::Gift) {
+            continue;
+        }
+        // players go along from area to area, so they're only placed on the first
+        if placement.thing == map::Thing::Player && has_player {
+            continue;
+        }
+        let id = si.new_index();
+        match placement.thing {
+            // the tap irrigation channels are dug from
+            map::Thing::Tap => world
+                .create_entity()
+                .with(state::Sprite {
+                    id,
+                    store_index: ids.tap,
+                    ..state::Sprite::default()
+                })
+                .with(pos)
+                .with(state::RenderLayer::Crops)
+                .with(state::Machine {
+                    kind: state::MachineKind::Tap,
+                })
+                .build(),
+            map::Thing::Pod => world
+                .create_entity()
+                .with(state::Sprite {
+                    id,
+                    store_index: ids.cryopod,
+                    animating: true,
+                    sprite_type: state::SpriteType::Tool,
+                    ..state::Sprite::default()
+                })
+                .with(pos)
+                .with(state::RenderLayer::Player)
+                .with(state::Interactible {
+                    item_type: state::ItemType::Pod,
+                    hold_to_use: false,
+                })
+                .with(base.0)
+                .build(),
+            map::Thing::Terminal => world
+                .create_entity()
+                .with(state::Sprite {
+                    id,
+                    store_index: ids.terminal,
+                    sprite_type: state::SpriteType::Tool,
+                    animating: true,
+                    flip: true,
+                    ..state::Sprite::default()
+                })
+                .with(pos)
+                .with(state::RenderLayer::Player)
+                .with(state::Interactible {
+                    item_type: state::ItemType::Terminal,
+                    hold_to_use: false,
+                })
+                .with(base.1)
+                .build(),
+            map::Thing::Compost => world
+                .create_entity()
+                .with(state::Sprite {
+                    id,
+                    store_index: ids.compost_bin,
+                    sprite_type: state::SpriteType::Tool,
+                    ..state::Sprite::default()
+                })
+                .with(pos)
+                .with(state::RenderLayer::Player)
+                .with(state::Interactible {
+                    item_type: state::ItemType::Compost,
+                    hold_to_use: false,
+                })
+                .with(state::Compost::default())
+                .build(),
+            // the robot helper
+            map::Thing::Robot => world
+                .create_entity()
+                .with(state::Sprite {
+                    id,
+                    store_index: ids.robot,
+                    sprite_type: state::SpriteType::Tool,
+                    ..state::Sprite::default()
+                })
+                .with(pos)
+                .with(state::RenderLayer::Player)
+                .with(state::Interactible {
+                    item_type: state::ItemType::Robot,
+                    hold_to_use: false,
+                })
+                .with(robot::Robot::default())
+                .with(state::Motion::default())
+                .build(),
+            map::Thing::Player => world
+                .create_entity()
+                .with(state::Sprite {
+                    id,
+                    store_index: ids.character_00,
+                    sprite_type: state::SpriteType::Player,
+                    ..state::Sprite::default()
+                })
+                .with(pos)
+                .with(state::RenderLayer::Player)
+                .with(state::Motion::default())
+                .with(state::Animation::new("idle"))
+                .with(state::AnimationController::default())
+                .with(state::Collider::FEET)
+                .with(state::Player {
+                    slot: 0,
+                    holding: state::ItemType::None,
+                    air_until: state::OXYGEN_TIME,
+                    last_use: None,
+                    target: None,
+                    walk: None,
+                })
+                .build(),
+            // tools
+            map::Thing::Shovel | map::Thing::Packet | map::Thing::Watercan => {
+                let (sheet, item_type) = match placement.thing {
+                    map::Thing::Shovel => (ids.tool_shovel, state::ItemType::Shovel),
+                    map::Thing::Packet => (ids.tool_packet, state::ItemType::Packet),
+                    _ => (ids.tool_watercan, state::ItemType::Watercan),
+                };
+                world
+                    .create_entity()
+                    .with(state::Sprite {
+                        id,
+                        store_index: sheet,
+                        sprite_type: state::SpriteType::Tool,
+                        ..state::Sprite::default()
+                    })
+                    .with(pos)
+                    .with(state::RenderLayer::Tools)
+                    .with(state::Interactible {
+                        item_type,
+                        hold_to_use: true,
+                    })
+                    .with(state::Motion::default())
+                    .build()
+            }
+            map::Thing::K | map::Thing::Gift => continue,
+        };
+    }
+    let player = map.place(map::Thing::Player).unwrap_or_default();
+    world.insert(state::Landmarks {
+        wake: player,
+        arrival: map.place(map::Thing::K).unwrap_or(player),
+        gift: map.place(map::Thing::Gift).unwrap_or(player),
+    });
+    *world.write_resource::<state::SpriteIndexer>() = si;
+}
+
+/// every sprite 'keep' picks, as it's saved: by its sheet's name, with the components that make
+/// it what it is.
+pub fn save_sprites(
+    world: &World,
+    keep: impl Fn(Entity, &state::Sprite) -> bool,
+) -> Vec<state::SavedSprite> {
+    let store = world.read_resource::<sprite::SpriteStore>();
+    let sprites = world.read_storage::<state::Sprite>();
+    let positions = world.read_storage::<state::Position>();
+    let layers = world.read_storage::<state::RenderLayer>();
+    let interactibles = world.read_storage::<state::Interactible>();
+    let machines = world.read_storage::<state::Machine>();
+    let teleporters = world.read_storage::<state::Teleporter>();
+    let composts = world.read_storage::<state::Compost>();
+    let robots = world.read_storage::<robot::Robot>();
+    let npcs = world.read_storage::<state::Npc>();
+    let motions = world.read_storage::<state::Motion>();
+    let fertilized = world.read_storage::<state::Fertilized>();
+    let thirsty = world.read_storage::<state::Thirsty>();
+    let colliders = world.read_storage::<state::Collider>();
+    let moisture = world.read_storage::<state::Moisture>();
+
+    (&world.entities(), &sprites, &positions)
+        .join()
+        .filter(|(e, sprite, _)| keep(*e, sprite))
+        .map(|(e, sprite, pos)| state::SavedSprite {
+            id: sprite.id,
+            sheet: store[sprite.store_index].name.clone(),
+            frame: sprite.frame,
+            flip: sprite.flip,
+            animating: sprite.animating,
+            anim_speed: sprite.anim_speed,
+            hidden: sprite.hidden,
+            sprite_type: sprite.sprite_type,
+            pos: (pos.x, pos.y),
+            layer: layers.get(e).copied(),
+            item: interactibles.get(e).map(|i| (i.item_type, i.hold_to_use)),
+            machine: machines.get(e).map(|m| m.kind),
+            teleporter: teleporters.get(e).map(|t| t.link),
+            compost: composts.get(e).cloned(),
+            robot: robots.get(e).map(|robot| {
+                robot
+                    .program
+                    .iter()
+                    .map(|line| line.map(|command| command.to_string()))
+                    .collect()
+            }),
+            npc: npcs.contains(e),
+            motion: motions.contains(e),
+            fertilized: fertilized.contains(e),
+            thirsty: thirsty.contains(e),
+            collider: colliders.get(e).copied(),
+            moisture: moisture.get(e).copied(),
+        })
+        .collect()
+}
+
+/// put 'sprites' in place of every sprite with an id but the players'.
+pub fn replace_sprites(world: &mut World, sprites: &[state::SavedSprite]) {
+    let replaced: Vec<Entity> = (&world.entities(), &world.read_storage::<state::Sprite>())
+        .join()
+        .filter(|(_, sprite)| sprite.id != 0 && sprite.sprite_type != state::SpriteType::Player)
+        .map(|(e, _)| e)
+        .collect();
+    let _ = world.delete_entities(&replaced);
+    spawn_sprites(world, sprites);
+}
+
+/// spawn 'sprites' as they were saved. sprites whose sheet is gone are left out.
+pub fn spawn_sprites(world: &mut World, sprites: &[state::SavedSprite]) {
+    let day = world.read_resource::<state::Game>().day;
+    let time = world.read_resource::<state::Time>().0;
+    for saved in sprites {
+        let Ok(store_index) = world
+            .read_resource::<sprite::SpriteStore>()
+            .index_by_name(&saved.sheet)
+        else {
+            continue;
+        };
+        let mut builder = world
+            .create_entity()
+            .with(state::Sprite {
+                id: saved.id,
+                store_index,
+                flip: saved.flip,
+                frame: saved.frame,
+                animating: saved.animating,
+                anim_speed: saved.anim_speed,
+                sprite_type: saved.sprite_type,
+                hidden: saved.hidden,
+                ..state::Sprite::default()
+            })
+            .with(state::Position {
+                x: saved.pos.0,
+                y: saved.pos.1,
+            });
+        if let Some(layer) = saved.layer {
+            builder = builder.with(layer);
+        }
+        if let Some((item_type, hold_to_use)) = saved.item {
+            builder = builder.with(state::Interactible {
+                item_type,
+                hold_to_use,
+            });
+        }
+        if let Some(kind) = saved.machine {
+            builder = builder.with(state::Machine { kind });
+        }
+        if let Some(link) = saved.teleporter {
+            builder = builder.with(state::Teleporter { link });
+        }
+        if let Some(compost) = &saved.compost {
+            builder = builder.with(compost.clone());
+        }
+        if let Some(program) = &saved.robot {
+            let mut robot = robot::Robot {
+                day,
+                ..robot::Robot::default()
+            };
+            for (line, command) in robot.program.iter_mut().zip(program) {
+                *line = command.as_deref().and_then(|c| c.parse().ok());
+            }
+            builder = builder.with(robot);
+        }
+        if saved.npc {
+            builder = builder
+                .with(state::Npc::new(saved.pos, time))
+                .with(state::Animation::new("idle"))
+                .with(state::AnimationController::default());
+        }
+        if saved.motion {
+            builder = builder.with(state::Motion::default());
+        }
+        if saved.fertilized {
+            builder = builder.with(state::Fertilized);
+        }
+        if saved.thirsty {
+            builder = builder.with(state::Thirsty);
+        }
+        if let Some(collider) = saved.collider {
+            builder = builder.with(collider);
+        }
+        if let Some(moisture) = saved.moisture {
+            builder = builder.with(moisture);
+        }
+        builder.build();
+    }
+}
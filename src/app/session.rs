@@ -0,0 +1,108 @@
+use super::{display, keys, App, AppError, Frontend, IOWrite, InputState};
+use std::collections::VecDeque;
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Duration;
+
+/// events from a remote terminal driving a session.
+pub enum SessionEvent {
+    Data(Vec<u8>),
+    Resize(u16, u16),
+}
+
+/// a writer shared between a session and its App, so the session can still restore the remote
+/// terminal after the App is done with it.
+#[derive(Clone)]
+pub struct SharedOutput(Arc<Mutex<Box<dyn IOWrite + Send>>>);
+
+impl SharedOutput {
+    pub fn new(out: Box<dyn IOWrite + Send>) -> SharedOutput {
+        SharedOutput(Arc::new(Mutex::new(out)))
+    }
+}
+
+impl IOWrite for SharedOutput {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.lock().unwrap().flush()
+    }
+}
+
+/// a remote terminal's input, arriving as events from its connection.
+struct SessionInput {
+    events: mpsc::Receiver<SessionEvent>,
+    pending: VecDeque<InputState>,
+    size: (u16, u16),
+}
+
+impl Frontend for SessionInput {
+    fn read_input(&mut self, ms: u64, typing: bool) -> Result<InputState, AppError> {
+        // wait for input like the local terminal polls, then take everything that arrived
+        let mut next = self.events.recv_timeout(Duration::from_millis(ms));
+        loop {
+            match next {
+                // the settings are the host's, so remote players get the qwerty keys
+                Ok(SessionEvent::Data(bytes)) => {
+                    self.pending
+                        .extend(keys::parse_keys(&bytes, typing, Default::default()))
+                }
+                Ok(SessionEvent::Resize(w, h)) => {
+                    self.size = (w, h);
+                    self.pending.push_back(InputState::Clear);
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => break,
+                Err(mpsc::RecvTimeoutError::Disconnected) => return Ok(InputState::Quit),
+            }
+            next = match self.events.try_recv() {
+                Ok(event) => Ok(event),
+                Err(mpsc::TryRecvError::Empty) => Err(mpsc::RecvTimeoutError::Timeout),
+                Err(mpsc::TryRecvError::Disconnected) => Err(mpsc::RecvTimeoutError::Disconnected),
+            };
+        }
+
+        // one input per update, as with the local terminal
+        Ok(self.pending.pop_front().unwrap_or(InputState::None))
+    }
+
+    fn size(&self) -> Result<(u16, u16), AppError> {
+        Ok(self.size)
+    }
+}
+
+/// run one independent game session on a remote terminal. input arrives as raw bytes on
+/// 'events', and the game is drawn to 'out'. returns when the player quits or disconnects.
+pub fn run(
+    size: (u16, u16),
+    events: mpsc::Receiver<SessionEvent>,
+    out: Box<dyn IOWrite + Send>,
+) -> Result<(), AppError> {
+    let mut out = SharedOutput::new(out);
+    write!(
+        out,
+        "{}{}{}{}",
+        display::ENTER_ALT_SCREEN,
+        display::HIDE_CURSOR,
+        display::CLEAR,
+        display::PUSH_TITLE
+    )?;
+    out.flush()?;
+
+    let mut app = App::with_output(size, Box::new(out.clone()), None)?;
+    app.run(&mut SessionInput {
+        events,
+        pending: VecDeque::new(),
+        size,
+    })?;
+
+    write!(
+        out,
+        "{}{}{}",
+        display::SHOW_CURSOR,
+        display::LEAVE_ALT_SCREEN,
+        display::POP_TITLE
+    )?;
+    out.flush()?;
+    Ok(())
+}
@@ -0,0 +1,170 @@
+use super::AppError;
+use serde::{Deserialize, Serialize};
+
+/// player settings, read from this file in the working directory when it exists.
+pub const SETTINGS: &str = "settings.json";
+
+/// the smallest farm the layout fits in, in cells.
+pub const MIN_WORLD: (u16, u16) = (64, 24);
+
+/// game speeds cycled through with the speed key.
+pub const SPEEDS: [f32; 4] = [0.5, 1.0, 2.0, 4.0];
+
+/// keyboard layouts the letter keys follow. the game's keys are named for where they sit on a
+/// qwerty keyboard, and a layout moves each of them to the key in the same place on its own
+/// keyboard: on dvorak, 'd h t n' move and 'g' uses.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Layout {
+    #[default]
+    Qwerty,
+    Azerty,
+    Dvorak,
+    Colemak,
+}
+
+impl Layout {
+    /// the three rows of letter keys, top to bottom, as typed on this layout.
+    fn rows(self) -> [&'static str; 3] {
+        match self {
+            Layout::Qwerty => ["qwertyuiop", "asdfghjkl;", "zxcvbnm,./"],
+            Layout::Azerty => ["azertyuiop", "qsdfghjklm", "wxcvbn,;:!"],
+            Layout::Dvorak => ["',.pyfgcrl", "aoeuidhtns", ";qjkxbmwvz"],
+            Layout::Colemak => ["qwfpgjluy;", "arstdhneio", "zxcvbkm,./"],
+        }
+    }
+
+    /// the qwerty key in the same place as 'c' on this layout, keeping its case. characters
+    /// off the letter rows are left as they are.
+    pub fn to_qwerty(self, c: char) -> char {
+        Layout::remap(c, self.rows(), Layout::Qwerty.rows())
+    }
+
+    /// the key on this layout in the same place as the qwerty key 'c', for showing keys.
+    pub fn from_qwerty(self, c: char) -> char {
+        Layout::remap(c, Layout::Qwerty.rows(), self.rows())
+    }
+
+    /// the qwerty keys in 'qwerty', like "k/j", as they're typed on this layout.
+    pub fn keys(self, qwerty: &str) -> String {
+        qwerty.chars().map(|c| self.from_qwerty(c)).collect()
+    }
+
+    fn remap(c: char, from: [&str; 3], to: [&str; 3]) -> char {
+        let lower = c.to_ascii_lowercase();
+        let found = from.iter().zip(to).find_map(|(from, to)| {
+            let i = from.chars().position(|k| k == lower)?;
+            to.chars().nth(i)
+        });
+        match found {
+            Some(k) if c.is_ascii_uppercase() => k.to_ascii_uppercase(),
+            Some(k) => k,
+            None => c,
+        }
+    }
+}
+
+/// how the thing in reach is picked out from the rest of the farm.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HighlightStyle {
+    /// its black outline drawn in the highlight color.
+    #[default]
+    Outline,
+    /// the outline, flashing on and off.
+    Blink,
+    /// a line under the whole sprite, leaving its colors alone.
+    Underline,
+}
+
+/// colors and styles for drawing the game, for sprites and color schemes the defaults don't
+/// suit.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    pub highlight: HighlightStyle,
+    /// red, green and blue of the highlight outline.
+    pub highlight_color: [u8; 3],
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            highlight: HighlightStyle::Outline,
+            highlight_color: [127, 255, 255],
+        }
+    }
+}
+
+/// specs resource with the player's settings. every field is optional in the file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// ring the terminal bell for new messages and blooming flowers, like `--bell`.
+    pub bell: bool,
+    /// show a speedrun timer, like `--timer`.
+    pub timer: bool,
+    /// volumes from 0.0 to 1.0, used with the audio feature.
+    pub music_volume: f32,
+    pub effects_volume: f32,
+    /// start with the music, sound effects and bell cues off. the mute key toggles it.
+    pub muted: bool,
+    /// how fast game time passes, from 0.5 to 4.0 times real time.
+    pub game_speed: f32,
+    /// size of the farm in cells. the terminal shows the part of it around the player.
+    pub world_width: u16,
+    pub world_height: u16,
+    /// capture the mouse. turned off, like `--no-mouse`, the terminal keeps selecting text.
+    pub mouse: bool,
+    /// the keyboard layout the letter keys follow.
+    pub layout: Layout,
+    pub theme: Theme,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            bell: false,
+            timer: false,
+            music_volume: 0.3,
+            effects_volume: 0.6,
+            muted: false,
+            game_speed: 1.0,
+            world_width: 128,
+            world_height: 40,
+            mouse: true,
+            layout: Layout::Qwerty,
+            theme: Theme::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// load settings from 'path', using defaults if the file doesn't exist.
+    pub fn load(path: &str) -> Result<Settings, AppError> {
+        match std::fs::read(path) {
+            Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Settings::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// the game speed, kept within the range of SPEEDS.
+    pub fn speed(&self) -> f32 {
+        self.game_speed.clamp(SPEEDS[0], SPEEDS[SPEEDS.len() - 1])
+    }
+
+    /// the farm's size, no smaller than MIN_WORLD.
+    pub fn world_size(&self) -> (u16, u16) {
+        (
+            self.world_width.max(MIN_WORLD.0),
+            self.world_height.max(MIN_WORLD.1),
+        )
+    }
+
+    /// switch to the next faster game speed, wrapping around to the slowest.
+    pub fn cycle_speed(&mut self) {
+        let speed = self.speed();
+        self.game_speed = SPEEDS.into_iter().find(|s| *s > speed).unwrap_or(SPEEDS[0]);
+    }
+}
@@ -0,0 +1,135 @@
+use super::state::{Game, GameEvent, ItemType, PRODUCE, SEEDS};
+use super::{assets, settings, AppError, InputState};
+use serde::{Deserialize, Serialize};
+
+/// the shop's prices: what it pays for each kind of produce, and what it asks for each kind of
+/// seed.
+pub const PRICES: &str = "res/prices.json";
+
+/// specs resource holding the player's money, earned by selling produce at the terminal.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Money(pub u32);
+
+/// one thing the shop trades, and what it's worth.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Price {
+    pub item: ItemType,
+    pub price: u32,
+}
+
+/// specs resource with the shop's prices, as read from the prices file: what it buys from the
+/// player, then what it sells.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Prices {
+    pub sell: Vec<Price>,
+    pub buy: Vec<Price>,
+}
+
+impl Prices {
+    pub fn load(path: &str) -> Result<Prices, AppError> {
+        Ok(serde_json::from_slice(&assets::read(path)?)?)
+    }
+
+    /// every line of the shop: whether it's sold or bought, and the price.
+    fn lines(&self) -> impl Iterator<Item = (bool, &Price)> {
+        self.sell
+            .iter()
+            .map(|p| (true, p))
+            .chain(self.buy.iter().map(|p| (false, p)))
+    }
+}
+
+/// the name of a pocket item in the shop.
+fn name(item: ItemType) -> String {
+    SEEDS
+        .iter()
+        .chain(PRODUCE.iter())
+        .find(|(i, _)| *i == item)
+        .map_or_else(
+            || format!("{:?}", item).to_lowercase(),
+            |(_, n)| n.to_string(),
+        )
+}
+
+/// specs resource for the shop tab of the terminal, and the line picked in it while it's open.
+#[derive(Debug, Default)]
+pub struct Shop {
+    pub picked: Option<usize>,
+}
+
+impl Shop {
+    /// the shop tab, while it's open: the money in hand and a line for each trade, with the
+    /// count of it in the pocket. keys are shown as typed on 'layout'.
+    pub fn text(
+        &self,
+        game: &Game,
+        prices: &Prices,
+        money: Money,
+        layout: settings::Layout,
+    ) -> Option<String> {
+        let picked = self.picked?;
+        let mut text = format!(
+            "### Shop | ${} | {}: pick | {}: trade | {}: back to inbox",
+            money.0,
+            layout.keys("k/j"),
+            layout.keys("u"),
+            layout.keys("h")
+        );
+        for (i, (selling, price)) in prices.lines().enumerate() {
+            let cursor = if i == picked { ">" } else { " " };
+            let verb = if selling { "sell" } else { "buy" };
+            text.push_str(&format!(
+                "\n{}{} {} ${} (have {})",
+                cursor,
+                verb,
+                name(price.item),
+                price.price,
+                game.pocket_count(price.item)
+            ));
+        }
+        Some(text)
+    }
+
+    /// handle a player's input in the open shop: picking a line, trading one of it, or going
+    /// back to the inbox. false for input the shop doesn't take.
+    pub fn handle(
+        &mut self,
+        input: InputState,
+        (game, prices, money): (&mut Game, &Prices, &mut Money),
+        events: &mut Vec<GameEvent>,
+    ) -> bool {
+        let Some(picked) = self.picked else {
+            return false;
+        };
+        let count = prices.lines().count();
+        match input {
+            InputState::Up | InputState::ShiftUp if count > 0 => {
+                self.picked = Some((picked + count - 1) % count);
+            }
+            InputState::Down | InputState::ShiftDown if count > 0 => {
+                self.picked = Some((picked + 1) % count);
+            }
+            InputState::Left | InputState::ShiftLeft => self.picked = None,
+            InputState::Action => {
+                let Some((selling, price)) = prices.lines().nth(picked) else {
+                    return true;
+                };
+                let Some(held) = game.pocket(price.item) else {
+                    return true;
+                };
+                if selling && *held > 0 {
+                    *held -= 1;
+                    money.0 += price.price;
+                    events.push(GameEvent::Pickup);
+                } else if !selling && money.0 >= price.price {
+                    *held += 1;
+                    money.0 -= price.price;
+                    events.push(GameEvent::Pickup);
+                }
+            }
+            InputState::Right | InputState::ShiftRight => {}
+            _ => return false,
+        }
+        true
+    }
+}
@@ -0,0 +1,75 @@
+use super::state::GameEvent;
+use super::{assets, cues, AppError};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// the sound mapping shipped with the game. edit it to re-skin audio without touching code.
+pub const SOUNDS: &str = "res/sounds.json";
+
+/// sounds named with this prefix are synthesized by the game instead of read from a file.
+pub const BUILTIN: &str = "builtin:";
+
+/// what happens when one gameplay event occurs.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EventSound {
+    /// a sound file, or a builtin sound such as "builtin:dig". played with the audio feature.
+    pub sound: Option<String>,
+    /// bytes written to the terminal instead when bell cues are enabled, e.g. "\u0007".
+    pub cue: Option<String>,
+}
+
+/// specs resource mapping gameplay events to sounds and terminal cues.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SoundMap {
+    /// looped in the background. played with the audio feature.
+    pub music: Option<String>,
+    pub events: HashMap<GameEvent, EventSound>,
+}
+
+impl Default for SoundMap {
+    fn default() -> Self {
+        let builtin = |name: &str, cue: Option<&str>| EventSound {
+            sound: Some(format!("{}{}", BUILTIN, name)),
+            cue: cue.map(str::to_string),
+        };
+        SoundMap {
+            music: Some(format!("{}music", BUILTIN)),
+            events: HashMap::from([
+                (GameEvent::Dig, builtin("dig", None)),
+                (GameEvent::Water, builtin("water", None)),
+                (GameEvent::Seed, builtin("seed", None)),
+                (GameEvent::Pickup, builtin("pickup", None)),
+                (GameEvent::Sleep, builtin("sleep", None)),
+                (GameEvent::Message, builtin("message", Some(cues::BELL))),
+                (GameEvent::Bloom, builtin("bloom", Some(cues::BELL))),
+            ]),
+        }
+    }
+}
+
+impl SoundMap {
+    /// load a mapping from 'path', using the builtin sounds if the file doesn't exist.
+    pub fn load(path: &str) -> Result<SoundMap, AppError> {
+        match assets::read(path) {
+            Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(SoundMap::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// the sound files the mapping refers to, without builtin sounds.
+    pub fn files(&self) -> Vec<&str> {
+        let mut files: Vec<&str> = self
+            .events
+            .values()
+            .filter_map(|e| e.sound.as_deref())
+            .chain(self.music.as_deref())
+            .filter(|s| !s.starts_with(BUILTIN))
+            .collect();
+        files.sort();
+        files.dedup();
+        files
+    }
+}
@@ -1,14 +1,52 @@
-use super::{path, AppError, Error};
-use image::io::Reader as ImageReader;
+use super::{assets, display, path, AppError, Error};
+#[cfg(feature = "dev")]
+use notify::Watcher;
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "dev")]
+use std::sync::mpsc;
 
-#[derive(Default, Debug, Serialize, Deserialize)]
+/// every sheet the game loads at startup, in store index order.
+pub const SHEETS: &[&str] = &[
+    "res/sheets/character-00.json",
+    "res/sheets/character-01.json",
+    "res/sheets/tile-dirt.json",
+    "res/sheets/grass.json",
+    "res/sheets/tool-shovel.json",
+    "res/sheets/tool-watercan.json",
+    "res/sheets/tool-packet.json",
+    "res/sheets/tool-packet2.json",
+    "res/sheets/crop-empty.json",
+    "res/sheets/crop-leaf.json",
+    "res/sheets/crop-flower.json",
+    "res/sheets/cryopod.json",
+    "res/sheets/terminal.json",
+    "res/sheets/compost-bin.json",
+    "res/sheets/dome-wall.json",
+    "res/sheets/robot.json",
+    "res/sheets/solar-panel.json",
+    "res/sheets/sprinkler.json",
+    "res/sheets/teleporter.json",
+    "res/sheets/tool-fiber.json",
+    "res/sheets/tool-seed.json",
+    "res/sheets/transition.json",
+    "res/sheets/particle-dirt.json",
+    "res/sheets/particle-water.json",
+    "res/sheets/particle-heart.json",
+    "res/sheets/portrait-k.json",
+    "res/sheets/emote.json",
+    "res/sheets/channel.json",
+    "res/sheets/tap.json",
+    "res/sheets/tile-ground.json",
+    "res/sheets/tile-water.json",
+];
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct WH {
     pub w: u32,
     pub h: u32,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Xywh {
     pub x: u32,
     pub y: u32,
@@ -16,15 +54,28 @@ pub struct Xywh {
     pub h: u32,
 }
 
-#[derive(Debug, Clone, Copy)]
-pub struct Rgba {
-    pub r: u8,
-    pub g: u8,
-    pub b: u8,
+/// a single sprite sheet pixel. the color is stored as the display color it will be drawn with,
+/// so the renderer never converts per pixel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pixel {
+    pub color: display::Color,
     pub a: u8,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl Pixel {
+    pub fn rgba(r: u8, g: u8, b: u8, a: u8) -> Pixel {
+        Pixel {
+            color: display::Color::Rgb { r, g, b },
+            a,
+        }
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.a > 0
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SpriteSheetJSONFrame {
     pub filename: String,
     pub frame: Xywh,
@@ -37,7 +88,7 @@ pub struct SpriteSheetJSONFrame {
     pub duration: u32,
 }
 
-#[derive(Default, Debug, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct SpriteSheetJSONMeta {
     pub app: String,
     pub version: String,
@@ -45,6 +96,63 @@ pub struct SpriteSheetJSONMeta {
     pub format: String,
     pub size: WH,
     pub scale: String,
+    #[serde(
+        default,
+        rename = "paletteCycles",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub palette_cycles: Vec<PaletteCycle>,
+    #[serde(default, rename = "frameTags", skip_serializing_if = "Vec::is_empty")]
+    pub frame_tags: Vec<FrameTag>,
+}
+
+/// a named run of frames in a sheet, from 'from' to 'to' inclusive, like a character's walk,
+/// as aseprite exports them. a sheet may tag the same name more than once.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FrameTag {
+    pub name: String,
+    pub from: usize,
+    pub to: usize,
+    #[serde(default)]
+    pub direction: Direction,
+}
+
+impl FrameTag {
+    pub fn contains(&self, frame: usize) -> bool {
+        (self.from..=self.to).contains(&frame)
+    }
+}
+
+/// which way a frame tag plays through its frames.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    #[default]
+    Forward,
+    Reverse,
+    /// forward, then back again.
+    Pingpong,
+    /// back, then forward again.
+    PingpongReverse,
+}
+
+impl Direction {
+    /// whether it plays from the tag's last frame first.
+    pub fn starts_back(self) -> bool {
+        matches!(self, Direction::Reverse | Direction::PingpongReverse)
+    }
+
+    pub fn pingpong(self) -> bool {
+        matches!(self, Direction::Pingpong | Direction::PingpongReverse)
+    }
+}
+
+/// colors in a sheet that take each other's place in turn, one step along every 'duration'
+/// milliseconds, so water can shimmer or a screen glow without extra frames.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaletteCycle {
+    pub colors: Vec<[u8; 3]>,
+    pub duration: u32,
 }
 
 #[derive(Default, Debug, Serialize, Deserialize)]
@@ -53,114 +161,509 @@ pub struct SpriteSheetJSON {
     pub meta: SpriteSheetJSONMeta,
 }
 
+/// one sheet packed into an atlas. 'rect' is where the sheet's image sits in the atlas image,
+/// and its frames keep their sheet-local coordinates.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AtlasSheet {
+    pub name: String,
+    pub rect: Xywh,
+    pub frames: Vec<SpriteSheetJSONFrame>,
+    #[serde(
+        default,
+        rename = "paletteCycles",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub palette_cycles: Vec<PaletteCycle>,
+    #[serde(default, rename = "frameTags", skip_serializing_if = "Vec::is_empty")]
+    pub frame_tags: Vec<FrameTag>,
+}
+
+/// many sheets sharing one image, as written by `qffp pack`.
+#[derive(Default, Debug, Serialize, Deserialize)]
+pub struct AtlasJSON {
+    pub sheets: Vec<AtlasSheet>,
+    pub meta: SpriteSheetJSONMeta,
+}
+
+/// a sheet in the sprite store, by its place there. handles to the game's own sheets are
+/// looked up once, into SpriteIds, when the store is loaded.
+#[derive(
+    Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub struct SpriteHandle(pub usize);
+
 #[derive(Default, Debug)]
 pub struct LoadedSprite {
     pub name: String,
-    pub index: usize,
+    pub index: SpriteHandle,
+    pub path: String,
     pub data: SpriteSheetJSON,
-    pub image: Vec<Vec<Rgba>>,
+    pub image: Vec<Vec<Pixel>>,
 }
 
-#[derive(Error)]
-pub struct SpriteStoreError;
-
-impl std::fmt::Display for SpriteStoreError {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "SpriteStore Error")
+impl LoadedSprite {
+    /// the pixel at 'x', 'y' from the top left of a frame as it's drawn, at its full source
+    /// size and mirrored if 'flip'. a trimmed frame only stores the part inside its sprite
+    /// source rect, so everything around that is transparent. a frame packed rotated is stored
+    /// turned a quarter clockwise, 'h' wide and 'w' high, and is turned back here.
+    pub fn pixel(&self, frame_number: usize, x: u32, y: u32, flip: bool) -> Pixel {
+        let frame = &self.data.frames[frame_number];
+        let (rect, trim) = (&frame.frame, &frame.sprite_source_size);
+        if x >= frame.source_size.w {
+            return Pixel::rgba(0, 0, 0, 0);
+        }
+        let x = if flip { frame.source_size.w - 1 - x } else { x };
+        let (Some(x), Some(y)) = (x.checked_sub(trim.x), y.checked_sub(trim.y)) else {
+            return Pixel::rgba(0, 0, 0, 0);
+        };
+        if x >= rect.w || y >= rect.h {
+            return Pixel::rgba(0, 0, 0, 0);
+        }
+        let (x, y) = if frame.rotated {
+            (rect.x + rect.h - 1 - y, rect.y + x)
+        } else {
+            (rect.x + x, rect.y + y)
+        };
+        self.image[y as usize][x as usize]
     }
-}
-impl std::fmt::Debug for SpriteStoreError {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{{ file: {}, line: {} }}", file!(), line!())
+
+    /// the first frame tag named 'name', if the sheet has one.
+    pub fn tag(&self, name: &str) -> Option<&FrameTag> {
+        self.data.meta.frame_tags.iter().find(|t| t.name == name)
     }
-}
 
-fn load_sprite(json_path: &str, index: usize) -> Result<LoadedSprite, AppError> {
-    let test_path = path::Path::new(json_path);
-    if !test_path.exists() {
-        println!("current dir: {:?}", std::env::current_dir());
-        println!("path does not exist: {:?}", test_path);
-        return Err(AppError::SpriteStore(SpriteStoreError));
+    /// whether 'frame' is in any of the frame tags named 'name'.
+    pub fn tagged(&self, frame: usize, name: &str) -> bool {
+        self.data
+            .meta
+            .frame_tags
+            .iter()
+            .any(|t| t.name == name && t.contains(frame))
     }
 
-    let json_str = std::fs::read_to_string(json_path).expect("file read to string error");
-    let json = serde_json::from_str::<SpriteSheetJSON>(&json_str)?;
+    /// the frame as far into tag 'to' as 'frame' is into tag 'from', like a crop's watered
+    /// frame at the same stage as its dry one. None unless 'frame' is in 'from' and the
+    /// matching frame is in 'to'.
+    pub fn retag(&self, frame: usize, from: &str, to: &str) -> Option<usize> {
+        let from = self.tag(from).filter(|t| t.contains(frame))?;
+        let to = self.tag(to)?;
+        Some(to.from + frame - from.from).filter(|f| to.contains(*f))
+    }
 
-    let image_path = format!("res/sheets/{}", json.meta.image);
-    let test_path = path::Path::new(image_path.as_str());
-    if !test_path.exists() {
-        println!("current dir: {:?}", std::env::current_dir());
-        println!("path does not exist: {:?}", test_path);
-        return Err(AppError::SpriteStore(SpriteStoreError));
+    /// 'color' moved along whichever of the sheet's palette cycles it's in, as of game time
+    /// 'time'. colors outside every cycle are left alone.
+    pub fn cycled(&self, color: display::Color, time: u64) -> display::Color {
+        let display::Color::Rgb { r, g, b } = color else {
+            return color;
+        };
+        for cycle in self.data.meta.palette_cycles.iter() {
+            if cycle.duration == 0 {
+                continue;
+            }
+            if let Some(i) = cycle.colors.iter().position(|c| *c == [r, g, b]) {
+                let step = (time / cycle.duration as u64) as usize;
+                let [r, g, b] = cycle.colors[(i + step) % cycle.colors.len()];
+                return display::Color::Rgb { r, g, b };
+            }
+        }
+        color
     }
+}
+
+/// what went wrong loading or looking up a sheet. 'sheet' is the sheet's name, and 'path' the
+/// file that couldn't be used, its json or its image.
+#[derive(Error, Debug)]
+pub enum SpriteStoreError {
+    #[error("sheet '{sheet}': can't read {path}: {source}")]
+    Read {
+        sheet: String,
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("sheet '{sheet}': {path} isn't a sprite sheet or atlas: {source}")]
+    Json {
+        sheet: String,
+        path: String,
+        source: serde_json::Error,
+    },
+    #[error("sheet '{sheet}': can't decode {path}: {source}")]
+    Image {
+        sheet: String,
+        path: String,
+        source: image::ImageError,
+    },
+    #[error("sheet '{sheet}': its {w}x{h} area at {x},{y} runs off the edge of {path}")]
+    OutOfBounds {
+        sheet: String,
+        path: String,
+        x: u32,
+        y: u32,
+        w: u32,
+        h: u32,
+    },
+    #[error("sheet '{sheet}': its image {image} has no file extension")]
+    ImageName { sheet: String, image: String },
+    #[error("no sheet named '{0}'")]
+    NoSuchName(String),
+    #[error("no sheet at store index {0}")]
+    NoSuchIndex(usize),
+}
+
+/// the name of the sheet in 'json_path', its file name without the extension, for errors
+/// found before the sheet itself is read.
+pub fn sheet_name(json_path: &str) -> String {
+    path::Path::new(json_path)
+        .file_stem()
+        .map_or(json_path.to_string(), |stem| stem.to_string_lossy().into())
+}
 
-    let image = ImageReader::open(format!("res/sheets/{}", json.meta.image))?
-        .decode()?
+/// sheet and atlas images are stored next to their json.
+fn image_path(json_path: &str, image: &str) -> path::PathBuf {
+    path::Path::new(json_path)
+        .parent()
+        .unwrap_or(path::Path::new(""))
+        .join(image)
+}
+
+/// read an asset of the sheet named 'sheet'.
+fn read_asset(sheet: &str, asset_path: &path::Path) -> Result<Vec<u8>, SpriteStoreError> {
+    let path = asset_path.to_string_lossy();
+    assets::read(&path).map_err(|source| SpriteStoreError::Read {
+        sheet: sheet.to_string(),
+        path: path.to_string(),
+        source,
+    })
+}
+
+/// decode the image of the sheet named 'sheet' into rows of pixels, optionally cropped to 'rect'.
+fn load_image(
+    sheet: &str,
+    image_path: &path::Path,
+    rect: Option<&Xywh>,
+) -> Result<Vec<Vec<Pixel>>, SpriteStoreError> {
+    let image = image::load_from_memory(&read_asset(sheet, image_path)?)
+        .map_err(|source| SpriteStoreError::Image {
+            sheet: sheet.to_string(),
+            path: image_path.to_string_lossy().into(),
+            source,
+        })?
         .into_rgba8();
+    let rect = match rect {
+        Some(rect) => rect.clone(),
+        None => Xywh {
+            x: 0,
+            y: 0,
+            w: image.width(),
+            h: image.height(),
+        },
+    };
+    if rect.x + rect.w > image.width() || rect.y + rect.h > image.height() {
+        return Err(SpriteStoreError::OutOfBounds {
+            sheet: sheet.to_string(),
+            path: image_path.to_string_lossy().into(),
+            x: rect.x,
+            y: rect.y,
+            w: rect.w,
+            h: rect.h,
+        });
+    }
 
     // convert rows to indexable vec
-    let rows = image.rows();
-    let mut rows_vec: Vec<Vec<Rgba>> = vec![];
-    for row in rows {
-        let mut row_vec: Vec<Rgba> = vec![];
-        for pixel in row {
-            row_vec.push(Rgba {
-                r: pixel[0],
-                g: pixel[1],
-                b: pixel[2],
-                a: pixel[3],
-            });
+    let mut rows_vec: Vec<Vec<Pixel>> = vec![];
+    for y in rect.y..(rect.y + rect.h) {
+        let mut row_vec: Vec<Pixel> = vec![];
+        for x in rect.x..(rect.x + rect.w) {
+            let pixel = image.get_pixel(x, y);
+            row_vec.push(Pixel::rgba(pixel[0], pixel[1], pixel[2], pixel[3]));
         }
         rows_vec.push(row_vec);
     }
+    Ok(rows_vec)
+}
 
-    let name = json.meta.image.rsplit_once('.').unwrap().0.to_string();
+fn load_sprite(
+    json_path: &str,
+    json: SpriteSheetJSON,
+    index: usize,
+) -> Result<LoadedSprite, SpriteStoreError> {
+    // a sheet is named for its image
+    let name = match json.meta.image.rsplit_once('.') {
+        Some((name, _)) => name.to_string(),
+        None => {
+            return Err(SpriteStoreError::ImageName {
+                sheet: sheet_name(json_path),
+                image: json.meta.image,
+            })
+        }
+    };
+    let image = load_image(&name, &image_path(json_path, &json.meta.image), None)?;
 
     Ok(LoadedSprite {
         name,
-        index,
+        index: SpriteHandle(index),
+        path: json_path.to_string(),
         data: json,
-        image: rows_vec,
+        image,
     })
 }
 
+fn load_atlas(
+    json_path: &str,
+    atlas: AtlasJSON,
+    first_index: usize,
+) -> Result<Vec<LoadedSprite>, SpriteStoreError> {
+    // each sheet gets its own cropped image, so the renderer doesn't need to know about atlases
+    let atlas_image = image_path(json_path, &atlas.meta.image);
+    let mut sprites: Vec<LoadedSprite> = vec![];
+    for (i, sheet) in atlas.sheets.into_iter().enumerate() {
+        let image = load_image(&sheet.name, &atlas_image, Some(&sheet.rect))?;
+        sprites.push(LoadedSprite {
+            name: sheet.name,
+            index: SpriteHandle(first_index + i),
+            path: json_path.to_string(),
+            data: SpriteSheetJSON {
+                frames: sheet.frames,
+                meta: SpriteSheetJSONMeta {
+                    size: WH {
+                        w: sheet.rect.w,
+                        h: sheet.rect.h,
+                    },
+                    palette_cycles: sheet.palette_cycles,
+                    frame_tags: sheet.frame_tags,
+                    ..atlas.meta.clone()
+                },
+            },
+            image,
+        });
+    }
+    Ok(sprites)
+}
+
+/// load a sprite sheet json, or every sheet in an atlas json, starting at store index 'first_index'.
+fn load_sheets(json_path: &str, first_index: usize) -> Result<Vec<LoadedSprite>, SpriteStoreError> {
+    let sheet = sheet_name(json_path);
+    let json_bytes = read_asset(&sheet, path::Path::new(json_path))?;
+    let bad_json = |source| SpriteStoreError::Json {
+        sheet: sheet.clone(),
+        path: json_path.to_string(),
+        source,
+    };
+    let value = serde_json::from_slice::<serde_json::Value>(&json_bytes).map_err(bad_json)?;
+    if value.get("sheets").is_some() {
+        let atlas = serde_json::from_value::<AtlasJSON>(value).map_err(bad_json)?;
+        load_atlas(json_path, atlas, first_index)
+    } else {
+        let json = serde_json::from_value::<SpriteSheetJSON>(value).map_err(bad_json)?;
+        Ok(vec![load_sprite(json_path, json, first_index)?])
+    }
+}
+
 #[derive(Default)]
 pub struct SpriteStore(pub Vec<LoadedSprite>);
 
 impl SpriteStore {
     pub fn new(json_paths: Vec<&str>) -> Result<SpriteStore, AppError> {
         let mut store: SpriteStore = SpriteStore(Vec::new());
-        for (index, path) in json_paths.iter().enumerate() {
-            store.0.push(load_sprite(path, index)?);
+        for path in json_paths.iter() {
+            let sheets = load_sheets(path, store.0.len())?;
+            store.0.extend(sheets);
         }
 
         Ok(store)
     }
 
     #[allow(dead_code)]
-    pub fn by_index(&self, index: usize) -> Result<&LoadedSprite, SpriteStoreError> {
-        if self.0.is_empty() || self.0.len() - 1 < index {
-            return Err(SpriteStoreError);
-        }
-        Ok(&self.0[index])
+    pub fn by_index(&self, index: SpriteHandle) -> Result<&LoadedSprite, SpriteStoreError> {
+        self.0
+            .get(index.0)
+            .ok_or(SpriteStoreError::NoSuchIndex(index.0))
     }
 
-    #[allow(dead_code)]
+    #[cfg(not(target_arch = "wasm32"))]
     pub fn by_name(&self, name: String) -> Result<&LoadedSprite, SpriteStoreError> {
         for sprite in self.0.iter() {
             if sprite.name == name {
                 return Ok(sprite);
             }
         }
-        Err(SpriteStoreError)
+        Err(SpriteStoreError::NoSuchName(name))
     }
 
-    pub fn index_by_name(&self, name: &str) -> Result<usize, SpriteStoreError> {
+    /// reload the sheet at 'index' from disk in place, keeping its index stable. the old sheet
+    /// is kept if the new one fails to load, since editors often write files in several steps.
+    /// a sheet loaded from an atlas is reloaded from its own file, which is the one edited.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn reload(&mut self, index: SpriteHandle) -> Result<(), AppError> {
+        let (path, name) = {
+            let sprite = self.by_index(index)?;
+            let own = SHEETS.iter().find(|sheet| sheet_name(sheet) == sprite.name);
+            let path = own.map_or(sprite.path.clone(), |sheet| sheet.to_string());
+            (path, sprite.name.clone())
+        };
+        let mut sprite = load_sheets(&path, 0)?
+            .into_iter()
+            .find(|s| s.name == name)
+            .ok_or(SpriteStoreError::NoSuchName(name))?;
+        sprite.index = index;
+        self.0[index.0] = sprite;
+        Ok(())
+    }
+
+    /// indices of loaded sheets whose json or image file has the given file name, or that are
+    /// named for it, like sheets packed into an atlas.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn indices_by_file(&self, file_name: &str) -> Vec<SpriteHandle> {
+        self.0
+            .iter()
+            .filter(|sprite| {
+                sprite.data.meta.image == file_name
+                    || path::Path::new(&sprite.path)
+                        .file_name()
+                        .is_some_and(|f| f == file_name)
+                    || sheet_name(file_name) == sprite.name
+            })
+            .map(|sprite| sprite.index)
+            .collect()
+    }
+
+    pub fn index_by_name(&self, name: &str) -> Result<SpriteHandle, SpriteStoreError> {
         for sprite in self.0.iter() {
             if sprite.name == name {
                 return Ok(sprite.index);
             }
         }
-        Err(SpriteStoreError)
+        Err(SpriteStoreError::NoSuchName(name.to_string()))
+    }
+}
+
+impl std::ops::Index<SpriteHandle> for SpriteStore {
+    type Output = LoadedSprite;
+
+    fn index(&self, handle: SpriteHandle) -> &LoadedSprite {
+        &self.0[handle.0]
+    }
+}
+
+/// specs resource with handles to each of the game's own sheets, so systems don't look them
+/// up by name as they run. a store missing any of them fails to load instead.
+#[derive(Default, Debug, Clone, Copy)]
+pub struct SpriteIds {
+    pub character_00: SpriteHandle,
+    pub character_01: SpriteHandle,
+    pub tile_dirt: SpriteHandle,
+    pub grass: SpriteHandle,
+    pub tool_shovel: SpriteHandle,
+    pub tool_watercan: SpriteHandle,
+    pub tool_packet: SpriteHandle,
+    pub tool_packet2: SpriteHandle,
+    pub crop_empty: SpriteHandle,
+    pub crop_leaf: SpriteHandle,
+    pub crop_flower: SpriteHandle,
+    pub cryopod: SpriteHandle,
+    pub terminal: SpriteHandle,
+    pub compost_bin: SpriteHandle,
+    pub dome_wall: SpriteHandle,
+    pub robot: SpriteHandle,
+    pub solar_panel: SpriteHandle,
+    pub sprinkler: SpriteHandle,
+    pub teleporter: SpriteHandle,
+    pub tool_fiber: SpriteHandle,
+    pub tool_seed: SpriteHandle,
+    pub transition: SpriteHandle,
+    pub particle_dirt: SpriteHandle,
+    pub particle_water: SpriteHandle,
+    pub particle_heart: SpriteHandle,
+    pub portrait_k: SpriteHandle,
+    pub emote: SpriteHandle,
+    pub channel: SpriteHandle,
+    pub tap: SpriteHandle,
+    pub tile_ground: SpriteHandle,
+    pub tile_water: SpriteHandle,
+}
+
+impl SpriteIds {
+    pub fn new(store: &SpriteStore) -> Result<SpriteIds, SpriteStoreError> {
+        let id = |name| store.index_by_name(name);
+        Ok(SpriteIds {
+            character_00: id("character-00")?,
+            character_01: id("character-01")?,
+            tile_dirt: id("tile-dirt")?,
+            grass: id("grass")?,
+            tool_shovel: id("tool-shovel")?,
+            tool_watercan: id("tool-watercan")?,
+            tool_packet: id("tool-packet")?,
+            tool_packet2: id("tool-packet2")?,
+            crop_empty: id("crop-empty")?,
+            crop_leaf: id("crop-leaf")?,
+            crop_flower: id("crop-flower")?,
+            cryopod: id("cryopod")?,
+            terminal: id("terminal")?,
+            compost_bin: id("compost-bin")?,
+            dome_wall: id("dome-wall")?,
+            robot: id("robot")?,
+            solar_panel: id("solar-panel")?,
+            sprinkler: id("sprinkler")?,
+            teleporter: id("teleporter")?,
+            tool_fiber: id("tool-fiber")?,
+            tool_seed: id("tool-seed")?,
+            transition: id("transition")?,
+            particle_dirt: id("particle-dirt")?,
+            particle_water: id("particle-water")?,
+            particle_heart: id("particle-heart")?,
+            portrait_k: id("portrait-k")?,
+            emote: id("emote")?,
+            channel: id("channel")?,
+            tap: id("tap")?,
+            tile_ground: id("tile-ground")?,
+            tile_water: id("tile-water")?,
+        })
+    }
+}
+
+/// watches the sheet directory so edited sheets can be reloaded into a running game.
+#[cfg(feature = "dev")]
+pub struct SheetWatcher {
+    _watcher: notify::RecommendedWatcher,
+    rx: mpsc::Receiver<notify::Result<notify::Event>>,
+}
+
+#[cfg(feature = "dev")]
+impl SheetWatcher {
+    /// watch the directory the game's sheets are read from, wherever on the asset search path
+    /// that is.
+    pub fn new() -> Result<SheetWatcher, AppError> {
+        let first = assets::find(SHEETS[0])?;
+        let dir = first.parent().unwrap_or(path::Path::new("."));
+        let (tx, rx) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(tx)?;
+        watcher.watch(dir, notify::RecursiveMode::NonRecursive)?;
+        Ok(SheetWatcher {
+            _watcher: watcher,
+            rx,
+        })
+    }
+
+    /// drain pending file events, returning the names of files that were written.
+    pub fn changed_files(&self) -> Vec<String> {
+        let mut files: Vec<String> = vec![];
+        while let Ok(event) = self.rx.try_recv() {
+            let Ok(event) = event else {
+                continue;
+            };
+            if !matches!(
+                event.kind,
+                notify::EventKind::Create(_) | notify::EventKind::Modify(_)
+            ) {
+                continue;
+            }
+            for changed in event.paths {
+                if let Some(name) = changed.file_name().and_then(|n| n.to_str()) {
+                    if !files.iter().any(|f| f == name) {
+                        files.push(name.to_string());
+                    }
+                }
+            }
+        }
+        files
     }
 }
@@ -0,0 +1,296 @@
+use super::{session, AppError};
+use russh::keys::ssh_key::private::Ed25519Keypair;
+use russh::keys::ssh_key::LineEnding;
+use russh::keys::{PrivateKey, PublicKey};
+use russh::server::{Auth, ChannelOpenHandle, Config, Handle, Handler, Msg, Server, Session};
+use russh::{Channel, ChannelId, MethodKind, MethodSet};
+use std::sync::{mpsc, Arc};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+
+pub const DEFAULT_PORT: u16 = 2222;
+
+/// the server's host key, made on the first start and kept so visitors can trust it once.
+pub const HOST_KEY: &str = "ssh_host_key";
+
+/// who may visit: anyone, or only those with the password or one of the keys.
+#[derive(Default)]
+struct Access {
+    open: bool,
+    password: Option<String>,
+    keys: Vec<PublicKey>,
+}
+
+impl Access {
+    /// the methods left to try after a rejected one.
+    fn methods(&self) -> MethodSet {
+        let mut methods = vec![];
+        if self.password.is_some() {
+            methods.push(MethodKind::Password);
+        }
+        if !self.keys.is_empty() {
+            methods.push(MethodKind::PublicKey);
+        }
+        MethodSet::from(&methods[..])
+    }
+
+    fn reject(&self) -> Auth {
+        Auth::Reject {
+            proceed_with_methods: Some(self.methods()),
+            partial_success: false,
+        }
+    }
+}
+
+/// the public keys in an authorized_keys file, one per line in the usual "type key comment"
+/// form. blank lines and comments are skipped.
+fn authorized_keys(path: &str) -> Result<Vec<PublicKey>, AppError> {
+    std::fs::read_to_string(path)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| PublicKey::from_openssh(line).map_err(|e| russh::Error::from(e).into()))
+        .collect()
+}
+
+/// the host key saved at 'path', or a new one saved there if there's none yet.
+fn host_key(path: &str) -> Result<PrivateKey, AppError> {
+    if let Ok(pem) = std::fs::read_to_string(path) {
+        return PrivateKey::from_openssh(pem).map_err(|e| russh::Error::from(e).into());
+    }
+    // thread_rng is a cryptographically secure generator, fine for a host key
+    let key = PrivateKey::from(Ed25519Keypair::from_seed(&rand::random::<[u8; 32]>()));
+    let pem = key.to_openssh(LineEnding::LF).map_err(russh::Error::from)?;
+    std::fs::write(path, pem.as_bytes())?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    }
+    Ok(key)
+}
+
+/// writes a session's output to its ssh channel. bytes are collected until flush, then sent
+/// from the async side.
+struct ChannelOutput {
+    sender: UnboundedSender<Vec<u8>>,
+    sink: Vec<u8>,
+}
+
+impl ChannelOutput {
+    fn start(handle: Handle, channel: ChannelId) -> ChannelOutput {
+        let (sender, mut receiver) = unbounded_channel::<Vec<u8>>();
+        tokio::spawn(async move {
+            while let Some(data) = receiver.recv().await {
+                if handle.data(channel, data).await.is_err() {
+                    break;
+                }
+            }
+            let _ = handle.eof(channel).await;
+            let _ = handle.close(channel).await;
+        });
+        ChannelOutput {
+            sender,
+            sink: vec![],
+        }
+    }
+}
+
+impl std::io::Write for ChannelOutput {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.sink.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        if self.sink.is_empty() {
+            return Ok(());
+        }
+        self.sender
+            .send(std::mem::take(&mut self.sink))
+            .map_err(|_| std::io::Error::from(std::io::ErrorKind::BrokenPipe))
+    }
+}
+
+struct SshServer {
+    access: Arc<Access>,
+}
+
+impl Server for SshServer {
+    type Handler = SshClient;
+
+    fn new_client(&mut self, _: Option<std::net::SocketAddr>) -> SshClient {
+        SshClient {
+            access: self.access.clone(),
+            size: (80, 24),
+            events: None,
+        }
+    }
+}
+
+/// one ssh connection. each shell gets its own game, running on its own thread.
+struct SshClient {
+    access: Arc<Access>,
+    size: (u16, u16),
+    events: Option<mpsc::Sender<session::SessionEvent>>,
+}
+
+impl Handler for SshClient {
+    type Error = russh::Error;
+
+    // on an open server, visiting a farm needs no account
+    async fn auth_none(&mut self, _: &str) -> Result<Auth, Self::Error> {
+        if self.access.open {
+            return Ok(Auth::Accept);
+        }
+        Ok(self.access.reject())
+    }
+
+    async fn auth_password(&mut self, _: &str, password: &str) -> Result<Auth, Self::Error> {
+        if self.access.open || self.access.password.as_deref() == Some(password) {
+            return Ok(Auth::Accept);
+        }
+        Ok(self.access.reject())
+    }
+
+    async fn auth_publickey(&mut self, _: &str, key: &PublicKey) -> Result<Auth, Self::Error> {
+        if self.access.open
+            || self
+                .access
+                .keys
+                .iter()
+                .any(|k| k.key_data() == key.key_data())
+        {
+            return Ok(Auth::Accept);
+        }
+        Ok(self.access.reject())
+    }
+
+    async fn channel_open_session(
+        &mut self,
+        _: Channel<Msg>,
+        reply: ChannelOpenHandle,
+        _: &mut Session,
+    ) -> Result<(), Self::Error> {
+        reply.accept().await;
+        Ok(())
+    }
+
+    async fn pty_request(
+        &mut self,
+        channel: ChannelId,
+        _: &str,
+        col_width: u32,
+        row_height: u32,
+        _: u32,
+        _: u32,
+        _: &[(russh::Pty, u32)],
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        // clients without a terminal of their own report no size, and keep the default one
+        if col_width > 0 && row_height > 0 {
+            self.size = (col_width as u16, row_height as u16);
+        }
+        session.channel_success(channel)?;
+        Ok(())
+    }
+
+    async fn shell_request(
+        &mut self,
+        channel: ChannelId,
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        let (tx, rx) = mpsc::channel();
+        self.events = Some(tx);
+        let out = ChannelOutput::start(session.handle(), channel);
+        let size = self.size;
+        std::thread::spawn(move || {
+            if let Err(e) = session::run(size, rx, Box::new(out)) {
+                eprintln!("ssh session ended with error: {}", e);
+            }
+        });
+        session.channel_success(channel)?;
+        Ok(())
+    }
+
+    async fn data(
+        &mut self,
+        _: ChannelId,
+        data: &[u8],
+        _: &mut Session,
+    ) -> Result<(), Self::Error> {
+        if let Some(events) = &self.events {
+            let _ = events.send(session::SessionEvent::Data(data.to_vec()));
+        }
+        Ok(())
+    }
+
+    async fn window_change_request(
+        &mut self,
+        _: ChannelId,
+        col_width: u32,
+        row_height: u32,
+        _: u32,
+        _: u32,
+        _: &mut Session,
+    ) -> Result<(), Self::Error> {
+        if let Some(events) = self
+            .events
+            .as_ref()
+            .filter(|_| col_width > 0 && row_height > 0)
+        {
+            let resize = session::SessionEvent::Resize(col_width as u16, row_height as u16);
+            let _ = events.send(resize);
+        }
+        Ok(())
+    }
+}
+
+/// serve an independent game to everyone who connects:
+/// `qffp ssh [addr] [--password <password>] [--authorized-keys <file>] [--open]`. visitors need
+/// the password or one of the keys, unless the server is '--open' to anyone. the host key is
+/// kept in HOST_KEY, so it stays the same across restarts.
+pub fn run(args: &[String]) -> Result<(), AppError> {
+    let usage = || {
+        AppError::Usage(
+            "qffp ssh [addr] [--password <password>] [--authorized-keys <file>] [--open]"
+                .to_string(),
+        )
+    };
+    let mut addr = format!("127.0.0.1:{}", DEFAULT_PORT);
+    let mut access = Access::default();
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "--open" => access.open = true,
+            "--password" => access.password = Some(args_iter.next().ok_or_else(usage)?.clone()),
+            "--authorized-keys" => {
+                access
+                    .keys
+                    .extend(authorized_keys(args_iter.next().ok_or_else(usage)?)?);
+            }
+            _ if arg.starts_with("--") => return Err(usage()),
+            _ => addr = arg.clone(),
+        }
+    }
+    if !access.open && access.password.is_none() && access.keys.is_empty() {
+        return Err(usage());
+    }
+
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime.block_on(async {
+        let config = Config {
+            inactivity_timeout: Some(std::time::Duration::from_secs(3600)),
+            auth_rejection_time: std::time::Duration::from_secs(1),
+            auth_rejection_time_initial: Some(std::time::Duration::from_secs(0)),
+            keys: vec![host_key(HOST_KEY)?],
+            nodelay: true,
+            ..Default::default()
+        };
+        println!("serving qffp over ssh on {}", addr);
+        let mut server = SshServer {
+            access: Arc::new(access),
+        };
+        server.run_on_address(Arc::new(config), &addr).await?;
+        Ok(())
+    })
+}
@@ -1,8 +1,23 @@
-use super::terminal;
+use super::{dialogue, savefile, settings, story, weather, AppError};
+use rand::{rngs::StdRng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use specs::storage::MaskedStorage;
 use specs::{
-    Component, Entities, LazyUpdate, Read, ReadStorage, System, VecStorage, Write, WriteStorage,
+    Component, Entities, Entity, FlaggedStorage, LazyUpdate, NullStorage, Read, ReadStorage,
+    Storage, System, VecStorage, Write, WriteStorage,
 };
-use std::ops::Add;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::{Add, Deref};
+
+/// specs resource for the game's randomness, seeded along with the farm's layout so a game
+/// plays out the same way from the same seed and inputs.
+pub struct GameRng(pub StdRng);
+
+impl Default for GameRng {
+    fn default() -> Self {
+        GameRng(StdRng::from_entropy())
+    }
+}
 
 /// Time stored to be used as a specs resource.
 #[derive(Default, PartialEq, PartialOrd, Clone, Copy)]
@@ -16,6 +31,43 @@ impl Add for Time {
     }
 }
 
+/// milliseconds of real time played, unaffected by the game speed. stored as a specs resource.
+#[derive(Default, PartialEq, PartialOrd, Clone, Copy)]
+pub struct RealTime(pub u64);
+
+/// how far real time has got into the next fixed step, from 0.0 to 1.0, for drawing between
+/// steps. stored as a specs resource.
+#[derive(Default, PartialEq, PartialOrd, Clone, Copy)]
+pub struct Interpolation(pub f32);
+
+/// size of the terminal the game is rendered to, in cells. stored as a specs resource so
+/// systems don't query the process's own terminal, which may not be the one being played on.
+#[derive(Default, PartialEq, Clone, Copy)]
+pub struct ScreenSize(pub u16, pub u16);
+
+/// size of the farm in cells, set by the settings rather than the terminal. systems keep
+/// everything inside it. stored as a specs resource.
+#[derive(Default, PartialEq, Clone, Copy)]
+pub struct WorldSize(pub u16, pub u16);
+
+/// the world cell at the top left of the screen. stored as a specs resource.
+#[derive(Default, PartialEq, Clone, Copy, Debug)]
+pub struct Camera(pub i64, pub i64);
+
+impl Camera {
+    /// center the screen on 'focus', without showing past the edges of the world. a world
+    /// smaller than the screen stays at its top left.
+    pub fn follow(focus: (i64, i64), screen: ScreenSize, world: WorldSize) -> Camera {
+        let axis = |focus: i64, screen: u16, world: u16| {
+            (focus - screen as i64 / 2).clamp(0, (world as i64 - screen as i64).max(0))
+        };
+        Camera(
+            axis(focus.0, screen.0, world.0),
+            axis(focus.1, screen.1, world.1),
+        )
+    }
+}
+
 /// SpriteIndexer is used to generate 'id' values on sprites.
 /// it exists at runtime as a mutable specs resource.
 #[derive(Default)]
@@ -29,16 +81,92 @@ impl SpriteIndexer {
     }
 }
 
-/// Essential specs 'Position' component used with sprites.
-#[derive(Component, Debug)]
-#[storage(VecStorage)]
+/// Essential specs 'Position' component used with sprites. flagged when written, so the
+/// renderer can tell what moved.
+#[derive(Debug, Clone)]
 pub struct Position {
     pub x: i64,
     pub y: i64,
-    pub z: i64,
 }
 
-#[derive(Default, Debug, PartialOrd, PartialEq)]
+impl Component for Position {
+    type Storage = FlaggedStorage<Self, VecStorage<Self>>;
+}
+
+/// milliseconds something moving smoothly takes to close most of the way to where it is, and
+/// how many cells away it can be before it jumps straight there instead, as after a teleport.
+pub const GLIDE_TIME: u64 = 60;
+pub const SNAP_DISTANCE: f32 = 8.0;
+
+/// specs component for something that moves smoothly: where it's drawn, in fractional cells,
+/// easing after its Position instead of jumping a whole step at once. it's only rounded to a
+/// cell when drawn.
+#[derive(Component, Debug, Clone, Copy, Default)]
+#[storage(VecStorage)]
+pub struct Motion {
+    /// where it's drawn, once it's been placed.
+    drawn: Option<(f32, f32)>,
+    /// the game time it last moved.
+    at: u64,
+}
+
+impl Motion {
+    /// ease toward 'to' for the time since the last move, up to 'time'.
+    pub fn glide(&mut self, to: &Position, time: u64) {
+        let (x, y) = (to.x as f32, to.y as f32);
+        let eased = (time.saturating_sub(self.at) as f32 / GLIDE_TIME as f32).min(1.0);
+        self.at = time;
+        self.drawn = Some(match self.drawn {
+            Some((dx, dy)) if (x - dx).abs().max((y - dy).abs()) <= SNAP_DISTANCE => {
+                (dx + (x - dx) * eased, dy + (y - dy) * eased)
+            }
+            _ => (x, y),
+        });
+    }
+}
+
+/// where something is drawn: at its Position, or if it moves smoothly, the cell nearest to
+/// where it's got to.
+pub fn drawn_pos(pos: &Position, motion: Option<&Motion>) -> Position {
+    match motion.and_then(|motion| motion.drawn) {
+        Some((x, y)) => Position {
+            x: x.round() as i64,
+            y: y.round() as i64,
+        },
+        None => pos.clone(),
+    }
+}
+
+/// specs component for the layer a sprite is drawn in, from the bottom up.
+#[derive(
+    Component, Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize,
+)]
+#[storage(VecStorage)]
+pub enum RenderLayer {
+    #[default]
+    Ground,
+    /// dug plots and crops, and what's built on the ground.
+    Crops,
+    Grass,
+    /// players and npcs, and the furniture they walk in front of and behind.
+    Player,
+    /// tools, drawn over whoever holds them.
+    Tools,
+    /// particles and screen effects.
+    Overlay,
+}
+
+/// the order sprites are drawn in: by layer, then within a layer, lower on the screen over
+/// higher, and newer over older.
+pub fn draw_order(
+    layer: Option<&RenderLayer>,
+    pos: &Position,
+    sprite: &Sprite,
+) -> (RenderLayer, i64, usize) {
+    (layer.copied().unwrap_or_default(), pos.y, sprite.id)
+}
+
+#[derive(Default, Debug, PartialOrd, PartialEq, Clone, Copy, Serialize, Deserialize)]
 pub enum SpriteType {
     #[default]
     Background,
@@ -46,34 +174,45 @@ pub enum SpriteType {
     Player,
     Tool,
     Crop,
+    /// small things that go in the pocket when picked up, like fiber and seeds.
+    Item,
     Particle,
 }
 
-/// Sprite is a specs component for sprites, and also tracks some game state.
-#[derive(Component, Debug)]
-#[storage(VecStorage)]
+/// Sprite is a specs component for sprites, and also tracks some game state. flagged when
+/// written, like Position.
+#[derive(Debug, Clone)]
 pub struct Sprite {
     pub id: usize,
-    pub store_index: usize,
+    pub store_index: super::sprite::SpriteHandle,
     pub flip: bool,
     pub frame: usize,
     pub animating: bool,
+    /// how fast it animates, as a multiple of the frame durations in its sheet.
+    pub anim_speed: f32,
     pub last_animate: u64,
     pub last_move: u64,
     pub sprite_type: SpriteType,
     pub highlight: bool,
+    /// left out when drawing, like the sleep transition between nights.
     pub hidden: bool,
+    /// despawned by CleanupSystem at the end of the update, along with the rest of its entity.
     pub delete: bool,
 }
 
+impl Component for Sprite {
+    type Storage = FlaggedStorage<Self, VecStorage<Self>>;
+}
+
 impl Default for Sprite {
     fn default() -> Self {
         Sprite {
             id: 0,
-            store_index: 0,
+            store_index: super::sprite::SpriteHandle::default(),
             flip: false,
             frame: 0,
             animating: false,
+            anim_speed: 1.0,
             last_animate: 0,
             last_move: 0,
             sprite_type: SpriteType::Background,
@@ -84,8 +223,136 @@ impl Default for Sprite {
     }
 }
 
+/// specs component playing the frames of one frame tag in a sprite's sheet, like a
+/// character's walk, instead of every frame in it. sheets without the tag play every frame.
+#[derive(Component, Debug, Clone)]
+#[storage(VecStorage)]
+pub struct Animation {
+    pub tag: &'static str,
+    /// whether a pingpong tag is on its way back through its frames.
+    back: bool,
+}
+
+impl Animation {
+    pub fn new(tag: &'static str) -> Animation {
+        Animation { tag, back: false }
+    }
+
+    /// switch to playing 'tag', showing its first frame, unless it's already playing.
+    pub fn play(
+        &mut self,
+        tag: &'static str,
+        sprite: &mut Sprite,
+        info: &super::sprite::LoadedSprite,
+    ) {
+        if self.tag == tag {
+            return;
+        }
+        *self = Animation::new(tag);
+        if let Some(tag) = info.tag(tag) {
+            self.back = tag.direction.starts_back();
+            sprite.frame = if self.back { tag.to } else { tag.from };
+        }
+    }
+
+    /// the frame after 'frame' in the tag, going round again from the end, or its first frame
+    /// if 'frame' isn't in it. None if the sheet has no such tag.
+    fn next(&mut self, frame: usize, info: &super::sprite::LoadedSprite) -> Option<usize> {
+        let tag = info.tag(self.tag)?;
+        if !tag.contains(frame) {
+            self.back = tag.direction.starts_back();
+            return Some(if self.back { tag.to } else { tag.from });
+        }
+        if !tag.direction.pingpong() {
+            self.back = tag.direction.starts_back();
+        } else if tag.from < tag.to && frame == if self.back { tag.from } else { tag.to } {
+            self.back = !self.back;
+        }
+        Some(match (self.back, frame) {
+            (true, f) if f == tag.from => tag.to,
+            (true, f) => f - 1,
+            (false, f) if f == tag.to => tag.from,
+            (false, f) => f + 1,
+        })
+    }
+}
+
+/// how long a character keeps walking after it was last asked to, and how long using something
+/// plays for.
+pub const WALK_STOP: u64 = 400;
+pub const USE_TIME: u64 = 300;
+
+/// what a character is doing, as far as drawing it goes. each state plays the frame tag of the
+/// same name, or stands idle if the sheet doesn't have one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AnimState {
+    #[default]
+    Idle,
+    Walk,
+    Use,
+    Held,
+}
+
+impl AnimState {
+    pub fn tag(self) -> &'static str {
+        match self {
+            AnimState::Idle => "idle",
+            AnimState::Walk => "walk",
+            AnimState::Use => "use",
+            AnimState::Held => "held",
+        }
+    }
+}
+
+/// specs component keeping a character's animation state as the player and npc logic moves it
+/// along, and choosing the tag its Animation plays. walking stops WALK_STOP after it was last
+/// asked for, and a use plays out for USE_TIME before the character walks again.
+#[derive(Component, Debug, Clone, Copy, Default)]
+#[storage(VecStorage)]
+pub struct AnimationController {
+    state: AnimState,
+    /// the game time the state was last asked for.
+    since: u64,
+}
+
+impl AnimationController {
+    /// ask for 'state' at 'time'.
+    pub fn set(&mut self, state: AnimState, time: u64) {
+        let using = self.state == AnimState::Use && time < self.since + USE_TIME;
+        if state == AnimState::Walk && using {
+            return;
+        }
+        self.state = state;
+        self.since = time;
+    }
+
+    /// go back to idle from a walk or use that's over by 'time', then give the tag to play
+    /// and whether it animates or holds its first frame.
+    fn update(&mut self, time: u64, info: &super::sprite::LoadedSprite) -> (&'static str, bool) {
+        let over = match self.state {
+            AnimState::Walk => self.since + WALK_STOP < time,
+            AnimState::Use => self.since + USE_TIME <= time,
+            AnimState::Idle | AnimState::Held => false,
+        };
+        if over {
+            self.set(AnimState::Idle, time);
+        }
+        match info.tag(self.state.tag()) {
+            Some(_) => (
+                self.state.tag(),
+                matches!(self.state, AnimState::Walk | AnimState::Use),
+            ),
+            None => (AnimState::Idle.tag(), false),
+        }
+    }
+}
+
+/// animation speeds for grass swaying in the background, and for a sprinting player's walk.
+pub const GRASS_ANIM_SPEED: f32 = 0.5;
+pub const SPRINT_ANIM_SPEED: f32 = 2.0;
+
 #[allow(dead_code)] // only necessary because Grass is not guaranteed to spawn
-#[derive(Default, Debug, PartialOrd, PartialEq, Clone, Copy)]
+#[derive(Default, Debug, PartialOrd, PartialEq, Clone, Copy, Serialize, Deserialize)]
 pub enum ItemType {
     #[default]
     None,
@@ -98,128 +365,1327 @@ pub enum ItemType {
     Packet2,
     Crop,
     Npc,
+    Robot,
+    Fiber,
+    Compost,
+    Seed,
+    Seed2,
+    /// produce from harvested crops, kept in the pocket.
+    Leaf,
+    Flower,
+}
+
+/// fiber needed for one fertilizer, and the nights it takes to rot.
+pub const FIBER_PER_FERTILIZER: u32 = 3;
+pub const COMPOST_NIGHTS: u32 = 2;
+
+/// specs component for the compost bin, where fiber from cleared grass rots into fertilizer.
+#[derive(Component, Debug, Clone, Default, Serialize, Deserialize)]
+#[storage(VecStorage)]
+pub struct Compost {
+    pub fiber: u32,
+    /// nights the current batch has been rotting.
+    pub nights: u32,
+    /// fertilizer waiting to be collected.
+    pub ready: u32,
 }
 
-pub struct SpriteDepths {
-    pub ground: i64,
-    pub grass: i64,
-    pub crops: i64,
-    pub player: i64,
-    pub tools: i64,
-    pub overlay: i64,
+/// specs component for fertilized soil: the crop planted there grows overnight even if it
+/// wasn't watered, using up the fertilizer.
+#[derive(Component, Debug, Default)]
+#[storage(NullStorage)]
+pub struct Fertilized;
+
+/// specs component for a crop out in a drought: it needs watering a second time before night,
+/// or it wilts a stage instead of growing.
+#[derive(Component, Debug, Default)]
+#[storage(NullStorage)]
+pub struct Thirsty;
+
+/// nights of water a crop holds once watered, and the fewest before it wilts.
+pub const FULL_MOISTURE: u32 = 3;
+pub const WILT_MOISTURE: u32 = 1;
+
+/// tint over a wilting crop, browning it.
+pub const WILTED: Tint = Tint::Multiply([200, 160, 110]);
+
+/// specs component for a crop's moisture: the nights of water it has left. each night it's
+/// watered fills it back up, and each it isn't dries it out by one. low on water, a crop wilts
+/// until it's watered again, and one left dry with none left dies back to an empty plot. a
+/// crop without one is freshly planted, and full.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[storage(VecStorage)]
+pub struct Moisture(pub u32);
+
+/// how far a hop carries the player, how long it lasts, and how many rows high it arcs.
+pub const HOP_DISTANCE: i64 = 8;
+pub const HOP_TIME: u64 = 500;
+pub const HOP_HEIGHT: f64 = 3.0;
+
+/// specs component for a player in the middle of a low-gravity hop from x 'from' to x 'to'.
+#[derive(Component, Debug)]
+#[storage(VecStorage)]
+pub struct Hop {
+    pub start: u64,
+    pub from: i64,
+    pub to: i64,
 }
 
-pub const DEPTHS: SpriteDepths = SpriteDepths {
-    ground: 0,
-    crops: 100_000,
-    grass: 200_000,
-    player: 300_000,
-    tools: 400_000,
-    overlay: 500_000,
-};
+impl Hop {
+    fn progress(&self, time: u64) -> f64 {
+        (time.saturating_sub(self.start) as f64 / HOP_TIME as f64).min(1.0)
+    }
+
+    pub fn landed(&self, time: u64) -> bool {
+        self.progress(time) >= 1.0
+    }
+
+    pub fn x(&self, time: u64) -> i64 {
+        self.from + ((self.to - self.from) as f64 * self.progress(time)).round() as i64
+    }
+
+    /// rows above the ground at 'time', for rendering.
+    pub fn height(&self, time: u64) -> i64 {
+        let t = self.progress(time);
+        (4.0 * t * (1.0 - t) * HOP_HEIGHT).round() as i64
+    }
+}
+
+/// milliseconds a dropped item takes to fall, and the rows it falls to the ground.
+pub const DROP_TIME: u64 = 150;
+pub const DROP_HEIGHT: i64 = 2;
+
+/// specs component for a dropped item falling from row 'from' to the ground at row 'to'.
+#[derive(Component, Debug)]
+#[storage(VecStorage)]
+pub struct Falling {
+    pub start: u64,
+    pub from: i64,
+    pub to: i64,
+}
+
+impl Falling {
+    fn progress(&self, time: u64) -> f64 {
+        (time.saturating_sub(self.start) as f64 / DROP_TIME as f64).min(1.0)
+    }
+
+    pub fn landed(&self, time: u64) -> bool {
+        self.progress(time) >= 1.0
+    }
+
+    /// the row at 'time', speeding up as it falls.
+    pub fn y(&self, time: u64) -> i64 {
+        let t = self.progress(time);
+        self.from + ((self.to - self.from) as f64 * t * t).round() as i64
+    }
+}
+
+/// milliseconds a flash takes to fade from white back to a sprite's own colors.
+pub const FLASH_TIME: u64 = 200;
+
+/// specs component recoloring a sprite as it's drawn, so systems can show something happened
+/// to it without touching its pixels.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[storage(VecStorage)]
+pub enum Tint {
+    /// every color's red, green and blue scaled by these, out of 255, until it's removed.
+    Multiply([u8; 3]),
+    /// white from 'start', fading out over FLASH_TIME.
+    Flash { start: u64 },
+}
+
+impl Tint {
+    /// whether the tint is over by 'time'. only a flash ever is.
+    pub fn faded(&self, time: u64) -> bool {
+        match self {
+            Tint::Multiply(_) => false,
+            Tint::Flash { start } => time >= start + FLASH_TIME,
+        }
+    }
+
+    /// 'color' as the tint draws it at 'time'.
+    pub fn apply(&self, color: super::display::Color, time: u64) -> super::display::Color {
+        let super::display::Color::Rgb { r, g, b } = color else {
+            return color;
+        };
+        let [r, g, b] = match *self {
+            Tint::Multiply(by) => {
+                let rgb = [r, g, b];
+                [0, 1, 2].map(|i| (rgb[i] as u16 * by[i] as u16 / 255) as u8)
+            }
+            Tint::Flash { start } => {
+                let left = (start + FLASH_TIME).saturating_sub(time).min(FLASH_TIME);
+                let white = (left * 255 / FLASH_TIME) as u16;
+                [r, g, b].map(|c| (c as u16 + (255 - c as u16) * white / 255) as u8)
+            }
+        };
+        super::display::Color::Rgb { r, g, b }
+    }
+}
+
+/// despawns every entity whose sprite is flagged for deletion. it runs after the systems that
+/// flag them, so they can still see the sprite for the rest of the update.
+pub struct CleanupSystem;
+
+impl<'a> System<'a> for CleanupSystem {
+    type SystemData = (Entities<'a>, ReadStorage<'a, Sprite>);
+
+    fn run(&mut self, data: Self::SystemData) {
+        use specs::Join;
+
+        let (entities, sprites) = data;
+        for (entity, sprite) in (&entities, &sprites).join() {
+            if sprite.delete {
+                let _ = entities.delete(entity);
+            }
+        }
+    }
+}
+
+/// eases everything that moves smoothly toward where it is, then points the camera at where
+/// the local player is drawn.
+pub struct MotionSystem;
+
+impl<'a> System<'a> for MotionSystem {
+    type SystemData = (
+        Read<'a, Time>,
+        Read<'a, ScreenSize>,
+        Read<'a, WorldSize>,
+        Write<'a, Camera>,
+        ReadStorage<'a, Player>,
+        ReadStorage<'a, Position>,
+        WriteStorage<'a, Motion>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        use specs::Join;
+
+        let (time, screen_size, world_size, mut camera, players, positions, mut motions) = data;
+        for (pos, motion) in (&positions, &mut motions).join() {
+            motion.glide(pos, time.0);
+        }
+        if let Some(focus) = player_focus(0, &players, &positions, &motions) {
+            *camera = Camera::follow(focus, *screen_size, *world_size);
+        }
+    }
+}
+
+/// takes tints off once they've faded.
+pub struct TintSystem;
+
+impl<'a> System<'a> for TintSystem {
+    type SystemData = (Entities<'a>, Read<'a, Time>, WriteStorage<'a, Tint>);
+
+    fn run(&mut self, data: Self::SystemData) {
+        use specs::Join;
+
+        let (entities, time, mut tints) = data;
+        let faded: Vec<Entity> = (&entities, &tints)
+            .join()
+            .filter(|(_, tint)| tint.faded(time.0))
+            .map(|(entity, _)| entity)
+            .collect();
+        for entity in faded {
+            tints.remove(entity);
+        }
+    }
+}
+
+/// fiber it takes to craft one teleporter pad, sprinkler, or solar panel.
+pub const PAD_FIBER: u32 = 4;
+pub const SPRINKLER_FIBER: u32 = 2;
+pub const PANEL_FIBER: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum MachineKind {
+    /// charges the power bank during the day.
+    Solar,
+    /// waters nearby crops every morning, drawing from the power bank.
+    Sprinkler,
+    /// lets water out into the irrigation channels joined to it.
+    Tap,
+    /// a segment of irrigation channel, watering the crops beside it while it's joined to a tap.
+    Channel,
+}
+
+/// specs component for crafted machines that make or use power.
+#[derive(Component, Debug)]
+#[storage(VecStorage)]
+pub struct Machine {
+    pub kind: MachineKind,
+}
+
+/// specs component for things characters can't walk through, as a box of cells from the
+/// sprite's top left. it usually only covers the sprite's base, so characters still pass in
+/// front of and behind it, and are drawn over or under it as they should be.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[storage(VecStorage)]
+pub struct Collider {
+    pub offset: (i64, i64),
+    pub size: (i64, i64),
+}
+
+impl Collider {
+    /// a character's feet.
+    pub const FEET: Collider = Collider {
+        offset: (3, 4),
+        size: (4, 1),
+    };
+
+    /// along the bottom row of a sprite of 'size' cells.
+    pub fn base(size: (i64, i64)) -> Collider {
+        Collider {
+            offset: (0, size.1 - 1),
+            size: (size.0, 1),
+        }
+    }
+
+    /// the box covered with the sprite at 'pos', as (x, y, width, height).
+    pub fn at(&self, pos: (i64, i64)) -> (i64, i64, i64, i64) {
+        (
+            pos.0 + self.offset.0,
+            pos.1 + self.offset.1,
+            self.size.0,
+            self.size.1,
+        )
+    }
+}
+
+/// specs component for teleporter pads. pads are linked in the order they're crafted: the first
+/// with the second, the third with the fourth, and so on. 'link' is shared by both pads of a pair.
+#[derive(Component, Debug)]
+#[storage(VecStorage)]
+pub struct Teleporter {
+    pub link: usize,
+}
 
 #[derive(Component, Debug)]
 #[storage(VecStorage)]
 pub struct Npc {
     pub move_target: (i64, i64),
+    /// the spots left to walk through on the way to 'move_target', the next one first.
+    pub path: Vec<(i64, i64)>,
     pub last_move: u64,
     pub move_wait: u64,
     pub move_stop: u64,
+    /// the bubble shown overhead, and when the npc was last greeted and hugged.
+    pub emote: Option<Emote>,
+    pub greeted: Option<u64>,
+    pub hugged: Option<u64>,
+}
+
+impl Npc {
+    /// an npc wandering toward 'move_target', starting at 'time'.
+    pub fn new(move_target: (i64, i64), time: u64) -> Npc {
+        Npc {
+            move_target,
+            path: vec![],
+            last_move: time,
+            move_wait: 200,
+            move_stop: 2000,
+            emote: None,
+            greeted: None,
+            hugged: None,
+        }
+    }
+
+    /// whether K's heart meter shows over this npc, for a while after being greeted.
+    pub fn shows_hearts(&self, time: u64) -> bool {
+        self.greeted.is_some_and(|t| time < t + HEART_TIME)
+    }
+}
+
+/// what an npc's bubble shows, in the order of the emote sheet's frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Emote {
+    /// a message is waiting at the terminal.
+    Alert,
+    /// being carried around.
+    Question,
+    /// just hugged.
+    Heart,
+    /// resting between walks.
+    Sleep,
 }
 
+/// milliseconds a hug's heart and the heart meter stay up, and an npc rests before dozing off.
+pub const HEART_TIME: u64 = 2000;
+pub const DOZE_TIME: u64 = 1000;
+
 /// specs component for interactive items.
 #[derive(Component, Debug)]
 #[storage(VecStorage)]
 pub struct Interactible {
     pub item_type: ItemType,
+    /// holding the use key down with this tool in hand keeps using it a step further along.
     pub hold_to_use: bool,
 }
 
+/// real milliseconds within which another use counts as the use key held down, auto-repeating.
+pub const HOLD_TIME: u64 = 200;
+
+// story flags, set by replies at the terminal and by what the player does
+
+/// replies to the unauthorized crops notice.
+pub const PROMISED: &str = "promised";
+pub const REFUSED: &str = "refused";
+/// what the player did about it: dug up every flower crop, or kept growing them.
+pub const COMPLIED: &str = "complied";
+pub const DEFIED: &str = "defied";
+/// the ending reached, one of ending::Ending.
+pub const ENDING_REBEL: &str = "ending-rebel";
+pub const ENDING_BLOOM: &str = "ending-bloom";
+pub const ENDING_QUIET: &str = "ending-quiet";
+
+/// sender of the farm's automated notices.
+pub const FARM: &str = "Luna Agriculture";
+/// sender of the letters from K, which take replies.
+pub const K: &str = "K";
+
+/// expressions K's portrait can show, in the order of the portrait sheet's frames.
+pub const EXPRESSIONS: [&str; 4] = ["smile", "grin", "worried", "love"];
+
+/// take the expression tags out of a message's text, returning the text shown and the portrait
+/// frame asked for. a tag is an expression's name in brackets, like "[grin]"; the last one wins.
+pub fn portrait_tags(text: &str) -> (String, Option<usize>) {
+    let tags: Vec<String> = EXPRESSIONS.iter().map(|e| format!("[{}]", e)).collect();
+    // where each tag last appears in the text as written, before any are taken out
+    let frame = tags
+        .iter()
+        .enumerate()
+        .filter_map(|(i, tag)| text.rfind(tag.as_str()).map(|at| (at, i)))
+        .max()
+        .map(|(_, i)| i);
+    let text = tags.iter().fold(text.to_string(), |text, tag| {
+        text.replace(&format!("{} ", tag), "")
+            .replace(tag.as_str(), "")
+    });
+    (text, frame)
+}
+
+/// the kinds of seed the packet can plant, and their names in the pocket.
+pub const SEEDS: [(ItemType, &str); 2] =
+    [(ItemType::Seed, "seeds"), (ItemType::Seed2, "flower seeds")];
+/// the produce harvested crops yield, and their names in the pocket.
+pub const PRODUCE: [(ItemType, &str); 2] =
+    [(ItemType::Leaf, "leaves"), (ItemType::Flower, "flowers")];
+
+/// the most hearts K's affection for the player can reach, and the fewest for a hug.
+pub const MAX_HEARTS: u32 = 5;
+pub const HUG_HEARTS: u32 = 2;
+
+/// the longest reply that can be typed to K, in characters.
+pub const REPLY_LEN: usize = 40;
+
 /// specs resource used to store some global game state.
 #[derive(Default)]
 pub struct Game {
-    pub holding: ItemType,
     pub show_help: bool,
     pub show_transition: bool,
-    pub terminal_messages: Vec<String>,
+    /// the story, as read from the dialogue file.
+    pub dialogue: dialogue::Dialogue,
     pub show_terminal: bool,
     pub terminal_message_index: usize,
     pub terminal_read: bool,
+    /// the messages that arrived before the one showing, in the order they did.
+    pub history: Vec<usize>,
+    /// messages read so far, by index, and the older message picked in the terminal's
+    /// archive tab while it's open, by where it is in the history.
+    pub read: BTreeSet<usize>,
+    pub archive: Option<usize>,
+    /// the reply being typed to K, while the reply box is open, and the replies sent so far
+    /// with the message each answered.
+    pub reply: Option<super::ui::TextInput>,
+    pub replies: Vec<(usize, String)>,
     pub clear_screen: bool,
+    pub farm_name: String,
+    pub day: u32,
+    /// seeds left in the seed packets.
+    pub seeds: u32,
+    pub seeds2: u32,
+    /// the seeds the packet plants, when the pocket has more than one kind.
+    pub sowing: ItemType,
+    /// fiber and fertilizer in the player's pocket.
+    pub fiber: u32,
+    pub fertilizer: u32,
+    /// produce harvested from crops, in the pocket.
+    pub leaves: u32,
+    pub flowers: u32,
+    /// flowers bloomed in all, and nights slept with a message unread, for picking the ending.
+    pub blooms: u32,
+    pub ignored: u32,
+    /// K's affection for the player, in hearts: won by reading and answering their letters,
+    /// and lost by sleeping on them.
+    pub hearts: u32,
+    /// the weather forecast posted at the terminal each morning.
+    pub forecast: Option<String>,
+    /// set through a dust storm day: highlights only reach things close by, and crops don't
+    /// grow that night.
+    pub dust_storm: bool,
+    /// the tile the local player's held tool works on, and where it would be set down.
+    pub placement: Option<(i64, i64)>,
+    /// the story's flags so far, and the reply highlighted at the terminal.
+    pub flags: BTreeSet<String>,
+    pub choice: usize,
 }
 
 impl Game {
-    pub fn new(terminal_messages: Vec<String>) -> Game {
+    pub fn new(dialogue: dialogue::Dialogue) -> Game {
         Game {
-            holding: ItemType::None,
             show_help: true,
             show_transition: true,
-            terminal_messages,
+            dialogue,
             show_terminal: false,
             terminal_message_index: 0,
             terminal_read: false,
+            history: vec![],
+            read: BTreeSet::new(),
+            archive: None,
+            reply: None,
+            replies: vec![],
             clear_screen: false,
+            farm_name: "Luna".to_string(),
+            day: 1,
+            seeds: 8,
+            seeds2: 0,
+            sowing: ItemType::Seed,
+            fiber: 0,
+            fertilizer: 0,
+            leaves: 0,
+            flowers: 0,
+            blooms: 0,
+            ignored: 0,
+            hearts: 0,
+            forecast: None,
+            dust_storm: false,
+            placement: None,
+            flags: BTreeSet::new(),
+            choice: 0,
+        }
+    }
+
+    /// the terminal window title: farm, day, and whether a message is waiting.
+    pub fn title(&self) -> String {
+        let unread = if self.terminal_read {
+            ""
+        } else {
+            " (1 new message)"
+        };
+        format!("{} — Day {}{}", self.farm_name, self.day, unread)
+    }
+
+    /// the text shown while the terminal is open, with any replies to pick from, and the
+    /// morning's forecast under it. keys are shown as typed on 'layout'.
+    pub fn terminal_text(&self, layout: settings::Layout) -> String {
+        if let Some(picked) = self.archive {
+            return self.archive_text(picked, layout);
+        }
+        let mut text = if self.terminal_read {
+            format!(
+                "No new messages. | {}: archive | {}: shop",
+                layout.keys("k"),
+                layout.keys("l")
+            )
+        } else {
+            self.message_text_with_replies(layout)
+        };
+        if let Some(forecast) = &self.forecast {
+            text.push_str("\n\n");
+            text.push_str(forecast);
+        }
+        text
+    }
+
+    /// the unread message, with the reply box or the replies to pick from.
+    fn message_text_with_replies(&self, layout: settings::Layout) -> String {
+        let message = &self.dialogue.messages[self.terminal_message_index];
+        let mut text = self.message_text(self.terminal_message_index);
+        if let Some(reply) = &self.reply {
+            text.push_str(&format!("\n> {} | enter: send | esc: cancel", reply.line()));
+        } else if self.takes_reply() {
+            text.push_str(&format!("\n{}: reply", layout.keys("u")));
+        }
+        if !message.choices.is_empty() {
+            text.push('\n');
+            for (i, choice) in message.choices.iter().enumerate() {
+                let cursor = if i == self.choice { ">" } else { " " };
+                text.push_str(&format!("{}{} ", cursor, choice.label));
+            }
+            text.push_str(&format!(
+                "| {}: choose | {}: reply",
+                layout.keys("k/j"),
+                layout.keys("u")
+            ));
+        }
+        text
+    }
+
+    /// the archive tab: every older message by sender, '*' marking those never read, followed
+    /// by the one picked, counted from the first to arrive.
+    fn archive_text(&self, picked: usize, layout: settings::Layout) -> String {
+        let mut text = format!(
+            "### Archive | {}: browse | {}: back to inbox",
+            layout.keys("k/j"),
+            layout.keys("u")
+        );
+        if self.history.is_empty() {
+            text.push_str("\nNo older messages.");
+            return text;
+        }
+        for (i, &index) in self.history.iter().enumerate() {
+            let cursor = if i == picked { ">" } else { " " };
+            let unread = if self.read.contains(&index) { " " } else { "*" };
+            text.push_str(&format!(
+                "\n{}{} {}: {}",
+                cursor,
+                unread,
+                self.dialogue.messages[index].sender,
+                dialogue::Message::subject(&self.message_text(index))
+            ));
+        }
+        text.push_str("\n\n");
+        text.push_str(&self.message_text(self.history[picked]));
+        text
+    }
+
+    /// the text of message 'index' as it reads with the story so far, quoting the last reply
+    /// sent before it if it echoes one.
+    pub fn message_text(&self, index: usize) -> String {
+        let message = &self.dialogue.messages[index];
+        let (text, _) = portrait_tags(message.text(&self.flags));
+        let arrived = |i: usize| {
+            self.history
+                .iter()
+                .position(|&h| h == i)
+                .unwrap_or(self.history.len())
+        };
+        let reply = self
+            .replies
+            .iter()
+            .rev()
+            .find(|(i, _)| arrived(*i) < arrived(index));
+        match (&message.echo, reply, text.split_once('\n')) {
+            (Some(echo), Some((_, reply)), Some((banner, rest))) => {
+                let (echo, _) = portrait_tags(echo);
+                format!("{}\n{}\n{}", banner, echo.replace("{reply}", reply), rest)
+            }
+            _ => text,
+        }
+    }
+
+    /// the frame of K's portrait to show beside the terminal, while a letter from K is showing
+    /// there: the expression its tags ask for, or the first.
+    pub fn portrait(&self) -> Option<usize> {
+        let index = match self.archive {
+            Some(picked) if !self.history.is_empty() => self.history[picked],
+            Some(_) => return None,
+            None if self.terminal_read => return None,
+            None => self.terminal_message_index,
+        };
+        let message = &self.dialogue.messages[index];
+        if !self.show_terminal || message.sender != K {
+            return None;
+        }
+        Some(portrait_tags(message.text(&self.flags)).1.unwrap_or(0))
+    }
+
+    /// whether the message showing is from K and waiting on a typed reply.
+    pub fn takes_reply(&self) -> bool {
+        let message = &self.dialogue.messages[self.terminal_message_index];
+        !self.terminal_read && message.sender == K && message.choices.is_empty()
+    }
+
+    /// handle the local player's typing in the reply box. enter sends the reply, marking the
+    /// message read, and escape closes the box without sending.
+    pub fn type_reply(&mut self, input: super::InputState) {
+        use super::ui::TextEvent;
+        let Some(event) = self.reply.as_mut().and_then(|reply| reply.handle(input)) else {
+            return;
+        };
+        self.reply = None;
+        if let TextEvent::Submit(reply) = event {
+            if !reply.is_empty() {
+                self.replies.push((self.terminal_message_index, reply));
+                self.hearts = (self.hearts + 1).min(MAX_HEARTS);
+            }
+            self.read_terminal();
+        }
+    }
+
+    /// pick an older message in the archive, opening it from the inbox. 'step' is -1 for an
+    /// older message and 1 for a newer one; past the newest goes back to the inbox.
+    pub fn browse_archive(&mut self, step: isize) {
+        let newest = self.history.len() as isize - 1;
+        let picked = match self.archive {
+            Some(picked) => picked as isize + step,
+            None if step < 0 => newest,
+            None => return,
+        };
+        self.archive = if picked > newest {
+            None
+        } else {
+            let picked = picked.max(0) as usize;
+            if newest >= 0 {
+                self.read.insert(self.history[picked]);
+            }
+            Some(picked)
+        };
+    }
+
+    /// highlight the next reply, or the previous one for a negative 'step'.
+    pub fn cycle_choice(&mut self, step: isize) {
+        let count = self.dialogue.messages[self.terminal_message_index]
+            .choices
+            .len();
+        if count > 0 {
+            self.choice = (self.choice as isize + step).rem_euclid(count as isize) as usize;
+        }
+    }
+
+    /// close the terminal, marking its message read and setting the flag of the reply picked.
+    pub fn read_terminal(&mut self) {
+        let message = &self.dialogue.messages[self.terminal_message_index];
+        if let (false, Some(choice)) = (self.terminal_read, message.choices.get(self.choice)) {
+            self.flags.insert(choice.flag.clone());
+        }
+        if !self.terminal_read && message.sender == K {
+            self.hearts = (self.hearts + 1).min(MAX_HEARTS);
+        }
+        self.show_terminal = false;
+        self.terminal_read = true;
+        self.read.insert(self.terminal_message_index);
+        self.choice = 0;
+    }
+
+    /// K's heart meter: a full heart for each heart won, and an empty one for the rest.
+    pub fn heart_meter(&self) -> String {
+        let full = self.hearts.min(MAX_HEARTS) as usize;
+        format!(
+            "K {}{}",
+            "\u{2665}".repeat(full),
+            "\u{2661}".repeat(MAX_HEARTS as usize - full)
+        )
+    }
+
+    pub fn flag(&self, flag: &str) -> bool {
+        self.flags.contains(flag)
+    }
+
+    /// seeds of kind 'seed' in the pocket.
+    pub fn seed_count(&self, seed: ItemType) -> u32 {
+        match seed {
+            ItemType::Seed => self.seeds,
+            ItemType::Seed2 => self.seeds2,
+            _ => 0,
+        }
+    }
+
+    /// the seeds the packet plants: the kind picked, or any other left once those run out.
+    pub fn packet_seed(&self) -> Option<ItemType> {
+        std::iter::once(self.sowing)
+            .chain(SEEDS.iter().map(|(seed, _)| *seed))
+            .find(|&seed| self.seed_count(seed) > 0)
+    }
+
+    /// pick the next kind of seed in the pocket for the packet to plant.
+    pub fn cycle_seed(&mut self) {
+        let at = SEEDS.iter().position(|(seed, _)| *seed == self.sowing);
+        let next = (1..=SEEDS.len())
+            .map(|step| SEEDS[(at.unwrap_or(0) + step) % SEEDS.len()].0)
+            .find(|&seed| self.seed_count(seed) > 0);
+        if let Some(seed) = next {
+            self.sowing = seed;
+        }
+    }
+
+    /// the seed selector shown while holding the packet, when there's more than one kind to
+    /// plant.
+    pub fn seed_selector(&self, layout: settings::Layout) -> Option<String> {
+        let kinds: Vec<_> = SEEDS
+            .iter()
+            .filter(|(seed, _)| self.seed_count(*seed) > 0)
+            .collect();
+        if kinds.len() < 2 {
+            return None;
+        }
+        let sowing = self.packet_seed();
+        let mut text = String::new();
+        for (seed, name) in kinds {
+            let cursor = if Some(*seed) == sowing { ">" } else { " " };
+            text.push_str(&format!("{}{} {} ", cursor, name, self.seed_count(*seed)));
+        }
+        text.push_str(&format!("| {}: switch", layout.keys("c")));
+        Some(text)
+    }
+
+    /// how many of 'item_type' are in the pocket.
+    pub fn pocket_count(&self, item_type: ItemType) -> u32 {
+        match item_type {
+            ItemType::Fiber => self.fiber,
+            ItemType::Seed => self.seeds,
+            ItemType::Seed2 => self.seeds2,
+            ItemType::Leaf => self.leaves,
+            ItemType::Flower => self.flowers,
+            _ => 0,
+        }
+    }
+
+    /// the count that picking up an item adds to, for items that go in the pocket.
+    pub fn pocket(&mut self, item_type: ItemType) -> Option<&mut u32> {
+        match item_type {
+            ItemType::Fiber => Some(&mut self.fiber),
+            ItemType::Seed => Some(&mut self.seeds),
+            ItemType::Seed2 => Some(&mut self.seeds2),
+            ItemType::Leaf => Some(&mut self.leaves),
+            ItemType::Flower => Some(&mut self.flowers),
+            _ => None,
         }
     }
 
-    pub fn advance_terminal(&mut self) {
-        self.terminal_message_index += 1;
+    /// whether the message showing is the story's last, with no way on.
+    pub fn at_end(&self) -> bool {
+        self.dialogue.messages[self.terminal_message_index]
+            .next
+            .is_empty()
+    }
+
+    /// deliver message 'index' as the new unread one.
+    pub fn advance_terminal(&mut self, index: usize) {
+        self.history.push(self.terminal_message_index);
+        self.terminal_message_index = index;
         self.terminal_read = false;
+        self.choice = 0;
+    }
+}
+
+/// something that happened in the game, for feedback such as sound cues and notifications.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GameEvent {
+    Dig,
+    Water,
+    Seed,
+    Sleep,
+    Message,
+    Bloom,
+    Pickup,
+}
+
+/// specs resource holding the events of the current update. UpdateGameState clears it first,
+/// so systems running after it see everything that happened this update.
+#[derive(Default)]
+pub struct Events(pub Vec<GameEvent>);
+
+/// how many dug up grass and crops can be restored with the undo key.
+pub const UNDO_DEPTH: usize = 5;
+
+/// a grass or crop entity removed by the shovel, kept so it can be put back.
+pub struct Dug {
+    pub sprite: Sprite,
+    pub pos: Position,
+    pub layer: RenderLayer,
+    pub item_type: ItemType,
+    /// the crop's water, fertilizer and drought, as they were.
+    pub moisture: Option<Moisture>,
+    pub fertilized: bool,
+    pub thirsty: bool,
+    /// fiber or seeds dropped when it was dug up, taken back with it.
+    pub dropped: Option<(Entity, ItemType)>,
+}
+
+/// specs resource holding the most recent digs, newest last. cleared when the day ends.
+#[derive(Default)]
+pub struct Undo(pub Vec<Dug>);
+
+/// the farm is saved to this file in the working directory on quitting, and picked up from it
+/// on starting.
+pub const SAVE: &str = "save.json";
+
+/// a farm as it's saved between sessions. the ground and dome are laid out again from the seed,
+/// and everything with a sprite id is kept here, along with the story and the pocket.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SaveGame {
+    pub seed: u64,
+    pub world: (u16, u16),
+    /// the next sprite id to give out.
+    pub next_id: usize,
+    pub day: u32,
+    /// the message showing at the terminal, by id, as are the messages below, so the dialogue
+    /// file can change between sessions.
+    pub terminal_message: String,
+    pub terminal_read: bool,
+    /// the messages that arrived before the one showing.
+    pub history: Vec<String>,
+    pub read: BTreeSet<String>,
+    pub replies: Vec<(String, String)>,
+    pub flags: BTreeSet<String>,
+    pub hearts: u32,
+    pub blooms: u32,
+    pub ignored: u32,
+    pub seeds: u32,
+    pub seeds2: u32,
+    pub sowing: ItemType,
+    pub fiber: u32,
+    pub fertilizer: u32,
+    pub leaves: u32,
+    pub flowers: u32,
+    pub money: u32,
+    pub weather: (weather::Sky, weather::Sky, u32),
+    pub power: u32,
+    pub player: Option<SavedPlayer>,
+    /// the sprites in the area the players are in.
+    pub sprites: Vec<SavedSprite>,
+    pub area: super::scene::Area,
+    /// the sprites with an id in the other areas visited.
+    pub away: BTreeMap<super::scene::Area, Vec<SavedSprite>>,
+}
+
+impl SaveGame {
+    /// load the save at 'path', or None if there isn't one. a save that fails its checksum or
+    /// can't be read as a farm is AppError::Damaged, for the player to decide what to do with.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(path: &str) -> Result<Option<SaveGame>, AppError> {
+        savefile::read(path)?
+            .map(|bytes| {
+                serde_json::from_slice(&bytes).map_err(|_| AppError::Damaged(path.to_string()))
+            })
+            .transpose()
+    }
+
+    /// write the save to 'path', keeping the last few versions as backups.
+    pub fn save(&self, path: &str) -> Result<(), AppError> {
+        savefile::write(path, &serde_json::to_vec_pretty(self)?)
+    }
+}
+
+/// the local player in a save.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedPlayer {
+    pub pos: (i64, i64),
+    pub flip: bool,
+    pub holding: ItemType,
+}
+
+/// a sprite in a save, by its sheet's name, with whichever components made it a crop, tool,
+/// machine or npc.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedSprite {
+    pub id: usize,
+    pub sheet: String,
+    pub frame: usize,
+    pub flip: bool,
+    pub animating: bool,
+    pub anim_speed: f32,
+    pub hidden: bool,
+    pub sprite_type: SpriteType,
+    pub pos: (i64, i64),
+    pub layer: Option<RenderLayer>,
+    pub item: Option<(ItemType, bool)>,
+    pub machine: Option<MachineKind>,
+    pub teleporter: Option<usize>,
+    pub compost: Option<Compost>,
+    /// the robot's program, a line of text per command.
+    pub robot: Option<Vec<Option<String>>>,
+    pub npc: bool,
+    pub motion: bool,
+    pub fertilized: bool,
+    pub thirsty: bool,
+    pub collider: Option<Collider>,
+    pub moisture: Option<Moisture>,
+}
+
+/// specs component for player-controlled sprites. 'slot' selects the player's input from the
+/// Input resource; slot 0 is always the local player.
+#[derive(Component, Debug, Default)]
+#[storage(VecStorage)]
+pub struct Player {
+    pub slot: usize,
+    pub holding: ItemType,
+    /// when the player's oxygen runs out outside the dome.
+    pub air_until: u64,
+    /// the real time of the player's last use.
+    pub last_use: Option<u64>,
+    /// the id of the tool picked out with the next target key, among several in reach.
+    pub target: Option<usize>,
+    /// where a click sent the player, walking a step at a time until they get there.
+    pub walk: Option<(i64, i64)>,
+}
+
+impl Player {
+    /// the share of a full oxygen tank left at 'time'.
+    pub fn oxygen(&self, time: u64) -> f64 {
+        self.air_until.saturating_sub(time) as f64 / OXYGEN_TIME as f64
     }
 }
 
+/// milliseconds of oxygen a full tank holds outside the dome.
+pub const OXYGEN_TIME: u64 = 30_000;
+
+/// specs resource describing the habitat dome: a wall at column 'x', with everything left of it
+/// inside, and an airlock through the wall on rows 'airlock.0' up to 'airlock.1'.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct Dome {
+    pub x: i64,
+    pub airlock: (i64, i64),
+}
+
+impl Dome {
+    /// whether a player sprite at 'pos' is entirely inside the dome.
+    pub fn inside(&self, pos: (i64, i64)) -> bool {
+        pos.0 + 7 <= self.x
+    }
+
+    /// whether a player sprite can stand at 'pos': not in the wall, unless in the airlock.
+    pub fn passable(&self, pos: (i64, i64)) -> bool {
+        let feet = pos.1 + 4;
+        let in_wall = pos.0 <= self.x && self.x < pos.0 + 7;
+        !in_wall || (self.airlock.0..self.airlock.1).contains(&feet)
+    }
+}
+
+/// specs resource with where things turn up partway through a game, as placed on the map: where
+/// the player wakes up after passing out, where K wanders to, and where K's seed packet is left.
+#[derive(Default, Clone, Copy)]
+pub struct Landmarks {
+    pub wake: (i64, i64),
+    pub arrival: (i64, i64),
+    pub gift: (i64, i64),
+}
+
+/// the top left cell of the ground tile holding the cell 'pos'.
+pub fn tile_of(pos: (i64, i64)) -> (i64, i64) {
+    let (w, h) = super::ground::TILE;
+    (pos.0.div_euclid(w) * w, pos.1.div_euclid(h) * h)
+}
+
+/// count flowers in bloom: crops at a frame their sheet tags "bloom".
+pub fn blooming_flowers<D>(
+    store: &super::sprite::SpriteStore,
+    interactibles: &ReadStorage<'_, Interactible>,
+    sprites: &Storage<'_, Sprite, D>,
+) -> usize
+where
+    D: Deref<Target = MaskedStorage<Sprite>>,
+{
+    use specs::Join;
+
+    (interactibles, sprites)
+        .join()
+        .filter(|(item, sprite)| {
+            item.item_type == ItemType::Crop
+                && store[sprite.store_index].tagged(sprite.frame, "bloom")
+        })
+        .count()
+}
+
+/// whether a crop at 'frame' of sheet 'info' is fully grown, at the last frame of its dry or
+/// watered stages, and ready to harvest.
+pub fn ripe(info: &super::sprite::LoadedSprite, frame: usize) -> bool {
+    ["dry", "watered"]
+        .iter()
+        .any(|tag| info.tag(tag).is_some_and(|t| t.to == frame))
+}
+
+/// the produce harvesting a crop of sheet 'crop' yields, and the seed it gives back, for
+/// crops that yield anything.
+pub fn produce(
+    ids: &super::sprite::SpriteIds,
+    crop: super::sprite::SpriteHandle,
+) -> Option<(ItemType, ItemType)> {
+    if crop == ids.crop_leaf {
+        Some((ItemType::Leaf, ItemType::Seed))
+    } else if crop == ids.crop_flower {
+        Some((ItemType::Flower, ItemType::Seed2))
+    } else {
+        None
+    }
+}
+
+/// the bubbles over npcs' heads, as sprites to draw over everything else, where the npcs are
+/// drawn.
+pub fn emote_bubbles(
+    (store, ids): (&super::sprite::SpriteStore, &super::sprite::SpriteIds),
+    npcs: &ReadStorage<'_, Npc>,
+    sprites: &ReadStorage<'_, Sprite>,
+    positions: &ReadStorage<'_, Position>,
+    motions: &ReadStorage<'_, Motion>,
+) -> Vec<(Position, Sprite)> {
+    use specs::Join;
+
+    let store_index = ids.emote;
+    (npcs, sprites, positions, motions.maybe())
+        .join()
+        .filter_map(|(npc, sprite, pos, motion)| {
+            let frame = npc.emote? as usize;
+            let pos = drawn_pos(pos, motion);
+            let width = store[sprite.store_index].data.frames[sprite.frame]
+                .source_size
+                .w as i64;
+            let bubble = &store[store_index].data.frames[frame].source_size;
+            let pos = Position {
+                x: pos.x + (width - bubble.w as i64) / 2,
+                y: pos.y - (bubble.h as i64 + 1) / 2,
+            };
+            let sprite = Sprite {
+                id: sprite.id,
+                store_index,
+                frame,
+                ..Sprite::default()
+            };
+            Some((pos, sprite))
+        })
+        .collect()
+}
+
+/// the center of the player in 'slot' where they're drawn, for a camera to follow.
+pub fn player_focus<P, Q, R>(
+    slot: usize,
+    players: &Storage<'_, Player, P>,
+    positions: &Storage<'_, Position, Q>,
+    motions: &Storage<'_, Motion, R>,
+) -> Option<(i64, i64)>
+where
+    P: Deref<Target = MaskedStorage<Player>>,
+    Q: Deref<Target = MaskedStorage<Position>>,
+    R: Deref<Target = MaskedStorage<Motion>>,
+{
+    use specs::Join;
+
+    (players, positions, motions.maybe())
+        .join()
+        .find(|(player, _, _)| player.slot == slot)
+        .map(|(_, pos, motion)| {
+            let pos = drawn_pos(pos, motion);
+            (pos.x + 4, pos.y + 2)
+        })
+}
+
+/// how far an interactible of 'search_type' is from 'from_pos', if 'entity' is one.
+fn distance_of_type(
+    entity: Entity,
+    from_pos: (i64, i64),
+    search_type: SpriteType,
+    store: &super::sprite::SpriteStore,
+    sprites: &WriteStorage<'_, Sprite>,
+    positions: &WriteStorage<'_, Position>,
+) -> Option<i64> {
+    let (sprite, pos) = (sprites.get(entity)?, positions.get(entity)?);
+    (sprite.sprite_type == search_type)
+        .then(|| super::grid::distance(super::grid::middle(store, sprite, pos), from_pos))
+}
+
+/// how close a player's middle has to be to a tool or item to pick it up.
+const PICKUP_DISTANCE: i64 = 4;
+
+/// the interactibles of 'search_type' in reach of 'from_pos', less than PICKUP_DISTANCE
+/// away, as (id, distance, item type).
+fn in_reach_of_type(
+    from_pos: (i64, i64),
+    search_type: SpriteType,
+    grid: &super::grid::Grid,
+    store: &super::sprite::SpriteStore,
+    interactibles: &ReadStorage<'_, Interactible>,
+    sprites: &WriteStorage<'_, Sprite>,
+    positions: &WriteStorage<'_, Position>,
+) -> Vec<(usize, i64, ItemType)> {
+    grid.near(from_pos, PICKUP_DISTANCE)
+        .filter_map(|entity| {
+            let dist = distance_of_type(entity, from_pos, search_type, store, sprites, positions)?;
+            let (item, sprite) = (interactibles.get(entity)?, sprites.get(entity)?);
+            (dist < PICKUP_DISTANCE).then_some((sprite.id, dist, item.item_type))
+        })
+        .collect()
+}
+
+/// the tool or item drawn over world cell 'at', if any, as (id, sprite type).
+fn clicked_at(
+    at: (i64, i64),
+    store: &super::sprite::SpriteStore,
+    interactibles: &ReadStorage<'_, Interactible>,
+    sprites: &WriteStorage<'_, Sprite>,
+    positions: &WriteStorage<'_, Position>,
+) -> Option<(usize, SpriteType)> {
+    use specs::Join;
+
+    (interactibles, sprites, positions)
+        .join()
+        .filter(|(_, sprite, _)| {
+            !sprite.hidden && matches!(sprite.sprite_type, SpriteType::Tool | SpriteType::Item)
+        })
+        .find(|(_, sprite, pos)| {
+            let size = &store[sprite.store_index].data.frames[sprite.frame].source_size;
+            // two rows of pixels to a cell
+            let (w, h) = (size.w as i64, (size.h as i64 + 1) / 2);
+            (pos.x..pos.x + w).contains(&at.0) && (pos.y..pos.y + h).contains(&at.1)
+        })
+        .map(|(_, sprite, _)| (sprite.id, sprite.sprite_type))
+}
+
 fn nearest_of_type(
     from_pos: (i64, i64),
     search_type: SpriteType,
-    store: &Read<'_, super::sprite::SpriteStore>,
-    interactibles: &specs::Storage<
-        '_,
-        Interactible,
-        specs::shred::Fetch<'_, specs::storage::MaskedStorage<Interactible>>,
-    >,
-    sprites: &mut specs::Storage<
-        '_,
-        Sprite,
-        specs::shred::FetchMut<'_, specs::storage::MaskedStorage<Sprite>>,
-    >,
-    positions: &mut specs::Storage<
-        '_,
-        Position,
-        specs::shred::FetchMut<'_, specs::storage::MaskedStorage<Position>>,
-    >,
+    grid: &super::grid::Grid,
+    store: &super::sprite::SpriteStore,
+    interactibles: &ReadStorage<'_, Interactible>,
+    sprites: &WriteStorage<'_, Sprite>,
+    positions: &WriteStorage<'_, Position>,
 ) -> (usize, i64, ItemType) {
-    {
-        use specs::Join;
+    grid.nearest(from_pos, 100, |entity| {
+        distance_of_type(entity, from_pos, search_type, store, sprites, positions)
+    })
+    .and_then(|(entity, dist)| {
+        Some((
+            sprites.get(entity)?.id,
+            dist,
+            interactibles.get(entity)?.item_type,
+        ))
+    })
+    .unwrap_or((0, 100, ItemType::None))
+}
 
-        let mut tool_dist = 100;
-        let mut tool_type = ItemType::None;
-        let tool_id: usize = {
-            let mut nearest_id = 0;
-            for (item, sprite, pos) in (interactibles, sprites, positions).join() {
-                if sprite.sprite_type != search_type {
-                    continue;
-                }
-                let (sprite_w, sprite_h) = {
-                    let s = &store.0[sprite.store_index];
-                    (
-                        s.data.frames[0].source_size.w as i64,
-                        s.data.frames[0].source_size.h as i64,
-                    )
-                };
-                let x = pos.x + sprite_w / 2;
-                let y = pos.y + sprite_h / 4; // y coordinate space is 2 pixels per unit, so...
-                let dist = (((x - from_pos.0) as f64).powi(2) + ((y - from_pos.1) as f64).powi(2))
-                    .sqrt() as i64;
-                if dist < tool_dist {
-                    tool_dist = dist;
-                    tool_type = item.item_type;
-                    nearest_id = sprite.id;
-                }
+/// percent chance that a bare patch of dirt next to grass grows grass overnight.
+const REGROW_CHANCE: u32 = 10;
+
+/// where grass spreads overnight, so cleared fields slowly grow back. the farm is treated as the
+/// 8x4 grid it was laid out on: each grid cell without grass or crops has a chance of growing
+/// grass when a neighboring cell has some.
+fn regrow_grass(
+    interactibles: &ReadStorage<'_, Interactible>,
+    positions: &WriteStorage<'_, Position>,
+    sz: (u16, u16),
+    rng: &mut StdRng,
+) -> Vec<(i64, i64)> {
+    use rand::Rng;
+    use specs::Join;
+    use std::collections::HashSet;
+
+    let mut grass: HashSet<(i64, i64)> = HashSet::new();
+    let mut taken: HashSet<(i64, i64)> = HashSet::new();
+    for (item, pos) in (interactibles, positions).join() {
+        if item.item_type != ItemType::Grass && item.item_type != ItemType::Crop {
+            continue;
+        }
+        // crops are planted anywhere, so mark every cell they overlap
+        for x in [pos.x, pos.x + 7] {
+            for y in [pos.y, pos.y + 3] {
+                taken.insert((x.div_euclid(8), y.div_euclid(4)));
             }
-            nearest_id
-        };
-        (tool_id, tool_dist, tool_type)
+        }
+        if item.item_type == ItemType::Grass {
+            grass.insert((pos.x.div_euclid(8), pos.y.div_euclid(4)));
+        }
     }
+
+    let mut regrown = vec![];
+    for y in (0..sz.1 as i64 - 4).step_by(4) {
+        for x in (0..sz.0 as i64 - 8).step_by(8) {
+            let (cx, cy) = (x / 8, y / 4);
+            let beside_grass = [(-1, 0), (1, 0), (0, -1), (0, 1)]
+                .iter()
+                .any(|(dx, dy)| grass.contains(&(cx + dx, cy + dy)));
+            if taken.contains(&(cx, cy)) || !beside_grass || rng.gen_range(0..100) >= REGROW_CHANCE
+            {
+                continue;
+            }
+            regrown.push((x, y));
+        }
+    }
+    regrown
+}
+
+/// spawn animated grass at 'pos', drawn in 'layer'.
+fn spawn_grass(
+    entities: &Entities<'_>,
+    lazy: &LazyUpdate,
+    si: &mut SpriteIndexer,
+    (store, ids): (&super::sprite::SpriteStore, &super::sprite::SpriteIds),
+    (pos, layer): ((i64, i64), RenderLayer),
+    rng: &mut StdRng,
+) {
+    use rand::Rng;
+
+    let grass_index = ids.grass;
+    let grass_frame_count = store[grass_index].data.frames.len();
+    let e = entities.create();
+    let id = si.new_index();
+    lazy.insert(
+        e,
+        Sprite {
+            id,
+            store_index: grass_index,
+            flip: rng.gen_range(0..2) == 0,
+            frame: rng.gen_range(0..grass_frame_count),
+            animating: true,
+            anim_speed: GRASS_ANIM_SPEED,
+            sprite_type: SpriteType::Crop,
+            ..Sprite::default()
+        },
+    );
+    lazy.insert(e, Position { x: pos.0, y: pos.1 });
+    lazy.insert(e, layer);
+    lazy.insert(
+        e,
+        Interactible {
+            item_type: ItemType::Grass,
+            hold_to_use: false,
+        },
+    );
+}
+
+/// spawn an item lying on the ground at 'pos'.
+fn spawn_item(
+    entities: &Entities<'_>,
+    lazy: &LazyUpdate,
+    si: &mut SpriteIndexer,
+    (store_index, frame): (super::sprite::SpriteHandle, usize),
+    pos: (i64, i64),
+    item_type: ItemType,
+) -> Entity {
+    let e = entities.create();
+    let id = si.new_index();
+    lazy.insert(
+        e,
+        Sprite {
+            id,
+            store_index,
+            frame,
+            sprite_type: SpriteType::Item,
+            ..Sprite::default()
+        },
+    );
+    lazy.insert(e, Position { x: pos.0, y: pos.1 });
+    lazy.insert(e, RenderLayer::Tools);
+    lazy.insert(
+        e,
+        Interactible {
+            item_type,
+            hold_to_use: false,
+        },
+    );
+    e
+}
+
+/// what one player can see and reach this frame, gathered before any storage is mutated.
+struct PlayerView {
+    slot: usize,
+    pos: (i64, i64),
+    flip: bool,
+    holding: ItemType,
+    /// whether what's held is used along a row while the use key is held down.
+    hold_to_use: bool,
+    /// rows above the ground, while hopping.
+    height: i64,
+    center: (i64, i64),
+    crop_pos: (i64, i64),
+    nearest_tool: (usize, i64, ItemType),
+    /// tools close enough to pick up, nearest first.
+    tools_in_reach: Vec<(usize, i64, ItemType)>,
+    /// the tool the player picked out of those in reach, if any.
+    target: Option<usize>,
+    nearest_item: (usize, i64, ItemType),
+    nearest_crop: (usize, i64, ItemType),
+    /// whether the nearest crop is ready to harvest.
+    crop_ripe: bool,
 }
 
 /// big "update game state" specs system; for a simple game, it's ok... right?
@@ -228,15 +1694,52 @@ impl<'a> System<'a> for UpdateGameState {
     type SystemData = (
         Entities<'a>,
         Read<'a, LazyUpdate>,
-        Write<'a, Game>,
+        (
+            Write<'a, Game>,
+            Write<'a, super::shop::Shop>,
+            Read<'a, super::shop::Prices>,
+            Write<'a, super::shop::Money>,
+        ),
+        Write<'a, Events>,
+        Write<'a, Undo>,
         Write<'a, SpriteIndexer>,
-        Read<'a, super::sprite::SpriteStore>,
-        Read<'a, Time>,
-        Read<'a, super::Input>,
+        (
+            Read<'a, super::sprite::SpriteStore>,
+            Read<'a, super::sprite::SpriteIds>,
+            Read<'a, super::grid::Grid>,
+            Read<'a, super::ground::TileMap>,
+        ),
+        (Read<'a, Time>, Read<'a, RealTime>),
+        Read<'a, WorldSize>,
+        (
+            Read<'a, Dome>,
+            Read<'a, Landmarks>,
+            Write<'a, super::scene::SceneManager>,
+        ),
+        Write<'a, GameRng>,
+        Write<'a, super::Input>,
         WriteStorage<'a, Sprite>,
         WriteStorage<'a, Position>,
         WriteStorage<'a, Npc>,
+        WriteStorage<'a, Player>,
+        WriteStorage<'a, Compost>,
+        WriteStorage<'a, Fertilized>,
+        (
+            WriteStorage<'a, Thirsty>,
+            WriteStorage<'a, Moisture>,
+            ReadStorage<'a, Tint>,
+        ),
+        ReadStorage<'a, Machine>,
+        ReadStorage<'a, Teleporter>,
+        (WriteStorage<'a, Hop>, WriteStorage<'a, Falling>),
+        WriteStorage<'a, super::robot::Robot>,
         ReadStorage<'a, Interactible>,
+        (
+            WriteStorage<'a, Animation>,
+            WriteStorage<'a, AnimationController>,
+            ReadStorage<'a, Collider>,
+            ReadStorage<'a, RenderLayer>,
+        ),
     );
 
     fn run(&mut self, data: Self::SystemData) {
@@ -244,103 +1747,277 @@ impl<'a> System<'a> for UpdateGameState {
         use specs::Join;
 
         // initialize data
-        const PICKUP_DISTANCE: i64 = 4;
         const CROP_DISTANCE: i64 = 2;
-        let sz = terminal::size().unwrap();
+        const PLOT_SIZE: (i64, i64) = (8, 2);
+        const DUST_HIGHLIGHT_DISTANCE: i64 = 2;
         let (
             entities,
             lazy,
-            mut game,
+            (mut game, mut shop, prices, mut money),
+            mut events,
+            mut undo,
             mut si,
-            store,
-            time,
-            input,
+            (store, ids, grid, tile_map),
+            (time, real_time),
+            world_size,
+            (dome, landmarks, mut scene),
+            mut rng,
+            mut input,
             mut sprites,
             mut positions,
             mut npcs,
+            mut players,
+            mut composts,
+            mut fertilized,
+            (mut thirsty, mut moisture, tints),
+            machines,
+            teleporters,
+            (mut hops, mut falling),
+            mut robots,
             interactibles,
+            (mut animations, mut controllers, colliders, layers),
         ) = data;
+        let sz = (world_size.0, world_size.1);
+        let rng = &mut rng.0;
+        events.0.clear();
+        let message_index = game.terminal_message_index;
 
-        // get player position and flip values for use with items later
-        let (player_pos, player_flip) = {
-            let mut p_pos = (0, 0);
-            let mut flip = false;
-            for (sprite, pos) in (&sprites, &positions).join() {
-                if sprite.sprite_type == SpriteType::Player {
-                    p_pos = (pos.x, pos.y);
-                    flip = sprite.flip;
-                    break;
+        // clicks stand in for the keys they mean: a tool or item in reach is picked up, and
+        // anywhere else is walked to, a step at a time until another key is pressed. clicks
+        // are ignored at the terminal, the robot's console and the reply box
+        let consoles: Vec<usize> = (&robots)
+            .join()
+            .filter_map(|robot| robot.console.map(|(slot, _)| slot))
+            .collect();
+        for (player, pos) in (&mut players, &positions).join() {
+            let Some(command) = input.0.get_mut(player.slot) else {
+                continue;
+            };
+            let busy = game.show_terminal
+                || (player.slot == 0 && game.reply.is_some())
+                || consoles.contains(&player.slot);
+            if let InputState::Click(x, y) = *command {
+                *command = InputState::None;
+                player.walk = None;
+                if busy {
+                    continue;
+                }
+                let center = (pos.x + 4, pos.y + 2);
+                let in_reach = |id, search_type| {
+                    in_reach_of_type(
+                        center,
+                        search_type,
+                        &grid,
+                        &store,
+                        &interactibles,
+                        &sprites,
+                        &positions,
+                    )
+                    .iter()
+                    .any(|&(i, _, _)| i == id)
+                };
+                match clicked_at((x, y), &store, &interactibles, &sprites, &positions) {
+                    Some((id, SpriteType::Item)) if in_reach(id, SpriteType::Item) => {
+                        *command = InputState::Pickup;
+                    }
+                    Some((id, SpriteType::Tool)) if in_reach(id, SpriteType::Tool) => {
+                        player.target = Some(id);
+                        *command = InputState::Pickup;
+                    }
+                    // the click is where the player's middle should end up
+                    _ => {
+                        player.walk = Some((
+                            (x - 4).clamp(0, sz.0 as i64 - 10),
+                            (y - 2).clamp(-2, sz.1 as i64 - 5),
+                        ));
+                    }
                 }
+            } else if *command != InputState::None {
+                player.walk = None;
             }
-            (p_pos, flip)
-        };
-        let player_center = (player_pos.0 + 4, player_pos.1 + 2);
+            let Some(to) = player
+                .walk
+                .filter(|_| !busy && *command == InputState::None)
+            else {
+                continue;
+            };
+            let step = (to.0 - pos.x, to.1 - pos.y);
+            *command = match step {
+                (dx, _) if dx <= -2 => InputState::Left,
+                (dx, _) if dx >= 2 => InputState::Right,
+                (_, dy) if dy < 0 => InputState::Up,
+                (_, dy) if dy > 0 => InputState::Down,
+                _ => {
+                    player.walk = None;
+                    InputState::None
+                }
+            };
+        }
 
-        // find nearest tool and its distance
-        let (nearest_tool_id, nearest_tool_dist, nearest_tool_type) = nearest_of_type(
-            player_center,
-            SpriteType::Tool,
-            &store,
-            &interactibles,
-            &mut sprites,
-            &mut positions,
-        );
+        // screen-level commands only come from the local player
+        match input.slot(0) {
+            InputState::ToggleHelp => game.show_help = !game.show_help,
+            InputState::Clear => game.clear_screen = true,
+            _ => game.clear_screen = false,
+        }
 
-        // offset from player from which to operate on crops
-        let crop_pos = {
-            let x = if player_flip { -9 } else { 0 };
-            (player_center.0 + x, player_center.1)
-        };
+        // get each player's position, flip, and nearby items for use with items later
+        let mut views: Vec<PlayerView> = vec![];
+        for (player, sprite, pos, hop) in (&players, &sprites, &positions, (&hops).maybe()).join() {
+            let center = (pos.x + 4, pos.y + 2);
 
-        // find nearest crop id (includes grass)
-        let (nearest_crop_id, nearest_crop_dist, _) = nearest_of_type(
-            (crop_pos.0 + 4, crop_pos.1 + 2),
-            SpriteType::Crop,
-            &store,
-            &interactibles,
-            &mut sprites,
-            &mut positions,
-        );
+            // the tile in front of the player's feet, where tools work on crops
+            let crop_pos = {
+                let x = if sprite.flip { -1 } else { 7 };
+                tile_of((pos.x + x, pos.y + 4))
+            };
 
-        for (item, sprite, pos) in (&interactibles, &mut sprites, &mut positions).join() {
-            sprite.highlight = false;
+            views.push(PlayerView {
+                slot: player.slot,
+                pos: (pos.x, pos.y),
+                flip: sprite.flip,
+                holding: player.holding,
+                hold_to_use: player.holding != ItemType::None
+                    && interactibles
+                        .join()
+                        .any(|item| item.item_type == player.holding && item.hold_to_use),
+                height: hop.map_or(0, |hop| hop.height(time.0)),
+                center,
+                crop_pos,
+                nearest_tool: (0, 100, ItemType::None),
+                tools_in_reach: vec![],
+                target: player.target,
+                nearest_item: (0, 100, ItemType::None),
+                nearest_crop: (0, 100, ItemType::None),
+                crop_ripe: false,
+            });
+        }
+        for view in views.iter_mut() {
+            // find nearest tool and its distance
+            view.nearest_tool = nearest_of_type(
+                view.center,
+                SpriteType::Tool,
+                &grid,
+                &store,
+                &interactibles,
+                &sprites,
+                &positions,
+            );
+
+            // a tool picked out among several in reach stands in for the nearest one, so the
+            // highlight and the next pickup or use follow it
+            let mut tools = in_reach_of_type(
+                view.center,
+                SpriteType::Tool,
+                &grid,
+                &store,
+                &interactibles,
+                &sprites,
+                &positions,
+            );
+            tools.sort_by_key(|(id, dist, _)| (*dist, *id));
+            if let Some(target) = tools.iter().find(|t| Some(t.0) == view.target) {
+                view.nearest_tool = *target;
+            }
+            view.tools_in_reach = tools;
+
+            // find nearest pocketable item, which can be picked up while holding a tool
+            view.nearest_item = nearest_of_type(
+                view.center,
+                SpriteType::Item,
+                &grid,
+                &store,
+                &interactibles,
+                &sprites,
+                &positions,
+            );
+
+            // find nearest crop id (includes grass)
+            view.nearest_crop = nearest_of_type(
+                (view.crop_pos.0 + 4, view.crop_pos.1 + 2),
+                SpriteType::Crop,
+                &grid,
+                &store,
+                &interactibles,
+                &sprites,
+                &positions,
+            );
+            view.crop_ripe = view.nearest_crop.2 == ItemType::Crop
+                && sprites.join().any(|s| {
+                    s.id == view.nearest_crop.0
+                        && produce(&ids, s.store_index).is_some()
+                        && ripe(&store[s.store_index], s.frame)
+                });
+        }
+
+        game.placement = views
+            .iter()
+            .find(|v| v.slot == 0 && v.holding != ItemType::None)
+            .map(|v| v.crop_pos);
+
+        // sprites and positions are only written when they change, so the renderer can tell
+        for (item, mut sprite_entry, mut pos_entry) in (
+            &interactibles,
+            &mut sprites.restrict_mut(),
+            &mut positions.restrict_mut(),
+        )
+            .join()
+        {
+            if sprite_entry.get_unchecked().highlight {
+                sprite_entry.get_mut_unchecked().highlight = false;
+            }
             // control position of held items... this is extremely hacky; would be easier
             // to store an offset per interactible component. easy rewrite, but not vital.
-            if game.holding == item.item_type {
-                sprite.flip = player_flip;
-                let wide_offset =
-                    if item.item_type == ItemType::Pod || item.item_type == ItemType::Npc {
-                        -3
-                    } else {
-                        0
-                    };
-                let action_offset = if input.0 == InputState::Action { 1 } else { 0 };
+            if let Some(view) = views.iter().find(|v| v.holding == item.item_type) {
+                let (sprite, pos) = (
+                    sprite_entry.get_mut_unchecked(),
+                    pos_entry.get_mut_unchecked(),
+                );
+                let holding = view.holding;
+                sprite.flip = view.flip;
+                let wide_offset = if holding == ItemType::Pod || holding == ItemType::Npc {
+                    -3
+                } else {
+                    0
+                };
+                let action_offset = if input.slot(view.slot) == InputState::Action {
+                    1
+                } else {
+                    0
+                };
                 let mut small_offset = 0;
-                if game.holding == ItemType::Watercan || game.holding == ItemType::Terminal {
+                if holding == ItemType::Watercan || holding == ItemType::Terminal {
                     small_offset = 1;
-                } else if game.holding == ItemType::Packet || game.holding == ItemType::Packet2 {
+                } else if holding == ItemType::Packet || holding == ItemType::Packet2 {
                     small_offset = 2;
                 }
 
-                pos.y = player_pos.1 + small_offset + action_offset + wide_offset;
+                pos.y = view.pos.1 + small_offset + action_offset + wide_offset - view.height;
                 if pos.y < -2 {
                     pos.y = -2;
                 }
 
-                let flip_offset = if player_flip { -3 } else { 5 };
-                pos.x = player_pos.0 + flip_offset + wide_offset;
+                let flip_offset = if view.flip { -3 } else { 5 };
+                pos.x = view.pos.0 + flip_offset + wide_offset;
                 if pos.x < 0 {
                     pos.x = 0;
                 }
-                break;
+                continue;
             }
 
-            // maintain or enable highlighting of nearby tool
-            if game.holding == ItemType::None
-                && nearest_tool_id == sprite.id
-                && nearest_tool_dist < PICKUP_DISTANCE
-            {
-                sprite.highlight = true;
+            // maintain or enable highlighting of nearby tool or item, which a dust storm makes
+            // harder to make out
+            let reach = if game.dust_storm {
+                DUST_HIGHLIGHT_DISTANCE
+            } else {
+                PICKUP_DISTANCE
+            };
+            let id = sprite_entry.get_unchecked().id;
+            if views.iter().any(|v| {
+                (v.holding == ItemType::None && v.nearest_tool.0 == id && v.nearest_tool.1 < reach)
+                    || (v.nearest_item.0 == id && v.nearest_item.1 < reach)
+            }) {
+                sprite_entry.get_mut_unchecked().highlight = true;
             }
         }
 
@@ -352,28 +2029,66 @@ impl<'a> System<'a> for UpdateGameState {
             Seed,
             Seed2,
             Grow,
-            None,
+            Collect,
+            Compost,
+            Fertilize,
+            Harvest,
+            Greet,
         }
         struct SpriteAction {
             id: usize,
             action: SpriteActionCommand,
         }
-        let mut sprite_action: SpriteAction = SpriteAction {
-            id: 0,
-            action: SpriteActionCommand::None,
-        };
+        let mut sprite_actions: Vec<SpriteAction> = vec![];
 
-        // make any NPC walk around randomly
-        for (npc, pos, sprite) in (&mut npcs, &mut positions, &mut sprites).join() {
-            if game.holding == ItemType::Npc {
-                sprite.animating = false;
-                sprite.frame = 1;
+        // make any NPC walk around randomly, finding their way around planted crops, tools,
+        // machines, water and the dome wall
+        let npc_held = views.iter().any(|v| v.holding == ItemType::Npc);
+        let walkable = super::pathfinding::Walkable::new(
+            (sz.0, sz.1),
+            &dome,
+            (&tile_map, ids.tile_water),
+            (&interactibles, &sprites, &positions)
+                .join()
+                .filter(|(item, sprite, _)| {
+                    item.item_type == ItemType::Crop
+                        || (sprite.sprite_type == SpriteType::Tool
+                            && item.item_type != ItemType::Npc
+                            && !views.iter().any(|v| v.holding == item.item_type))
+                })
+                .map(|(_, sprite, pos)| {
+                    let size = &store[sprite.store_index].data.frames[sprite.frame].source_size;
+                    (pos.x, pos.y, size.w as i64, size.h as i64 / 2)
+                }),
+        );
+        for (npc, pos, sprite, controller) in
+            (&mut npcs, &mut positions, &mut sprites, &mut controllers).join()
+        {
+            if npc_held {
+                controller.set(AnimState::Held, time.0);
                 continue;
             }
-            let (target_x, target_y) = npc.move_target;
             if npc.last_move + npc.move_wait < time.0 {
+                // find the way again when there's none yet, or something's been put in it. a
+                // target there's no way to is given up on
+                use super::pathfinding::{feet, standing};
+                let here = feet((pos.x, pos.y));
+                let in_the_way = npc
+                    .path
+                    .iter()
+                    .any(|at| feet(*at) != here && walkable.blocked(feet(*at)));
+                if (pos.x, pos.y) != npc.move_target && (npc.path.is_empty() || in_the_way) {
+                    npc.path = walkable
+                        .path(here, feet(npc.move_target))
+                        .map_or(vec![], |tiles| tiles.into_iter().map(standing).collect());
+                    match npc.path.last_mut() {
+                        Some(last) => *last = npc.move_target,
+                        None => npc.move_target = (pos.x, pos.y),
+                    }
+                }
+                let (target_x, target_y) = npc.path.first().copied().unwrap_or(npc.move_target);
                 if pos.x != target_x {
-                    sprite.animating = true;
+                    controller.set(AnimState::Walk, time.0);
                     if pos.x < target_x {
                         sprite.flip = false;
                         pos.x += 1;
@@ -384,7 +2099,7 @@ impl<'a> System<'a> for UpdateGameState {
                     npc.last_move = time.0;
                 }
                 if pos.y != target_y {
-                    sprite.animating = true;
+                    controller.set(AnimState::Walk, time.0);
                     if pos.y < target_y {
                         pos.y += 1;
                     } else {
@@ -392,13 +2107,14 @@ impl<'a> System<'a> for UpdateGameState {
                     }
                     npc.last_move = time.0;
                 }
-                if pos.x == target_x && pos.y == target_y {
-                    sprite.animating = false;
-                    sprite.frame = 0;
+                if (pos.x, pos.y) == (target_x, target_y) && !npc.path.is_empty() {
+                    npc.path.remove(0);
+                }
+                if (pos.x, pos.y) == npc.move_target {
+                    controller.set(AnimState::Idle, time.0);
                     if npc.last_move + npc.move_stop < time.0 {
                         // generate new move_target
                         use rand::Rng;
-                        let mut rng = rand::thread_rng();
                         let (x, y) = (rng.gen_range(8..(sz.0 - 8)), rng.gen_range(4..(sz.1 - 4)));
                         npc.move_target = (x as i64, y as i64);
                     }
@@ -406,35 +2122,236 @@ impl<'a> System<'a> for UpdateGameState {
             }
         }
 
-        // sprites with positions get looped again to animate and handle the player
-        for (sprite, pos) in (&mut sprites, &mut positions).join() {
+        // teleporter pads as (link, top left), to find where each pad leads
+        let pads: Vec<(usize, (i64, i64))> = (&teleporters, &positions)
+            .join()
+            .map(|(pad, pos)| (pad.link, (pos.x, pos.y)))
+            .collect();
+        // where plots have been dug, so a held shovel leaves room between them
+        let plots: Vec<(i64, i64)> = (&interactibles, &positions)
+            .join()
+            .filter(|(item, _)| item.item_type == ItemType::Crop)
+            .map(|(_, pos)| (pos.x, pos.y))
+            .collect();
+        let (taps, mut channels) = super::irrigation::water_cells((&machines, &positions).join());
+        let mut teleported = false;
+        let mut crafted = 0;
+        let robot_ids: Vec<(usize, Entity)> = (&entities, &sprites, &robots)
+            .join()
+            .map(|(entity, sprite, _)| (sprite.id, entity))
+            .collect();
+
+        // things a hop can't land on, as (x, y, width, height) in cells: tools and machines
+        // that aren't being held
+        let solids: Vec<(i64, i64, i64, i64)> = (&interactibles, &sprites, &positions)
+            .join()
+            .filter(|(item, sprite, _)| {
+                sprite.sprite_type == SpriteType::Tool
+                    && !views.iter().any(|v| v.holding == item.item_type)
+            })
+            .map(|(_, sprite, pos)| {
+                let size = &store[sprite.store_index].data.frames[sprite.frame].source_size;
+                (pos.x, pos.y, size.w as i64, size.h as i64 / 2)
+            })
+            .collect();
+
+        // what the players hold, with its size in cells, and whether a held thing of a size
+        // has room to be set down at a spot: on the farm, clear of the wall and of other tools
+        let held: Vec<(ItemType, (i64, i64))> = (&interactibles, &sprites)
+            .join()
+            .filter(|(item, _)| views.iter().any(|v| v.holding == item.item_type))
+            .map(|(item, sprite)| {
+                let size = &store[sprite.store_index].data.frames[sprite.frame].source_size;
+                (item.item_type, (size.w as i64, size.h as i64 / 2))
+            })
+            .collect();
+        let room = |(x, y): (i64, i64), (w, h): (i64, i64)| {
+            let in_wall = x <= dome.x
+                && dome.x < x + w
+                && !(dome.airlock.0..dome.airlock.1).contains(&(y + h - 1));
+            let on_solid = solids
+                .iter()
+                .any(|(sx, sy, sw, sh)| x < sx + sw && *sx < x + w && y < sy + sh && *sy < y + h);
+            x >= 0 && x + w <= sz.0 as i64 && !in_wall && !on_solid
+        };
+        let mut drops: Vec<(ItemType, (i64, i64))> = vec![];
+        // the boxes players can't walk into: the colliders of everything but the players
+        // themselves and what they're carrying
+        let blockers: Vec<(i64, i64, i64, i64)> =
+            (&colliders, &positions, !&players, (&interactibles).maybe())
+                .join()
+                .filter(|(_, _, _, item)| {
+                    !item.is_some_and(|item| views.iter().any(|v| v.holding == item.item_type))
+                })
+                .map(|(collider, pos, _, _)| collider.at((pos.x, pos.y)))
+                .collect();
+        let blocked = |collider: Option<&Collider>, at: (i64, i64)| {
+            collider.is_some_and(|collider| {
+                let (x, y, w, h) = collider.at(at);
+                blockers.iter().any(|(bx, by, bw, bh)| {
+                    x < bx + bw && *bx < x + w && y < by + bh && *by < y + h
+                })
+            })
+        };
+
+        // sprites with positions get looped again to animate and handle the players
+        for (entity, mut sprite_entry, mut pos_entry, player, mut animation, mut controller) in (
+            &entities,
+            &mut sprites.restrict_mut(),
+            &mut positions.restrict_mut(),
+            (&mut players).maybe(),
+            (&mut animations).maybe(),
+            (&mut controllers).maybe(),
+        )
+            .join()
+        {
             // reset overlay sprites if transition is requested
+            let sprite = sprite_entry.get_unchecked();
             if sprite.sprite_type == SpriteType::Overlay {
-                let end_frame = store.0[sprite.store_index].data.frames.len() - 1;
-                if game.show_transition {
-                    sprite.animating = true;
-                } else if sprite.frame == end_frame {
-                    sprite.animating = false;
+                let end_frame = store[sprite.store_index].data.frames.len() - 1;
+                let shown = if game.show_transition {
+                    Some(true)
+                } else {
+                    (sprite.frame == end_frame).then_some(false)
+                };
+                if let Some(shown) =
+                    shown.filter(|shown| sprite.animating != *shown || sprite.hidden == *shown)
+                {
+                    let sprite = sprite_entry.get_mut_unchecked();
+                    sprite.animating = shown;
+                    sprite.hidden = !shown;
                 }
             }
 
             // animate sprite frames by frame length in the loaded sprite metadata
-            let sprite_data = &store.0[sprite.store_index];
+            let sprite = sprite_entry.get_unchecked();
+            let sprite_data = &store[sprite.store_index];
             let frame_wait = sprite_data.data.frames[sprite.frame].duration as u64;
-            if sprite.animating && sprite.last_animate + frame_wait < time.0 {
+            // a speed of 0 holds the current frame
+            let anim_wait = (frame_wait as f32 / sprite.anim_speed) as u64;
+            if sprite.animating && sprite.last_animate.saturating_add(anim_wait) < time.0 {
+                let sprite = sprite_entry.get_mut_unchecked();
                 sprite.last_animate = time.0;
-                sprite.frame = (sprite.frame + 1) % sprite_data.data.frames.len();
+                sprite.frame = animation
+                    .as_mut()
+                    .and_then(|a| a.next(sprite.frame, sprite_data))
+                    .unwrap_or((sprite.frame + 1) % sprite_data.data.frames.len());
+            }
+
+            // below here, only player sprites are handled
+            let Some(player) = player else {
+                continue;
+            };
+            let (sprite, pos) = (
+                sprite_entry.get_mut_unchecked(),
+                pos_entry.get_mut_unchecked(),
+            );
+            let Some(view) = views.iter().find(|v| v.slot == player.slot) else {
+                continue;
+            };
+            let (nearest_tool_id, nearest_tool_dist, nearest_tool_type) = view.nearest_tool;
+            let (nearest_crop_id, nearest_crop_dist, nearest_crop_type) = view.nearest_crop;
+            let crop_pos = view.crop_pos;
+            let player_center = view.center;
+
+            // the dome refills the oxygen tank, and running out outside it means waking up back
+            // inside with the same flash as sleeping
+            if dome.inside((pos.x, pos.y)) {
+                player.air_until = time.0 + OXYGEN_TIME;
+            } else if time.0 >= player.air_until {
+                player.air_until = time.0 + OXYGEN_TIME;
+                (pos.x, pos.y) = landmarks.wake;
+                hops.remove(entity);
+                teleported = true;
+                continue;
+            }
+
+            // a hop carries the player over a tile, ignoring input until landing
+            if let Some(hop) = hops.get(entity) {
+                pos.x = hop.x(time.0);
+                if hop.landed(time.0) {
+                    hops.remove(entity);
+                }
+                continue;
+            }
+
+            // a player at the robot's console is programming it instead of moving
+            if let Some(robot) = (&mut robots)
+                .join()
+                .find(|r| r.console.is_some_and(|(slot, _)| slot == player.slot))
+            {
+                robot.edit(input.slot(player.slot), sz.1 as u32 / 4);
+                continue;
+            }
+
+            // the local player typing a reply doesn't move
+            if game.reply.is_some() && player.slot == 0 {
+                game.type_reply(input.slot(0));
+                continue;
             }
 
-            // below here, only player sprite is handled
-            if sprite.sprite_type != SpriteType::Player {
+            // a player in the terminal's shop trades instead of moving
+            if game.show_terminal
+                && shop.handle(
+                    input.slot(player.slot),
+                    (&mut game, &prices, &mut money),
+                    &mut events.0,
+                )
+            {
                 continue;
             }
 
+            // a player at a message with replies picks one instead of moving up and down
+            let replies = game.archive.is_none()
+                && !game.terminal_read
+                && !game.dialogue.messages[game.terminal_message_index]
+                    .choices
+                    .is_empty();
+            if game.show_terminal && replies {
+                match input.slot(player.slot) {
+                    InputState::Up | InputState::ShiftUp => {
+                        game.cycle_choice(-1);
+                        continue;
+                    }
+                    InputState::Down | InputState::ShiftDown => {
+                        game.cycle_choice(1);
+                        continue;
+                    }
+                    _ => {}
+                }
+            }
+
+            // a player at the terminal browses the archive instead of moving up and down, and
+            // opens the shop instead of moving right
+            if game.show_terminal {
+                match input.slot(player.slot) {
+                    InputState::Up | InputState::ShiftUp => {
+                        game.browse_archive(-1);
+                        continue;
+                    }
+                    InputState::Down | InputState::ShiftDown => {
+                        game.browse_archive(1);
+                        continue;
+                    }
+                    InputState::Right | InputState::ShiftRight => {
+                        game.archive = None;
+                        shop.picked = Some(0);
+                        continue;
+                    }
+                    _ => {}
+                }
+            }
+
             // input parsing on player
-            let mut rng = rand::thread_rng();
             let mut impulse = (0 as f64, 0 as f64);
-            match input.0 {
+            let sprinting = matches!(
+                input.slot(player.slot),
+                InputState::ShiftUp
+                    | InputState::ShiftDown
+                    | InputState::ShiftLeft
+                    | InputState::ShiftRight
+            );
+            match input.slot(player.slot) {
                 InputState::Left => {
                     impulse.0 = -2.0;
                 }
@@ -460,31 +2377,137 @@ impl<'a> System<'a> for UpdateGameState {
                     impulse.0 = 4.0;
                 }
                 InputState::Pickup => {
-                    if game.holding == ItemType::None
+                    // a tool can only be held by one player at a time
+                    let taken = views.iter().any(|v| v.holding == nearest_tool_type);
+                    let (nearest_item_id, nearest_item_dist, _) = view.nearest_item;
+                    if nearest_item_id != 0 && nearest_item_dist < PICKUP_DISTANCE {
+                        // fiber and seeds go in the pocket, whatever the player is holding
+                        sprite_actions.push(SpriteAction {
+                            id: nearest_item_id,
+                            action: SpriteActionCommand::Collect,
+                        });
+                        events.0.push(GameEvent::Pickup);
+                    } else if player.holding == ItemType::None
                         && nearest_tool_type != ItemType::None
                         && nearest_tool_dist < PICKUP_DISTANCE
+                        && !taken
                     {
-                        game.holding = nearest_tool_type;
-                    } else if game.holding != ItemType::None {
-                        game.holding = ItemType::None;
+                        player.holding = nearest_tool_type;
+                        player.target = None;
+                        events.0.push(GameEvent::Pickup);
+                    } else if player.holding != ItemType::None {
+                        // set it down in the tile in front of the player, or the one at their
+                        // feet if something's in the way there, standing at the bottom middle
+                        let size = held
+                            .iter()
+                            .find(|(item_type, _)| *item_type == player.holding)
+                            .map_or((1, 1), |(_, size)| *size);
+                        let (w, h) = super::ground::TILE;
+                        let spot = [crop_pos, tile_of((pos.x + 3, pos.y + 4))]
+                            .into_iter()
+                            .map(|(x, y)| (x + (w - size.0) / 2, y + h - size.1))
+                            .find(|spot| room(*spot, size));
+                        if let Some(spot) = spot {
+                            drops.push((player.holding, spot));
+                            player.holding = ItemType::None;
+                        }
                     }
                 }
                 InputState::Action => 'action: {
-                    if game.holding == ItemType::None && nearest_tool_type == ItemType::Pod {
-                        sprite_action = SpriteAction {
+                    // standing on a linked pad jumps to the other pad of its pair
+                    let feet = (pos.x + 3, pos.y + 4);
+                    let on_pad = pads.iter().find(|(_, (x, y))| {
+                        (*x..x + 8).contains(&feet.0) && (*y..y + 2).contains(&feet.1)
+                    });
+                    if let Some((_, to)) = on_pad.and_then(|(link, from)| {
+                        pads.iter().find(|(l, other)| l == link && other != from)
+                    }) {
+                        pos.x = to.0;
+                        pos.y = to.1 - 3;
+                        teleported = true;
+                        break 'action;
+                    }
+
+                    // a use that comes right after the last, as a held key repeats, also steps
+                    // the way the player faces, so a held watering can waters down a row
+                    let held = player
+                        .last_use
+                        .is_some_and(|last| real_time.0 < last + HOLD_TIME);
+                    player.last_use = Some(real_time.0);
+                    if let Some(controller) = controller.as_mut() {
+                        controller.set(AnimState::Use, time.0);
+                    }
+                    if held && view.hold_to_use {
+                        impulse.0 = if sprite.flip { -2.0 } else { 2.0 };
+                    }
+
+                    if player.holding == ItemType::None
+                        && view.crop_ripe
+                        && nearest_crop_dist < CROP_DISTANCE
+                    {
+                        // a fully grown crop is picked with empty hands
+                        sprite_actions.push(SpriteAction {
+                            id: nearest_crop_id,
+                            action: SpriteActionCommand::Harvest,
+                        });
+                    } else if player.holding == ItemType::None
+                        && game.fertilizer > 0
+                        && nearest_crop_type == ItemType::Crop
+                        && nearest_crop_dist < CROP_DISTANCE
+                    {
+                        sprite_actions.push(SpriteAction {
+                            id: nearest_crop_id,
+                            action: SpriteActionCommand::Fertilize,
+                        });
+                    } else if player.holding == ItemType::None && nearest_tool_type == ItemType::Pod
+                    {
+                        sprite_actions.push(SpriteAction {
                             id: 0,
                             action: SpriteActionCommand::Grow,
-                        }
-                    } else if game.holding == ItemType::None
+                        });
+                    } else if player.holding == ItemType::None
                         && nearest_tool_type == ItemType::Terminal
                     {
-                        if game.show_terminal {
-                            game.show_terminal = false;
-                            game.terminal_read = true;
+                        if game.archive.is_some() {
+                            game.archive = None;
+                        } else if game.show_terminal && game.takes_reply() {
+                            game.reply = Some(super::ui::TextInput::new(REPLY_LEN));
+                        } else if game.show_terminal {
+                            game.read_terminal();
                         } else if nearest_tool_dist <= PICKUP_DISTANCE {
                             game.show_terminal = true;
+                            shop.picked = None;
+                        }
+                    } else if player.holding == ItemType::None
+                        && nearest_tool_type == ItemType::Compost
+                        && nearest_tool_dist <= PICKUP_DISTANCE
+                    {
+                        sprite_actions.push(SpriteAction {
+                            id: nearest_tool_id,
+                            action: SpriteActionCommand::Compost,
+                        });
+                    } else if player.holding == ItemType::None
+                        && nearest_tool_type == ItemType::Robot
+                        && nearest_tool_dist <= PICKUP_DISTANCE
+                    {
+                        let robot = robot_ids
+                            .iter()
+                            .find(|(id, _)| *id == nearest_tool_id)
+                            .and_then(|(_, entity)| robots.get_mut(*entity));
+                        if let Some(robot) = robot {
+                            robot.console = Some((player.slot, 0));
+                        }
+                    } else if player.holding == ItemType::Shovel {
+                        // a held shovel clears grass and breaks new ground a plot along from the
+                        // last, instead of digging up what it just dug
+                        let crowded = plots.iter().any(|(x, y)| {
+                            (x - crop_pos.0).abs() < PLOT_SIZE.0
+                                && (y - crop_pos.1).abs() < PLOT_SIZE.1
+                        });
+                        if held && crowded {
+                            break 'action;
                         }
-                    } else if game.holding == ItemType::Shovel {
+                        events.0.push(GameEvent::Dig);
                         // spawn a dirt particle
                         let e = entities.create();
                         let id = si.new_index();
@@ -492,9 +2515,7 @@ impl<'a> System<'a> for UpdateGameState {
                             e,
                             Sprite {
                                 id,
-                                store_index: store
-                                    .index_by_name("particle-dirt")
-                                    .expect("store index runtime error"),
+                                store_index: ids.particle_dirt,
                                 sprite_type: SpriteType::Particle,
                                 animating: true,
                                 ..Sprite::default()
@@ -505,16 +2526,16 @@ impl<'a> System<'a> for UpdateGameState {
                             Position {
                                 x: crop_pos.0,
                                 y: crop_pos.1,
-                                z: DEPTHS.overlay + id as i64,
                             },
                         );
+                        lazy.insert(e, RenderLayer::Overlay);
 
                         // find nearest grass or crop to dig up
                         if nearest_crop_dist < CROP_DISTANCE {
-                            sprite_action = SpriteAction {
+                            sprite_actions.push(SpriteAction {
                                 id: nearest_crop_id,
                                 action: SpriteActionCommand::Delete,
-                            };
+                            });
                             break 'action;
                         }
 
@@ -526,9 +2547,7 @@ impl<'a> System<'a> for UpdateGameState {
                             e,
                             Sprite {
                                 id,
-                                store_index: store
-                                    .index_by_name("crop-empty")
-                                    .expect("store index runtime error"),
+                                store_index: ids.crop_empty,
                                 flip: rng.gen_range(0..2) == 0,
                                 sprite_type: SpriteType::Crop,
                                 ..Sprite::default()
@@ -539,9 +2558,9 @@ impl<'a> System<'a> for UpdateGameState {
                             Position {
                                 x: crop_pos.0,
                                 y: crop_pos.1,
-                                z: DEPTHS.crops + id as i64,
                             },
                         );
+                        lazy.insert(e, RenderLayer::Crops);
                         lazy.insert(
                             e,
                             Interactible {
@@ -549,7 +2568,8 @@ impl<'a> System<'a> for UpdateGameState {
                                 hold_to_use: false,
                             },
                         );
-                    } else if game.holding == ItemType::Watercan {
+                    } else if player.holding == ItemType::Watercan {
+                        events.0.push(GameEvent::Water);
                         // spawn a water particle
                         let e = entities.create();
                         let id = si.new_index();
@@ -557,9 +2577,7 @@ impl<'a> System<'a> for UpdateGameState {
                             e,
                             Sprite {
                                 id,
-                                store_index: store
-                                    .index_by_name("particle-water")
-                                    .expect("store index runtime error"),
+                                store_index: ids.particle_water,
                                 sprite_type: SpriteType::Particle,
                                 animating: true,
                                 ..Sprite::default()
@@ -570,78 +2588,228 @@ impl<'a> System<'a> for UpdateGameState {
                             Position {
                                 x: crop_pos.0,
                                 y: crop_pos.1,
-                                z: DEPTHS.overlay + id as i64,
                             },
                         );
+                        lazy.insert(e, RenderLayer::Overlay);
                         if nearest_crop_dist < CROP_DISTANCE {
-                            sprite_action = SpriteAction {
+                            sprite_actions.push(SpriteAction {
                                 id: nearest_crop_id,
                                 action: SpriteActionCommand::Water,
-                            };
+                            });
                         }
-                    } else if game.holding == ItemType::Packet {
-                        if nearest_crop_dist < CROP_DISTANCE {
-                            sprite_action = SpriteAction {
+                    } else if player.holding == ItemType::Packet {
+                        // the packet plants whichever seeds are picked in the selector
+                        let seed = game.packet_seed();
+                        if let (true, Some(seed)) = (nearest_crop_dist < CROP_DISTANCE, seed) {
+                            events.0.push(GameEvent::Seed);
+                            sprite_actions.push(SpriteAction {
                                 id: nearest_crop_id,
-                                action: SpriteActionCommand::Seed,
-                            };
+                                action: if seed == ItemType::Seed2 {
+                                    SpriteActionCommand::Seed2
+                                } else {
+                                    SpriteActionCommand::Seed
+                                },
+                            });
                         }
-                    } else if game.holding == ItemType::Packet2 {
-                        if nearest_crop_dist < CROP_DISTANCE {
-                            sprite_action = SpriteAction {
+                    } else if player.holding == ItemType::Packet2 {
+                        if nearest_crop_dist < CROP_DISTANCE && game.seeds2 > 0 {
+                            events.0.push(GameEvent::Seed);
+                            sprite_actions.push(SpriteAction {
                                 id: nearest_crop_id,
                                 action: SpriteActionCommand::Seed2,
-                            };
+                            });
                         }
-                    } else if ((game.holding == ItemType::None) || (game.holding == ItemType::Npc))
+                    } else if ((player.holding == ItemType::None)
+                        || (player.holding == ItemType::Npc))
                         && nearest_tool_type == ItemType::Npc
                     {
-                        // spawn a heart on the player!
+                        // greet the npc, and hug them once they're fond enough of the player
+                        sprite_actions.push(SpriteAction {
+                            id: nearest_tool_id,
+                            action: SpriteActionCommand::Greet,
+                        });
+                        if game.hearts >= HUG_HEARTS {
+                            // spawn a heart on the player!
+                            let e = entities.create();
+                            let id = si.new_index();
+                            lazy.insert(
+                                e,
+                                Sprite {
+                                    id,
+                                    store_index: ids.particle_heart,
+                                    sprite_type: SpriteType::Particle,
+                                    animating: true,
+                                    ..Sprite::default()
+                                },
+                            );
+                            lazy.insert(
+                                e,
+                                Position {
+                                    x: player_center.0,
+                                    y: player_center.1 - 2,
+                                },
+                            );
+                            lazy.insert(e, RenderLayer::Overlay);
+                        }
+                    }
+                }
+                InputState::Undo => {
+                    // put back the last grass or crop dug up, as it was
+                    if let Some(dug) = undo.0.pop() {
+                        match dug.dropped {
+                            Some((e, _)) if entities.is_alive(e) => {
+                                let _ = entities.delete(e);
+                            }
+                            Some((_, item_type)) => {
+                                if let Some(count) = game.pocket(item_type) {
+                                    *count = count.saturating_sub(1);
+                                }
+                            }
+                            None => {}
+                        }
                         let e = entities.create();
-                        let id = si.new_index();
-                        lazy.insert(
-                            e,
-                            Sprite {
-                                id,
-                                store_index: store
-                                    .index_by_name("particle-heart")
-                                    .expect("store index runtime error"),
-                                sprite_type: SpriteType::Particle,
-                                animating: true,
-                                ..Sprite::default()
-                            },
-                        );
+                        lazy.insert(e, dug.sprite);
+                        lazy.insert(e, dug.pos);
+                        lazy.insert(e, dug.layer);
                         lazy.insert(
                             e,
-                            Position {
-                                x: player_center.0,
-                                y: player_center.1 - 2,
-                                z: DEPTHS.overlay + id as i64,
+                            Interactible {
+                                item_type: dug.item_type,
+                                hold_to_use: false,
                             },
                         );
+                        if let Some(m) = dug.moisture {
+                            lazy.insert(e, m);
+                        }
+                        if dug.fertilized {
+                            lazy.insert(e, Fertilized);
+                        }
+                        if dug.thirsty {
+                            lazy.insert(e, Thirsty);
+                        }
                     }
                 }
-                InputState::ToggleHelp => {
-                    game.show_help = !game.show_help;
+                InputState::NextTarget => {
+                    // pick out the next tool in reach after the one picked now, wrapping around
+                    let tools = &view.tools_in_reach;
+                    let next = tools
+                        .iter()
+                        .position(|t| t.0 == nearest_tool_id)
+                        .map_or(0, |i| (i + 1) % tools.len());
+                    player.target = tools.get(next).map(|t| t.0);
                 }
-                InputState::Quit => {}
-                InputState::Clear => {
-                    game.clear_screen = true;
+                InputState::Hop => {
+                    // hop one tile the way the player faces, over crops but not onto tools
+                    let to = pos.x
+                        + if sprite.flip {
+                            -HOP_DISTANCE
+                        } else {
+                            HOP_DISTANCE
+                        };
+                    let feet = (to + 3, pos.y + 4);
+                    let (low, high) = (pos.x.min(to), pos.x.max(to) + 7);
+                    let over_wall = low <= dome.x && dome.x < high;
+                    let blocked = to < 0
+                        || to > sz.0 as i64 - 10
+                        || (over_wall && !dome.passable((dome.x, pos.y)))
+                        || solids.iter().any(|(x, y, w, h)| {
+                            (*x..x + w).contains(&feet.0) && (*y..y + h).contains(&feet.1)
+                        });
+                    if !blocked {
+                        if let Some(controller) = controller.as_mut() {
+                            controller.set(AnimState::Idle, time.0);
+                        }
+                        let _ = hops.insert(
+                            entity,
+                            Hop {
+                                start: time.0,
+                                from: pos.x,
+                                to,
+                            },
+                        );
+                    }
                 }
-                InputState::None => {
-                    // when the player sprite stops moving, it stops animating after this many milliseconds.
-                    if sprite.last_move + 400 < time.0 {
-                        sprite.animating = false;
-                        sprite.frame = 0;
+                InputState::Craft if player.holding == ItemType::Packet => game.cycle_seed(),
+                InputState::Craft => {
+                    // craft at the player's feet out of fiber. what gets made depends on what's
+                    // held: a sprinkler with the watering can, or a channel where it can run on
+                    // from a tap or another channel, a solar panel with the shovel, and a
+                    // teleporter pad with empty hands.
+                    let feet = super::irrigation::cell((player_center.0, pos.y + 4));
+                    let recipe = match player.holding {
+                        ItemType::Watercan
+                            if super::irrigation::can_dig(feet, &taps, &channels) =>
+                        {
+                            Some((
+                                ids.channel,
+                                super::irrigation::CHANNEL_FIBER,
+                                Some(MachineKind::Channel),
+                            ))
+                        }
+                        ItemType::Watercan => {
+                            Some((ids.sprinkler, SPRINKLER_FIBER, Some(MachineKind::Sprinkler)))
+                        }
+                        ItemType::Shovel => {
+                            Some((ids.solar_panel, PANEL_FIBER, Some(MachineKind::Solar)))
+                        }
+                        ItemType::None => Some((ids.teleporter, PAD_FIBER, None)),
+                        _ => None,
+                    };
+                    if let Some((store_index, cost, machine)) =
+                        recipe.filter(|(_, cost, _)| game.fiber >= *cost)
+                    {
+                        game.fiber -= cost;
+                        let e = entities.create();
+                        let id = si.new_index();
+                        let (x, y) = if machine == Some(MachineKind::Channel) {
+                            channels.insert(feet);
+                            let cell = super::irrigation::CELL;
+                            (feet.0 * cell.0, feet.1 * cell.1)
+                        } else {
+                            (pos.x, pos.y + 3)
+                        };
+                        lazy.insert(
+                            e,
+                            Sprite {
+                                id,
+                                store_index,
+                                animating: machine.is_none(),
+                                ..Sprite::default()
+                            },
+                        );
+                        lazy.insert(e, Position { x, y });
+                        lazy.insert(e, RenderLayer::Crops);
+                        if let Some(kind) = machine {
+                            lazy.insert(e, Machine { kind });
+                        } else {
+                            lazy.insert(
+                                e,
+                                Teleporter {
+                                    link: (pads.len() + crafted) / 2,
+                                },
+                            );
+                            crafted += 1;
+                        }
                     }
-                    game.clear_screen = false;
                 }
+                InputState::ToggleHelp
+                | InputState::CycleSpeed
+                | InputState::Mute
+                | InputState::Quit
+                | InputState::Clear
+                | InputState::Char(_)
+                | InputState::Backspace
+                | InputState::Click(..)
+                | InputState::None => {}
             }
 
             // player movement
             if impulse.0.abs() > 0.0 || impulse.1.abs() > 0.0 {
                 if game.show_terminal {
                     game.show_terminal = false;
+                    game.archive = None;
+                    game.reply = None;
+                    shop.picked = None;
                 }
 
                 if impulse.0 < 0.0 {
@@ -649,12 +2817,28 @@ impl<'a> System<'a> for UpdateGameState {
                 } else if impulse.0 > 0.0 {
                     sprite.flip = false;
                 }
-                sprite.animating = true;
+                sprite.anim_speed = if sprinting { SPRINT_ANIM_SPEED } else { 1.0 };
+                if let Some(controller) = controller.as_mut() {
+                    controller.set(AnimState::Walk, time.0);
+                }
                 if sprite.last_move + frame_wait / 2 < time.0 {
                     sprite.last_move = time.0;
+                    let from = (pos.x, pos.y);
                     pos.x += impulse.0 as i64;
                     pos.y += impulse.1 as i64;
 
+                    // walking off an edge that leads into another area goes there once this
+                    // update is over
+                    if pos.x < 0 {
+                        scene.walk_off(super::map::Edge::Left, pos.y);
+                    } else if pos.x > sz.0 as i64 - 10 {
+                        scene.walk_off(super::map::Edge::Right, pos.y);
+                    } else if pos.y < -2 {
+                        scene.walk_off(super::map::Edge::Top, pos.x);
+                    } else if pos.y > sz.1 as i64 - 5 {
+                        scene.walk_off(super::map::Edge::Bottom, pos.x);
+                    }
+
                     // clamp pos.x to 0, sz.0
                     if pos.x < 0 {
                         pos.x = 0;
@@ -668,188 +2852,177 @@ impl<'a> System<'a> for UpdateGameState {
                     } else if pos.y > sz.1 as i64 - 5 {
                         pos.y = sz.1 as i64 - 5;
                     }
+
+                    // the dome wall can only be crossed through the airlock, and neither it nor
+                    // anything with a collider can be walked through, though a player caught
+                    // in a collider can step out of it. either stops a walk
+                    let into = blocked(colliders.get(entity), (pos.x, pos.y))
+                        && !blocked(colliders.get(entity), from);
+                    if !dome.passable((pos.x, pos.y)) || into {
+                        (pos.x, pos.y) = from;
+                        player.walk = None;
+                    }
                 }
             }
         }
 
+        // characters play the tag for what the logic above has them doing
+        for (mut sprite_entry, animation, controller) in (
+            &mut sprites.restrict_mut(),
+            &mut animations,
+            &mut controllers,
+        )
+            .join()
+        {
+            let sprite = sprite_entry.get_unchecked();
+            let info = &store[sprite.store_index];
+            let (tag, animating) = controller.update(time.0, info);
+            if animation.tag != tag || sprite.animating != animating {
+                let sprite = sprite_entry.get_mut_unchecked();
+                animation.play(tag, sprite, info);
+                sprite.animating = animating;
+            }
+        }
+
+        // dropped things fall the last few rows to where they were set down
+        for (entity, item, pos) in (&entities, &interactibles, &mut positions).join() {
+            if let Some((_, (x, y))) = drops.iter().find(|(t, _)| *t == item.item_type) {
+                pos.x = *x;
+                pos.y = y - DROP_HEIGHT;
+                let _ = falling.insert(
+                    entity,
+                    Falling {
+                        start: time.0,
+                        from: pos.y,
+                        to: *y,
+                    },
+                );
+            }
+        }
+        // something picked back up before it lands is caught
+        let holding: Vec<ItemType> = (&players).join().map(|p| p.holding).collect();
+        let mut landed = vec![];
+        for (entity, item, fall, pos) in
+            (&entities, &interactibles, &falling, &mut positions).join()
+        {
+            if holding.contains(&item.item_type) {
+                landed.push(entity);
+                continue;
+            }
+            pos.y = fall.y(time.0);
+            if fall.landed(time.0) {
+                landed.push(entity);
+            }
+        }
+        for entity in landed {
+            falling.remove(entity);
+        }
+
         if game.show_transition {
             game.show_transition = false;
         }
 
         // if the command is Grow, the player slept, so animate the transition effect
         // and check game state for terminal story sequence progression
-        if sprite_action.action == SpriteActionCommand::Grow {
+        let slept = sprite_actions
+            .iter()
+            .any(|a| a.action == SpriteActionCommand::Grow);
+        if slept || teleported {
+            // flash the transition effect from its start
             game.show_transition = true;
             for (sprite, _pos) in (&mut sprites, &positions).join() {
                 if sprite.sprite_type == SpriteType::Overlay {
                     sprite.frame = 0;
                 }
             }
-            match game.terminal_message_index {
-                0 => {
-                    // introductory message progresses story once read
-                    if game.terminal_read {
-                        game.advance_terminal();
-                    }
-                }
-                1 => {
-                    // "water your crops" message progresses story once a crop has been watered
-                    for (item, sprite) in (&interactibles, &mut sprites).join() {
-                        if item.item_type == ItemType::Crop && sprite.frame > 0 {
-                            game.advance_terminal();
-                            break;
-                        }
-                    }
-                }
-                2 => {
-                    // K intro message progresses once read, and spawns Packet2
-                    if game.terminal_read {
-                        game.advance_terminal();
-                        let e = entities.create();
-                        let id = si.new_index();
-                        lazy.insert(
-                            e,
-                            Sprite {
-                                id,
-                                store_index: store
-                                    .index_by_name("tool-packet2")
-                                    .expect("store index runtime error"),
-                                sprite_type: SpriteType::Tool,
-                                ..Sprite::default()
-                            },
-                        );
-                        lazy.insert(
-                            e,
-                            Position {
-                                x: sz.0 as i64 / 2,
-                                y: sz.1 as i64 / 2,
-                                z: DEPTHS.tools + id as i64,
-                            },
-                        );
-                        lazy.insert(
-                            e,
-                            Interactible {
-                                item_type: ItemType::Packet2,
-                                hold_to_use: true,
-                            },
-                        );
-                    }
-                }
-                3 => {
-                    // if the player seeds with Packet2, the next message gets displayed
-                    for (item, sprite) in (&interactibles, &mut sprites).join() {
-                        if item.item_type == ItemType::Crop
-                            && sprite.store_index
-                                == store.index_by_name("crop-flower").expect("store error")
-                            && game.terminal_read
-                        {
-                            game.advance_terminal();
-                            break;
-                        }
-                    }
-                }
-                4 => {
-                    // if the player progresses Packet2 seeds past 2 frames, next message
-                    for (item, sprite) in (&interactibles, &mut sprites).join() {
-                        if item.item_type == ItemType::Crop
-                            && sprite.store_index
-                                == store.index_by_name("crop-flower").expect("store error")
-                            && game.terminal_read
-                            && ((sprite.frame == 2) || (sprite.frame == 5))
-                        {
-                            game.advance_terminal();
-                            break;
-                        }
-                    }
-                }
-                5 => {
-                    // progresses once read
-                    if game.terminal_read {
-                        game.advance_terminal();
-                    }
-                }
-                6 => {
-                    // progresses once a flower blooms
-                    for (item, sprite) in (&interactibles, &mut sprites).join() {
-                        if item.item_type == ItemType::Crop
-                            && sprite.store_index
-                                == store.index_by_name("crop-flower").expect("store error")
-                            && game.terminal_read
-                            && ((sprite.frame == 3) || (sprite.frame == 6))
-                        {
-                            game.advance_terminal();
-                            break;
-                        }
-                    }
+        }
+        if slept {
+            events.0.push(GameEvent::Sleep);
+            // crops grow overnight, so yesterday's digs can't be put back as they were
+            undo.0.clear();
+            game.day += 1;
+            if !game.terminal_read {
+                game.ignored += 1;
+                if game.dialogue.messages[game.terminal_message_index].sender == K {
+                    game.hearts = game.hearts.saturating_sub(1);
                 }
-                7 => {
-                    // progresses once read, and spawns NPC
-                    if game.terminal_read {
-                        game.advance_terminal();
+            }
+            for pos in regrow_grass(&interactibles, &positions, sz, rng) {
+                spawn_grass(
+                    &entities,
+                    &lazy,
+                    &mut si,
+                    (&store, &ids),
+                    (pos, RenderLayer::Grass),
+                    rng,
+                );
+            }
+            // the story moves on along the first way whose conditions hold
+            let facts = story::Facts::gather(&game, &store, &interactibles, &sprites);
+            for arrival in story::advance(&mut game, &facts) {
+                story::spawn(
+                    &entities,
+                    &lazy,
+                    &mut si,
+                    &ids,
+                    (arrival, &landmarks, time.0),
+                );
+            }
+        }
 
-                        let e = entities.create();
-                        let id = si.new_index();
-                        lazy.insert(
-                            e,
-                            Sprite {
-                                id,
-                                store_index: store
-                                    .index_by_name("character-01")
-                                    .expect("store index runtime error"),
-                                sprite_type: SpriteType::Tool,
-                                ..Sprite::default()
-                            },
-                        );
-                        lazy.insert(
-                            e,
-                            Position {
-                                x: sz.0 as i64 / 2,
-                                y: 0,
-                                z: DEPTHS.player + id as i64,
-                            },
-                        );
-                        lazy.insert(
-                            e,
-                            Interactible {
-                                item_type: ItemType::Npc,
-                                hold_to_use: false,
-                            },
-                        );
-                        lazy.insert(
-                            e,
-                            Npc {
-                                move_target: (sz.0 as i64 / 2, sz.1 as i64 / 2),
-                                last_move: time.0,
-                                move_wait: 200,
-                                move_stop: 2000,
-                            },
-                        );
-                    }
-                }
-                8 => {
-                    // progresses once read
-                    if game.terminal_read {
-                        game.advance_terminal();
-                    }
+        if game.terminal_message_index != message_index {
+            events.0.push(GameEvent::Message);
+        }
+
+        // npcs show how they're doing in a bubble overhead
+        for (npc, sprite, pos) in (&mut npcs, &sprites, &positions).join() {
+            if sprite_actions
+                .iter()
+                .any(|a| a.id == sprite.id && a.action == SpriteActionCommand::Greet)
+            {
+                npc.greeted = Some(time.0);
+                if game.hearts >= HUG_HEARTS {
+                    npc.hugged = Some(time.0);
                 }
-                _ => {}
             }
+            let resting = (pos.x, pos.y) == npc.move_target && npc.last_move + DOZE_TIME < time.0;
+            npc.emote = if npc_held {
+                Some(Emote::Question)
+            } else if npc.hugged.is_some_and(|t| time.0 < t + HEART_TIME) {
+                Some(Emote::Heart)
+            } else if !game.terminal_read {
+                Some(Emote::Alert)
+            } else if resting {
+                Some(Emote::Sleep)
+            } else {
+                None
+            };
         }
 
         // remove particles that reach the end of their animation
-        for (entity, sprite) in (&entities, &sprites).join() {
+        for mut entry in (&mut sprites.restrict_mut()).join() {
+            let sprite = entry.get_unchecked();
             if sprite.sprite_type == SpriteType::Particle {
-                let end_frame = store.0[sprite.store_index].data.frames.len() - 1;
+                let end_frame = store[sprite.store_index].data.frames.len() - 1;
                 if sprite.frame == end_frame {
-                    lazy.remove::<Sprite>(entity);
-                    lazy.remove::<Position>(entity);
+                    entry.get_mut_unchecked().delete = true;
                 }
             }
         }
 
         // perform actions - grow, water, seed, or tag crop entities for deletion
-        for (entity, item, sprite, pos) in
-            (&entities, &interactibles, &mut sprites, &positions).join()
+        let flowers = blooming_flowers(&store, &interactibles, &sprites);
+        let crop_empty = ids.crop_empty;
+        let crop_leaf = ids.crop_leaf;
+        let crop_flower = ids.crop_flower;
+        for (entity, item, sprite, pos, compost) in (
+            &entities,
+            &interactibles,
+            &mut sprites,
+            &positions,
+            (&mut composts).maybe(),
+        )
+            .join()
         {
             // if terminal is read, stop animating
             if item.item_type == ItemType::Terminal {
@@ -862,90 +3035,330 @@ impl<'a> System<'a> for UpdateGameState {
                 continue;
             }
 
-            // grow all crops that were watered
-            if sprite_action.action == SpriteActionCommand::Grow && item.item_type == ItemType::Crop
-            {
-                if sprite.frame < 4 {
+            // rot fiber overnight, and trade the pocket's fiber for any fertilizer that's ready
+            if let Some(compost) = compost {
+                if slept && compost.fiber >= FIBER_PER_FERTILIZER {
+                    compost.nights += 1;
+                    if compost.nights >= COMPOST_NIGHTS {
+                        compost.fiber -= FIBER_PER_FERTILIZER;
+                        compost.ready += 1;
+                        compost.nights = 0;
+                    }
+                }
+                if sprite_actions
+                    .iter()
+                    .any(|a| a.id == sprite.id && a.action == SpriteActionCommand::Compost)
+                {
+                    compost.fiber += std::mem::take(&mut game.fiber);
+                    game.fertilizer += std::mem::take(&mut compost.ready);
+                }
+                sprite.frame = if compost.ready > 0 {
+                    3
+                } else if compost.nights > 0 {
+                    2
+                } else if compost.fiber > 0 {
+                    1
+                } else {
+                    0
+                };
+                continue;
+            }
+
+            // crops dry out overnight unless they were watered, dying once they run out
+            if slept && item.item_type == ItemType::Crop && sprite.store_index != crop_empty {
+                let info = &store[sprite.store_index];
+                let left = moisture.get(entity).map_or(FULL_MOISTURE, |m| m.0);
+                if info.tagged(sprite.frame, "watered") {
+                    let _ = moisture.insert(entity, Moisture(FULL_MOISTURE));
+                } else if left == 0 {
+                    sprite.store_index = crop_empty;
+                    sprite.frame = 0;
+                    moisture.remove(entity);
+                    thirsty.remove(entity);
+                    fertilized.remove(entity);
                     continue;
-                } else if sprite.frame < 7 {
-                    sprite.frame = sprite.frame - 4 + 1;
-                } else if sprite.frame == 7 {
-                    // in the case of empty crops that are watered or grow to frame 7,
-                    // remove this crop and replace it with animated grass
-                    lazy.remove::<Sprite>(entity);
-                    lazy.remove::<Position>(entity);
-                    lazy.remove::<Interactible>(entity);
-
-                    use rand::Rng;
-                    let mut rng = rand::thread_rng();
-                    let e = entities.create();
-                    let id = si.new_index();
-                    let grass_frame_count = store.0[sprite.store_index].data.frames.len();
-                    lazy.insert(
-                        e,
-                        Sprite {
-                            id,
-                            store_index: store
-                                .index_by_name("grass")
-                                .expect("store index runtime error"),
-                            flip: rng.gen_range(0..2) == 0,
-                            frame: rng.gen_range(0..grass_frame_count),
-                            animating: true,
-                            sprite_type: SpriteType::Crop,
-                            ..Sprite::default()
-                        },
-                    );
-                    lazy.insert(
-                        e,
-                        Position {
-                            x: pos.x,
-                            y: pos.y,
-                            z: DEPTHS.crops + id as i64,
-                        },
-                    );
-                    lazy.insert(
-                        e,
-                        Interactible {
-                            item_type: ItemType::Grass,
-                            hold_to_use: false,
-                        },
+                } else {
+                    let _ = moisture.insert(entity, Moisture(left - 1));
+                }
+            }
+
+            // grow all crops that were watered, or planted in fertilized soil, unless a dust
+            // storm kept the sun off them all day
+            if slept && item.item_type == ItemType::Crop && !game.dust_storm {
+                // a crop still thirsty from a drought doesn't grow, and wilts if it was never
+                // watered at all
+                let info = &store[sprite.store_index];
+                if thirsty.remove(entity).is_some() {
+                    let wilted =
+                        Some(sprite.frame.saturating_sub(1)).filter(|f| info.tagged(*f, "dry"));
+                    sprite.frame = info
+                        .retag(sprite.frame, "watered", "dry")
+                        .or(wilted)
+                        .unwrap_or(sprite.frame);
+                    continue;
+                }
+                if info.tagged(sprite.frame, "dry") {
+                    if info.tagged(sprite.frame + 1, "dry")
+                        && sprite.store_index != crop_empty
+                        && fertilized.remove(entity).is_some()
+                    {
+                        sprite.frame += 1;
+                    }
+                    continue;
+                }
+                // a watered crop grows into the next stage's dry frame
+                let grown = info
+                    .retag(sprite.frame, "watered", "dry")
+                    .map(|dry| dry + 1)
+                    .filter(|f| info.tagged(*f, "dry"));
+                if let Some(grown) = grown {
+                    sprite.frame = grown;
+                } else if info.tagged(sprite.frame, "watered") {
+                    // in the case of empty crops that are watered or watered at their last
+                    // stage, remove this crop and replace it with animated grass
+                    sprite.delete = true;
+                    spawn_grass(
+                        &entities,
+                        &lazy,
+                        &mut si,
+                        (&store, &ids),
+                        ((pos.x, pos.y), RenderLayer::Crops),
+                        rng,
                     );
                 }
             }
 
+            // a crop low on water looks wilted, until it's watered
+            if item.item_type == ItemType::Crop {
+                let wilting = sprite.store_index != crop_empty
+                    && moisture.get(entity).is_some_and(|m| m.0 <= WILT_MOISTURE)
+                    && !store[sprite.store_index].tagged(sprite.frame, "watered");
+                if wilting && tints.get(entity).is_none() {
+                    lazy.insert(entity, WILTED);
+                } else if !wilting && tints.get(entity) == Some(&WILTED) {
+                    lazy.remove::<Tint>(entity);
+                }
+            }
+
             // below actions only operate on single sprites
-            if sprite_action.id != sprite.id {
+            let Some(sprite_action) = sprite_actions.iter().find(|a| a.id == sprite.id) else {
                 continue;
+            };
+
+            // a crop flashes as it's watered, seeded or fertilized, to show what the use hit
+            if matches!(
+                sprite_action.action,
+                SpriteActionCommand::Water
+                    | SpriteActionCommand::Seed
+                    | SpriteActionCommand::Seed2
+                    | SpriteActionCommand::Fertilize
+                    | SpriteActionCommand::Harvest
+            ) {
+                lazy.insert(entity, Tint::Flash { start: time.0 });
             }
 
-            if sprite_action.action == SpriteActionCommand::Water && sprite.frame < 4 {
-                sprite.frame += 4;
+            if sprite_action.action == SpriteActionCommand::Water {
+                // a thirsty crop takes a second watering once it's already wet
+                let info = &store[sprite.store_index];
+                if let Some(watered) = info.retag(sprite.frame, "dry", "watered") {
+                    sprite.frame = watered;
+                } else {
+                    thirsty.remove(entity);
+                }
             } else if sprite_action.action == SpriteActionCommand::Seed {
-                if sprite.store_index
-                    == store
-                        .index_by_name("crop-empty")
-                        .expect("store index error")
-                {
-                    sprite.frame = 0;
-                    sprite.store_index =
-                        store.index_by_name("crop-leaf").expect("store index error");
+                // players sowing together can queue more seeds than are left, so the count is
+                // checked again as each is spent
+                if let Some(left) = game.seeds.checked_sub(1) {
+                    if sprite.store_index == ids.crop_empty {
+                        game.seeds = left;
+                        sprite.frame = 0;
+                        sprite.store_index = ids.crop_leaf;
+                        moisture.remove(entity);
+                    }
                 }
             } else if sprite_action.action == SpriteActionCommand::Seed2 {
-                if sprite.store_index
-                    == store
-                        .index_by_name("crop-empty")
-                        .expect("store index error")
-                {
+                if let Some(left) = game.seeds2.checked_sub(1) {
+                    if sprite.store_index == ids.crop_empty {
+                        game.seeds2 = left;
+                        sprite.frame = 0;
+                        sprite.store_index = ids.crop_flower;
+                        moisture.remove(entity);
+                    }
+                }
+            } else if sprite_action.action == SpriteActionCommand::Collect {
+                if let Some(count) = game.pocket(item.item_type) {
+                    *count += 1;
+                }
+                let _ = entities.delete(entity);
+            } else if sprite_action.action == SpriteActionCommand::Fertilize {
+                if item.item_type == ItemType::Crop && !fertilized.contains(entity) {
+                    let _ = fertilized.insert(entity, Fertilized);
+                    game.fertilizer -= 1;
+                }
+            } else if sprite_action.action == SpriteActionCommand::Harvest {
+                // a harvested crop leaves an empty plot, and its produce and seed go in the
+                // pocket
+                let harvest = produce(&ids, sprite.store_index)
+                    .filter(|_| ripe(&store[sprite.store_index], sprite.frame));
+                if let Some((produce, seed)) = harvest {
+                    for item_type in [produce, seed] {
+                        if let Some(count) = game.pocket(item_type) {
+                            *count += 1;
+                        }
+                    }
+                    sprite.store_index = ids.crop_empty;
                     sprite.frame = 0;
-                    sprite.store_index = store
-                        .index_by_name("crop-flower")
-                        .expect("store index error");
+                    thirsty.remove(entity);
+                    events.0.push(GameEvent::Pickup);
                 }
             } else if sprite_action.action == SpriteActionCommand::Delete {
-                lazy.remove::<Sprite>(entity);
-                lazy.remove::<Position>(entity);
-                lazy.remove::<Interactible>(entity);
+                // cleared grass leaves fiber behind for the compost bin, and fully grown crops
+                // sometimes leave seeds of their own kind
+                use rand::Rng;
+                let grown =
+                    ripe(&store[sprite.store_index], sprite.frame) && rng.gen_range(0..2) == 0;
+                let drop = if item.item_type == ItemType::Grass {
+                    Some((ids.tool_fiber, 0, ItemType::Fiber))
+                } else if grown && sprite.store_index == crop_leaf {
+                    Some((ids.tool_seed, 0, ItemType::Seed))
+                } else if grown && sprite.store_index == crop_flower {
+                    Some((ids.tool_seed, 1, ItemType::Seed2))
+                } else {
+                    None
+                };
+                let dropped = drop.map(|(store_index, frame, item_type)| {
+                    let e = spawn_item(
+                        &entities,
+                        &lazy,
+                        &mut si,
+                        (store_index, frame),
+                        (pos.x + 2, pos.y + 2),
+                        item_type,
+                    );
+                    (e, item_type)
+                });
+                if undo.0.len() == UNDO_DEPTH {
+                    undo.0.remove(0);
+                }
+                undo.0.push(Dug {
+                    sprite: sprite.clone(),
+                    pos: pos.clone(),
+                    layer: layers.get(entity).copied().unwrap_or_default(),
+                    item_type: item.item_type,
+                    moisture: moisture.get(entity).copied(),
+                    fertilized: fertilized.contains(entity),
+                    thirsty: thirsty.contains(entity),
+                    dropped,
+                });
+                sprite.delete = true;
             }
         }
+
+        let bloomed = blooming_flowers(&store, &interactibles, &sprites).saturating_sub(flowers);
+        if bloomed > 0 {
+            game.blooms += bloomed as u32;
+            events.0.push(GameEvent::Bloom);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{App, InputState};
+    use super::*;
+    use specs::{Builder, Join, WorldExt};
+
+    /// plant a crop in front of the local player, holding a shovel, with everything else to
+    /// dig cleared away. returns the crop's sprite id.
+    fn crop_in_front(app: &mut App) -> usize {
+        let world = app.world_mut();
+        let diggable: Vec<Entity> = (&world.entities(), &world.read_storage::<Interactible>())
+            .join()
+            .filter(|(_, item)| matches!(item.item_type, ItemType::Grass | ItemType::Crop))
+            .map(|(e, _)| e)
+            .collect();
+        world.delete_entities(&diggable).unwrap();
+        let (pos, flip) = (
+            &mut world.write_storage::<Player>(),
+            &world.read_storage::<Position>(),
+            &world.read_storage::<Sprite>(),
+        )
+            .join()
+            .find(|(player, _, _)| player.slot == 0)
+            .map(|(player, pos, sprite)| {
+                player.holding = ItemType::Shovel;
+                ((pos.x, pos.y), sprite.flip)
+            })
+            .unwrap();
+        let at = tile_of((pos.0 + if flip { -1 } else { 7 }, pos.1 + 4));
+        let id = world.write_resource::<SpriteIndexer>().new_index();
+        let store_index = world
+            .read_resource::<super::super::sprite::SpriteIds>()
+            .crop_leaf;
+        world
+            .create_entity()
+            .with(Sprite {
+                id,
+                store_index,
+                sprite_type: SpriteType::Crop,
+                frame: 1,
+                ..Sprite::default()
+            })
+            .with(Position { x: at.0, y: at.1 })
+            .with(RenderLayer::Crops)
+            .with(Interactible {
+                item_type: ItemType::Crop,
+                hold_to_use: false,
+            })
+            .with(Moisture(2))
+            .with(Fertilized)
+            .build();
+        world.maintain();
+        id
+    }
+
+    #[test]
+    fn the_last_portrait_tag_in_the_text_wins() {
+        // "love" comes after "grin" in EXPRESSIONS, but before it in the text
+        let (text, frame) = portrait_tags("[love] hi [grin] there");
+        assert_eq!(text, "hi there");
+        assert_eq!(frame, Some(1));
+        // taking "smile" out first mustn't move "love" back before where it was
+        let (text, frame) = portrait_tags("[smile] hi [love] there");
+        assert_eq!(text, "hi there");
+        assert_eq!(frame, Some(3));
+        assert_eq!(portrait_tags("[smile] [love]").1, Some(3));
+        assert_eq!(portrait_tags("no tags"), ("no tags".to_string(), None));
+    }
+
+    #[test]
+    fn undo_puts_a_dug_up_crop_back_as_it_was() {
+        let mut app = App::with_output((80, 40), Box::new(std::io::sink()), Some(1)).unwrap();
+        let id = crop_in_front(&mut app);
+        let crop = |app: &App| {
+            let world = app.world();
+            (
+                &world.read_storage::<Sprite>(),
+                &world.read_storage::<RenderLayer>(),
+                (&world.read_storage::<Moisture>()).maybe(),
+                (&world.read_storage::<Fertilized>()).maybe(),
+            )
+                .join()
+                .find(|(sprite, _, _, _)| sprite.id == id)
+                .map(|(_, layer, moisture, fertilized)| {
+                    (*layer, moisture.copied(), fertilized.is_some())
+                })
+        };
+
+        app.step(InputState::Action, 50).unwrap();
+        app.step(InputState::None, 50).unwrap();
+        assert_eq!(crop(&app), None);
+
+        app.step(InputState::Undo, 50).unwrap();
+        app.step(InputState::None, 50).unwrap();
+        assert_eq!(
+            crop(&app),
+            Some((RenderLayer::Crops, Some(Moisture(2)), true))
+        );
     }
 }
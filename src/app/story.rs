@@ -0,0 +1,245 @@
+use super::sprite::{SpriteIds, SpriteStore};
+use super::state::{
+    Animation, AnimationController, Collider, Game, Interactible, ItemType, Landmarks, Motion, Npc,
+    Position, RenderLayer, Sprite, SpriteIndexer, SpriteType,
+};
+use serde::{Deserialize, Serialize};
+use specs::storage::MaskedStorage;
+use specs::{Entities, LazyUpdate, ReadStorage, Storage};
+use std::collections::BTreeSet;
+use std::ops::Deref;
+
+/// a way on from a message: once every condition holds at night, the effects happen and the
+/// message 'to' arrives.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Next {
+    #[serde(default)]
+    pub when: Vec<Condition>,
+    pub to: String,
+    #[serde(default)]
+    pub effects: Vec<Effect>,
+}
+
+/// something about the story or the farm a way on waits for.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    /// the message has been read.
+    Read,
+    /// a story flag is set, or isn't.
+    Flag(String),
+    NotFlag(String),
+    /// some crop on the farm is as described, or none is.
+    Crop(Crops),
+    NoCrop(Crops),
+}
+
+/// the crops a condition looks for. everything left out matches any crop.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Crops {
+    /// the crop's sheet, like "crop-flower".
+    pub sheet: Option<String>,
+    /// a frame tag of the sheet the crop's frame is in, like "bloom".
+    pub tag: Option<String>,
+    /// frames the crop is at, any of them.
+    pub frames: Vec<usize>,
+    /// the least frame the crop is at, like 1 for any crop that's been watered.
+    pub at_least: usize,
+}
+
+impl Crops {
+    fn matches(&self, crop: &Crop) -> bool {
+        self.sheet.as_ref().is_none_or(|sheet| *sheet == crop.sheet)
+            && self.tag.as_ref().is_none_or(|tag| crop.tags.contains(tag))
+            && (self.frames.is_empty() || self.frames.contains(&crop.frame))
+            && crop.frame >= self.at_least
+    }
+}
+
+impl Condition {
+    pub fn holds(&self, facts: &Facts, flags: &BTreeSet<String>) -> bool {
+        match self {
+            Condition::Read => facts.read,
+            Condition::Flag(flag) => flags.contains(flag),
+            Condition::NotFlag(flag) => !flags.contains(flag),
+            Condition::Crop(crops) => facts.crops.iter().any(|c| crops.matches(c)),
+            Condition::NoCrop(crops) => !facts.crops.iter().any(|c| crops.matches(c)),
+        }
+    }
+}
+
+/// one crop on the farm, as the conditions see it.
+#[derive(Debug, Clone)]
+pub struct Crop {
+    pub sheet: String,
+    pub frame: usize,
+    /// the sheet's frame tags the crop's frame is in.
+    pub tags: Vec<String>,
+}
+
+/// the farm as the conditions see it, gathered at night.
+#[derive(Debug, Clone, Default)]
+pub struct Facts {
+    pub read: bool,
+    pub crops: Vec<Crop>,
+}
+
+impl Facts {
+    pub fn gather<D>(
+        game: &Game,
+        store: &SpriteStore,
+        interactibles: &ReadStorage<'_, Interactible>,
+        sprites: &Storage<'_, Sprite, D>,
+    ) -> Facts
+    where
+        D: Deref<Target = MaskedStorage<Sprite>>,
+    {
+        use specs::Join;
+
+        let crops = (interactibles, sprites)
+            .join()
+            .filter(|(item, _)| item.item_type == ItemType::Crop)
+            .map(|(_, sprite)| {
+                let sheet = &store[sprite.store_index];
+                Crop {
+                    sheet: sheet.name.clone(),
+                    frame: sprite.frame,
+                    tags: sheet
+                        .data
+                        .meta
+                        .frame_tags
+                        .iter()
+                        .filter(|t| t.contains(sprite.frame))
+                        .map(|t| t.name.clone())
+                        .collect(),
+                }
+            })
+            .collect();
+        Facts {
+            read: game.terminal_read,
+            crops,
+        }
+    }
+}
+
+/// something that happens as the story moves on.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Effect {
+    /// set a story flag.
+    Flag(String),
+    /// top the pocket up to at least this many flower seeds.
+    FlowerSeeds(u32),
+    /// bring something onto the farm.
+    Spawn(Arrival),
+}
+
+/// what the story can bring onto the farm, each at its landmark.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Arrival {
+    /// K's packet of flower seeds, at the gift spot.
+    Gift,
+    /// K, walking in from the arrival spot.
+    K,
+}
+
+/// move the story on at night along the first way on from the message showing whose
+/// conditions hold, if any, returning what arrives on the farm with it.
+pub fn advance(game: &mut Game, facts: &Facts) -> Vec<Arrival> {
+    let Some(next) = game.dialogue.messages[game.terminal_message_index]
+        .next
+        .iter()
+        .find(|next| next.when.iter().all(|c| c.holds(facts, &game.flags)))
+        .cloned()
+    else {
+        return vec![];
+    };
+    let mut arrivals = vec![];
+    for effect in next.effects {
+        match effect {
+            Effect::Flag(flag) => {
+                game.flags.insert(flag);
+            }
+            Effect::FlowerSeeds(count) => game.seeds2 = game.seeds2.max(count),
+            Effect::Spawn(arrival) => arrivals.push(arrival),
+        }
+    }
+    if let Some(to) = game.dialogue.index(&next.to) {
+        game.advance_terminal(to);
+    }
+    arrivals
+}
+
+/// bring 'arrival' onto the farm at its landmark.
+pub fn spawn(
+    entities: &Entities<'_>,
+    lazy: &LazyUpdate,
+    si: &mut SpriteIndexer,
+    ids: &SpriteIds,
+    (arrival, landmarks, time): (Arrival, &Landmarks, u64),
+) {
+    let e = entities.create();
+    let id = si.new_index();
+    match arrival {
+        Arrival::Gift => {
+            lazy.insert(
+                e,
+                Sprite {
+                    id,
+                    store_index: ids.tool_packet2,
+                    sprite_type: SpriteType::Tool,
+                    ..Sprite::default()
+                },
+            );
+            lazy.insert(
+                e,
+                Position {
+                    x: landmarks.gift.0,
+                    y: landmarks.gift.1,
+                },
+            );
+            lazy.insert(e, RenderLayer::Tools);
+            lazy.insert(
+                e,
+                Interactible {
+                    item_type: ItemType::Packet2,
+                    hold_to_use: true,
+                },
+            );
+            lazy.insert(e, Motion::default());
+        }
+        Arrival::K => {
+            lazy.insert(
+                e,
+                Sprite {
+                    id,
+                    store_index: ids.character_01,
+                    sprite_type: SpriteType::Tool,
+                    ..Sprite::default()
+                },
+            );
+            lazy.insert(
+                e,
+                Position {
+                    x: landmarks.arrival.0,
+                    y: 0,
+                },
+            );
+            lazy.insert(e, RenderLayer::Player);
+            lazy.insert(
+                e,
+                Interactible {
+                    item_type: ItemType::Npc,
+                    hold_to_use: false,
+                },
+            );
+            lazy.insert(e, Npc::new(landmarks.arrival, time));
+            lazy.insert(e, Motion::default());
+            lazy.insert(e, Animation::new("idle"));
+            lazy.insert(e, AnimationController::default());
+            lazy.insert(e, Collider::FEET);
+        }
+    }
+}
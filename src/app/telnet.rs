@@ -0,0 +1,238 @@
+use super::{session, AppError};
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+
+pub const DEFAULT_PORT: u16 = 2323;
+
+/// games served at once. each runs on its own thread, so visitors past this are turned away
+/// rather than left to use up the host.
+pub const MAX_SESSIONS: usize = 16;
+
+/// the longest subnegotiation kept. NAWS needs 5 bytes, and the rest are ignored anyway.
+const MAX_SUB: usize = 16;
+
+// telnet protocol bytes used here
+const IAC: u8 = 255;
+const DONT: u8 = 254;
+const DO: u8 = 253;
+const WONT: u8 = 252;
+const WILL: u8 = 251;
+const SB: u8 = 250;
+const SE: u8 = 240;
+const ECHO: u8 = 1;
+const SUPPRESS_GO_AHEAD: u8 = 3;
+const NAWS: u8 = 31;
+
+enum ParseState {
+    Data,
+    Iac,
+    Option,
+    Sub,
+    SubIac,
+}
+
+/// strips telnet commands from incoming bytes, picking out window size (NAWS) reports.
+struct TelnetParser {
+    state: ParseState,
+    sub: Vec<u8>,
+}
+
+impl TelnetParser {
+    fn new() -> TelnetParser {
+        TelnetParser {
+            state: ParseState::Data,
+            sub: vec![],
+        }
+    }
+
+    fn feed(&mut self, bytes: &[u8], events: &mpsc::Sender<session::SessionEvent>) {
+        let mut data: Vec<u8> = vec![];
+        for &b in bytes {
+            self.state = match self.state {
+                ParseState::Data if b == IAC => ParseState::Iac,
+                ParseState::Data => {
+                    data.push(b);
+                    ParseState::Data
+                }
+                ParseState::Iac => match b {
+                    // an escaped 255 is a data byte
+                    IAC => {
+                        data.push(b);
+                        ParseState::Data
+                    }
+                    WILL | WONT | DO | DONT => ParseState::Option,
+                    SB => {
+                        self.sub.clear();
+                        ParseState::Sub
+                    }
+                    _ => ParseState::Data,
+                },
+                ParseState::Option => ParseState::Data,
+                ParseState::Sub if b == IAC => ParseState::SubIac,
+                ParseState::Sub => {
+                    if self.sub.len() < MAX_SUB {
+                        self.sub.push(b);
+                    }
+                    ParseState::Sub
+                }
+                ParseState::SubIac if b == SE => {
+                    // NAWS reports width and height as two big-endian u16s
+                    if self.sub.len() == 5 && self.sub[0] == NAWS {
+                        let w = u16::from_be_bytes([self.sub[1], self.sub[2]]);
+                        let h = u16::from_be_bytes([self.sub[3], self.sub[4]]);
+                        if w > 0 && h > 0 {
+                            let _ = events.send(session::SessionEvent::Resize(w, h));
+                        }
+                    }
+                    ParseState::Data
+                }
+                ParseState::SubIac => {
+                    if self.sub.len() < MAX_SUB {
+                        self.sub.push(b);
+                    }
+                    ParseState::Sub
+                }
+            };
+        }
+        if !data.is_empty() {
+            let _ = events.send(session::SessionEvent::Data(data));
+        }
+    }
+}
+
+fn serve_client(mut stream: TcpStream, raw: bool) -> Result<(), AppError> {
+    stream.set_nodelay(true)?;
+    if !raw {
+        // we echo nothing and want single keypresses, plus window size reports
+        stream.write_all(&[IAC, WILL, ECHO, IAC, WILL, SUPPRESS_GO_AHEAD, IAC, DO, NAWS])?;
+    }
+
+    let (tx, rx) = mpsc::channel();
+    let mut reader = stream.try_clone()?;
+    std::thread::spawn(move || {
+        let mut parser = TelnetParser::new();
+        let mut buf = [0u8; 256];
+        loop {
+            match reader.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(n) if raw => {
+                    let _ = tx.send(session::SessionEvent::Data(buf[..n].to_vec()));
+                }
+                Ok(n) => parser.feed(&buf[..n], &tx),
+            }
+        }
+    });
+
+    let result = session::run((80, 24), rx, Box::new(stream.try_clone()?));
+    let _ = stream.shutdown(std::net::Shutdown::Both);
+    result
+}
+
+/// a session's place in the count of those running, given back when it ends, even by panic.
+struct Slot(Arc<AtomicUsize>);
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// serve an independent game to every telnet connection, up to MAX_SESSIONS at once:
+/// `qffp telnet [addr] [--raw]`. '--raw' skips telnet negotiation for plain sockets such as `nc`
+/// with a terminal in raw mode.
+pub fn run(args: &[String]) -> Result<(), AppError> {
+    let raw = args.iter().any(|a| a == "--raw");
+    let default_addr = format!("127.0.0.1:{}", DEFAULT_PORT);
+    let addr = args
+        .iter()
+        .find(|a| a.as_str() != "--raw")
+        .unwrap_or(&default_addr);
+
+    let listener = TcpListener::bind(addr)?;
+    println!("serving qffp over telnet on {}", addr);
+    let sessions = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming() {
+        let Ok(mut stream) = stream else {
+            continue;
+        };
+        if sessions.fetch_add(1, Ordering::SeqCst) >= MAX_SESSIONS {
+            sessions.fetch_sub(1, Ordering::SeqCst);
+            let _ = stream.write_all(b"every farm is taken, try again later\r\n");
+            continue;
+        }
+        let slot = Slot(sessions.clone());
+        std::thread::spawn(move || {
+            let _slot = slot;
+            if let Err(e) = serve_client(stream, raw) {
+                eprintln!("telnet session ended with error: {}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// feed 'chunks' to a new parser, returning the data and window sizes it reported.
+    fn parse(chunks: &[&[u8]]) -> (Vec<u8>, Vec<(u16, u16)>) {
+        let (tx, rx) = mpsc::channel();
+        let mut parser = TelnetParser::new();
+        for chunk in chunks {
+            parser.feed(chunk, &tx);
+        }
+        drop(tx);
+        let (mut data, mut sizes) = (vec![], vec![]);
+        for event in rx {
+            match event {
+                session::SessionEvent::Data(bytes) => data.extend(bytes),
+                session::SessionEvent::Resize(w, h) => sizes.push((w, h)),
+            }
+        }
+        (data, sizes)
+    }
+
+    #[test]
+    fn passes_data_through() {
+        assert_eq!(parse(&[b"wasd", b" u"]), (b"wasd u".to_vec(), vec![]));
+    }
+
+    #[test]
+    fn strips_commands() {
+        let input = [b'a', IAC, WILL, ECHO, b'b', IAC, DO, NAWS, IAC, 241, b'c'];
+        assert_eq!(parse(&[&input]), (b"abc".to_vec(), vec![]));
+        // an escaped IAC is a data byte
+        assert_eq!(parse(&[&[IAC, IAC, b'x']]), (vec![IAC, b'x'], vec![]));
+    }
+
+    #[test]
+    fn reads_window_sizes() {
+        let naws = [IAC, SB, NAWS, 0, 120, 0, 40, IAC, SE];
+        assert_eq!(parse(&[&naws]), (vec![], vec![(120, 40)]));
+        // split across reads
+        assert_eq!(parse(&[&naws[..4], &naws[4..]]), (vec![], vec![(120, 40)]));
+        // a size of 255 is escaped inside the subnegotiation
+        let escaped = [IAC, SB, NAWS, 0, IAC, IAC, 0, 30, IAC, SE, b'q'];
+        assert_eq!(parse(&[&escaped]), (b"q".to_vec(), vec![(255, 30)]));
+        // no size at all is ignored
+        let empty = [IAC, SB, NAWS, 0, 0, 0, 0, IAC, SE];
+        assert_eq!(parse(&[&empty]), (vec![], vec![]));
+    }
+
+    #[test]
+    fn bounds_long_subnegotiations() {
+        let mut long = vec![IAC, SB, NAWS];
+        long.extend(std::iter::repeat_n(7, 10_000));
+        long.extend([IAC, SE, b'k']);
+        let mut parser = TelnetParser::new();
+        let (tx, rx) = mpsc::channel();
+        parser.feed(&long, &tx);
+        assert!(parser.sub.len() <= MAX_SUB);
+        drop(tx);
+        let events: Vec<_> = rx.into_iter().collect();
+        assert!(matches!(events.as_slice(), [session::SessionEvent::Data(d)] if d == b"k"));
+    }
+}
@@ -0,0 +1,273 @@
+use super::{display, settings, ui, AppError, Frontend, InputState};
+use crossterm::event::{poll, read, Event, KeyCode, KeyModifiers, MouseButton, MouseEventKind};
+use crossterm::{cursor, event, style, terminal, QueueableCommand};
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+pub use crossterm::terminal::size;
+
+/// the terminal this process runs in, with any pasted text still to be typed out.
+#[derive(Default)]
+pub struct LocalTerminal {
+    pasted: VecDeque<char>,
+    layout: settings::Layout,
+    /// set when the process is told to stop.
+    stop: Arc<AtomicBool>,
+}
+
+impl LocalTerminal {
+    /// a terminal that quits the game when the process is killed with SIGTERM, or gets SIGHUP
+    /// because the terminal or the ssh connection it was in went away. the game then shuts down
+    /// as if the player quit, saving what it would and putting the terminal back.
+    pub fn new(layout: settings::Layout) -> Result<LocalTerminal, AppError> {
+        let terminal = LocalTerminal {
+            layout,
+            ..LocalTerminal::default()
+        };
+        #[cfg(unix)]
+        for signal in [signal_hook::consts::SIGTERM, signal_hook::consts::SIGHUP] {
+            signal_hook::flag::register(signal, terminal.stop.clone())?;
+        }
+        Ok(terminal)
+    }
+}
+
+impl Frontend for LocalTerminal {
+    fn read_input(&mut self, ms: u64, typing: bool) -> Result<InputState, AppError> {
+        if self.stop.load(Ordering::Relaxed) {
+            return Ok(InputState::Quit);
+        }
+        // a paste is typed out a character each tick, without waiting, until it's all in or
+        // the text box closes
+        if !typing {
+            self.pasted.clear();
+        }
+        if let Some(c) = self.pasted.pop_front() {
+            return Ok(InputState::Char(c));
+        }
+        read_input(ms, typing, self.layout, &mut self.pasted)
+    }
+
+    fn size(&self) -> Result<(u16, u16), AppError> {
+        Ok(size()?)
+    }
+}
+
+/// read one key input from the terminal. 'ms' is the poll duration in milliseconds. while
+/// 'typing', keys are read as text, and text pasted in is added to 'pasted' to be typed out.
+/// otherwise letters are commands, found where they'd be on a qwerty keyboard for 'layout'.
+pub fn read_input(
+    ms: u64,
+    typing: bool,
+    layout: settings::Layout,
+    pasted: &mut VecDeque<char>,
+) -> Result<InputState, AppError> {
+    let mut input: InputState = InputState::None;
+    if poll(Duration::from_millis(ms))? {
+        match read()? {
+            Event::FocusGained => {}
+            Event::FocusLost => {}
+            Event::Key(event) => 'key: {
+                if event.kind != event::KeyEventKind::Press {
+                    input = InputState::None;
+                    break 'key;
+                }
+                let code = event.code;
+                let mods = event.modifiers;
+
+                if typing {
+                    input = match code {
+                        KeyCode::Char('c') if mods == KeyModifiers::CONTROL => InputState::Quit,
+                        KeyCode::Char(c) => InputState::Char(c),
+                        KeyCode::Backspace => InputState::Backspace,
+                        KeyCode::Left => InputState::Left,
+                        KeyCode::Right => InputState::Right,
+                        KeyCode::Enter => InputState::Action,
+                        KeyCode::Esc => InputState::Pickup,
+                        _ => InputState::None,
+                    };
+                    break 'key;
+                }
+
+                let code = match code {
+                    KeyCode::Char(c) if mods != KeyModifiers::CONTROL => {
+                        KeyCode::Char(layout.to_qwerty(c))
+                    }
+                    code => code,
+                };
+
+                // quit command
+                if (code == KeyCode::Char('c') && mods == KeyModifiers::CONTROL)
+                    || code == KeyCode::Char('q')
+                {
+                    input = InputState::Quit;
+                }
+                // movement commands
+                else if code == KeyCode::Left
+                    || code == KeyCode::Char('h')
+                    || code == KeyCode::Char('H')
+                {
+                    if mods == KeyModifiers::SHIFT {
+                        input = InputState::ShiftLeft;
+                    } else {
+                        input = InputState::Left;
+                    }
+                } else if code == KeyCode::Right
+                    || code == KeyCode::Char('l')
+                    || code == KeyCode::Char('L')
+                {
+                    if mods == KeyModifiers::SHIFT {
+                        input = InputState::ShiftRight;
+                    } else {
+                        input = InputState::Right;
+                    }
+                } else if code == KeyCode::Down
+                    || code == KeyCode::Char('j')
+                    || code == KeyCode::Char('J')
+                {
+                    if mods == KeyModifiers::SHIFT {
+                        input = InputState::ShiftDown;
+                    } else {
+                        input = InputState::Down;
+                    }
+                } else if code == KeyCode::Up
+                    || code == KeyCode::Char('k')
+                    || code == KeyCode::Char('K')
+                {
+                    if mods == KeyModifiers::SHIFT {
+                        input = InputState::ShiftUp;
+                    } else {
+                        input = InputState::Up;
+                    }
+                }
+                // other commands
+                else if code == KeyCode::Char('u') {
+                    input = InputState::Action;
+                } else if code == KeyCode::Char(' ') {
+                    input = InputState::Pickup;
+                } else if code == KeyCode::Char('?') {
+                    input = InputState::ToggleHelp;
+                } else if code == KeyCode::Char('f') {
+                    input = InputState::CycleSpeed;
+                } else if code == KeyCode::Char('m') {
+                    input = InputState::Mute;
+                } else if code == KeyCode::Char('z') {
+                    input = InputState::Undo;
+                } else if code == KeyCode::Char('c') {
+                    input = InputState::Craft;
+                } else if code == KeyCode::Char('x') {
+                    input = InputState::Hop;
+                } else if code == KeyCode::Tab {
+                    input = InputState::NextTarget;
+                } else {
+                    input = InputState::None;
+                }
+            }
+            // the left button walks and picks up, the right one uses, like 'u'
+            Event::Mouse(event) => match event.kind {
+                MouseEventKind::Down(MouseButton::Left) => {
+                    input = InputState::Click(event.column as i64, event.row as i64);
+                }
+                MouseEventKind::Down(MouseButton::Right) => input = InputState::Action,
+                _ => {}
+            },
+            Event::Paste(text) if typing => pasted.extend(ui::paste_chars(&text)),
+            Event::Paste(_) => {}
+            Event::Resize(_width, _height) => {
+                input = InputState::Clear;
+            }
+        }
+    }
+    Ok(input)
+}
+
+/// guess from the environment whether this terminal can draw the unicode half blocks sprites
+/// are made of. basic terminals and locales other than UTF-8 get the ASCII render mode, and the
+/// old windows console gets the legacy one.
+pub fn probe_render_mode() -> display::RenderMode {
+    if legacy_console() {
+        return display::RenderMode::Legacy;
+    }
+    let term = std::env::var("TERM").unwrap_or_default();
+    let basic = ["dumb", "vt100", "vt102", "vt220", "ansi"].contains(&term.as_str());
+    // the first locale variable that's set decides the character set, as in setlocale
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok().filter(|l| !l.is_empty()));
+    let utf8 = locale.is_none_or(|l| {
+        let l = l.to_lowercase();
+        l.contains("utf-8") || l.contains("utf8")
+    });
+    if basic || !utf8 {
+        display::RenderMode::Ascii
+    } else {
+        display::RenderMode::Blocks
+    }
+}
+
+/// whether this is the old windows console, with no truecolor and only its codepage's
+/// characters in its default fonts. newer terminals on windows announce themselves in the
+/// environment: windows terminal, terminals in editors, ConEmu, and mintty with TERM.
+fn legacy_console() -> bool {
+    let set = |var| std::env::var_os(var).is_some_and(|v| !v.is_empty());
+    cfg!(windows)
+        && !set("WT_SESSION")
+        && !set("TERM_PROGRAM")
+        && !set("TERM")
+        && std::env::var("ConEmuANSI").map_or(true, |v| v != "ON")
+}
+
+/// frames are written as escape sequences, so the windows console has to be switched into
+/// understanding them. consoles older than windows 10 can't be, and would only show garbage.
+#[cfg(windows)]
+fn check_escape_sequences() -> Result<(), AppError> {
+    if crossterm::ansi_support::supports_ansi() {
+        Ok(())
+    } else {
+        Err(AppError::Console)
+    }
+}
+
+#[cfg(not(windows))]
+fn check_escape_sequences() -> Result<(), AppError> {
+    Ok(())
+}
+
+/// initialize crossterm settings: raw mode, alternate screen, hidden cursor, bracketed paste,
+/// and mouse capture if 'mouse' is set. the alternate screen is entered with an escape sequence, which the windows
+/// console also follows once escape sequences are switched on.
+pub fn enter_terminal(mouse: bool) -> Result<(), AppError> {
+    check_escape_sequences()?;
+    terminal::enable_raw_mode()?;
+    let mut out = io::stdout();
+    out.queue(terminal::EnterAlternateScreen)?
+        .queue(cursor::Hide)?
+        .queue(cursor::SavePosition)?
+        .queue(event::EnableBracketedPaste)?;
+    if mouse {
+        out.queue(event::EnableMouseCapture)?;
+    }
+    out.queue(terminal::Clear(terminal::ClearType::All))?
+        .queue(style::Print(display::PUSH_TITLE))?
+        .flush()?;
+    Ok(())
+}
+
+/// undo everything enter_terminal did, given the same 'mouse'.
+pub fn leave_terminal(mouse: bool) -> Result<(), AppError> {
+    terminal::disable_raw_mode()?;
+    let mut out = io::stdout();
+    if mouse {
+        out.queue(event::DisableMouseCapture)?;
+    }
+    out.queue(event::DisableBracketedPaste)?
+        .queue(cursor::RestorePosition)?
+        .queue(cursor::Show)?
+        .queue(terminal::LeaveAlternateScreen)?
+        .queue(style::Print(display::POP_TITLE))?
+        .flush()?;
+    Ok(())
+}
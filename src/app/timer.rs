@@ -0,0 +1,117 @@
+use super::{savefile, state, AppError};
+use serde::{Deserialize, Serialize};
+use specs::{Read, System, Write};
+
+/// the player's best speedrun, kept in this file in the working directory.
+pub const PROFILE: &str = "profile.json";
+
+/// records kept between runs. every field is optional in the file.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    /// milliseconds of real time from the start to the last story message.
+    pub best_time: Option<u64>,
+    /// the best run's time at each story message.
+    pub best_splits: Vec<u64>,
+}
+
+impl Profile {
+    /// load the profile from 'path', starting a new one if the file doesn't exist. a file
+    /// that fails its checksum or can't be read as a profile is reported as damaged.
+    pub fn load(path: &str) -> Result<Profile, AppError> {
+        match savefile::read(path)? {
+            Some(bytes) => {
+                serde_json::from_slice(&bytes).map_err(|_| AppError::Damaged(path.to_string()))
+            }
+            None => Ok(Profile::default()),
+        }
+    }
+
+    /// save the profile to 'path', keeping the last few versions as backups.
+    pub fn save(&self, path: &str) -> Result<(), AppError> {
+        savefile::write(path, &serde_json::to_vec_pretty(self)?)
+    }
+}
+
+/// format milliseconds as m:ss.t
+pub fn format_time(ms: u64) -> String {
+    format!("{}:{:02}.{}", ms / 60_000, ms / 1000 % 60, ms / 100 % 10)
+}
+
+/// specs resource for the speedrun timer. only present when the timer is enabled.
+pub struct Timer {
+    /// real time at each story message reached so far.
+    pub splits: Vec<u64>,
+    /// the run's time, once the last story message arrives.
+    pub finished: Option<u64>,
+    pub profile: Profile,
+    /// whether a new best run is saved to the profile. off when checking someone's replay.
+    pub save: bool,
+}
+
+impl Timer {
+    pub fn new(profile: Profile) -> Timer {
+        Timer {
+            splits: vec![],
+            finished: None,
+            profile,
+            save: true,
+        }
+    }
+
+    /// the time so far, and the last split compared to the best run's.
+    pub fn status(&self, now: u64) -> String {
+        let time = format_time(self.finished.unwrap_or(now));
+        let Some((i, split)) = self.splits.iter().enumerate().next_back() else {
+            return time;
+        };
+        match self.profile.best_splits.get(i) {
+            Some(&best) if best <= *split => {
+                format!("{} | {} +{}", time, i + 1, format_time(split - best))
+            }
+            Some(&best) => format!("{} | {} -{}", time, i + 1, format_time(best - split)),
+            None => format!("{} | {} {}", time, i + 1, format_time(*split)),
+        }
+    }
+}
+
+/// takes a split whenever the story advances, and records the run in the profile when the last
+/// message arrives. does nothing unless the timer is enabled.
+pub struct TimerSystem;
+
+impl<'a> System<'a> for TimerSystem {
+    type SystemData = (
+        Option<Write<'a, Timer>>,
+        Read<'a, state::Game>,
+        Read<'a, state::RealTime>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (timer, game, time) = data;
+        let Some(mut timer) = timer else {
+            return;
+        };
+        if timer.finished.is_some() {
+            return;
+        }
+        while timer.splits.len() < game.history.len() {
+            timer.splits.push(time.0);
+        }
+        if !game.at_end() {
+            return;
+        }
+        timer.finished = Some(time.0);
+        if timer.profile.best_time.is_some_and(|best| best <= time.0) {
+            return;
+        }
+        timer.profile = Profile {
+            best_time: Some(time.0),
+            best_splits: timer.splits.clone(),
+        };
+        // a run that can't be recorded is still a finished run, so failures are ignored
+        #[cfg(not(target_arch = "wasm32"))]
+        if timer.save {
+            let _ = timer.profile.save(PROFILE);
+        }
+    }
+}
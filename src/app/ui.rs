@@ -0,0 +1,90 @@
+use super::InputState;
+
+/// what a text input's owner should do after a key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextEvent {
+    /// enter was pressed on this text, trimmed.
+    Submit(String),
+    /// escape was pressed.
+    Cancel,
+}
+
+/// a single line of typed text with a cursor, for text boxes like the reply box. it takes
+/// InputState as typed while a frontend is 'typing': characters, backspace, left and right,
+/// enter and escape.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TextInput {
+    text: String,
+    /// the cursor's position, in characters.
+    cursor: usize,
+    /// the most characters the text can hold.
+    max: usize,
+}
+
+impl TextInput {
+    pub fn new(max: usize) -> TextInput {
+        TextInput {
+            max,
+            ..TextInput::default()
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// the byte offset of the character at 'cursor'.
+    fn offset(&self, cursor: usize) -> usize {
+        self.text
+            .char_indices()
+            .nth(cursor)
+            .map_or(self.text.len(), |(i, _)| i)
+    }
+
+    /// type 'c' at the cursor, unless the text is full or it's a control character.
+    pub fn insert(&mut self, c: char) {
+        if c.is_control() || self.text.chars().count() >= self.max {
+            return;
+        }
+        self.text.insert(self.offset(self.cursor), c);
+        self.cursor += 1;
+    }
+
+    /// handle one typed input.
+    pub fn handle(&mut self, input: InputState) -> Option<TextEvent> {
+        match input {
+            InputState::Char(c) => self.insert(c),
+            InputState::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.text.remove(self.offset(self.cursor));
+            }
+            InputState::Left => self.cursor = self.cursor.saturating_sub(1),
+            InputState::Right => self.cursor = (self.cursor + 1).min(self.text.chars().count()),
+            InputState::Action => return Some(TextEvent::Submit(self.text.trim().to_string())),
+            InputState::Pickup => return Some(TextEvent::Cancel),
+            _ => {}
+        }
+        None
+    }
+
+    /// the text with the cursor marked: '_' at the end, or '|' between two characters.
+    pub fn line(&self) -> String {
+        let offset = self.offset(self.cursor);
+        let mark = if offset == self.text.len() { '_' } else { '|' };
+        format!("{}{}{}", &self.text[..offset], mark, &self.text[offset..])
+    }
+}
+
+/// the characters of pasted 'text' as they're typed into a single line: a trailing line break
+/// is dropped, other line breaks and tabs become spaces, and other control characters are left
+/// out.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn paste_chars(text: &str) -> impl Iterator<Item = char> + '_ {
+    text.trim_end_matches(['\r', '\n'])
+        .chars()
+        .filter_map(|c| match c {
+            '\n' | '\r' | '\t' => Some(' '),
+            c if c.is_control() => None,
+            c => Some(c),
+        })
+}
@@ -0,0 +1,193 @@
+use super::{pack, path, sprite, AppError};
+
+/// check one sheet, or every sheet in an atlas, returning every problem found rather than
+/// stopping at the first.
+fn validate_sheet(json_path: &str) -> Vec<String> {
+    let json_str = match std::fs::read_to_string(json_path) {
+        Ok(s) => s,
+        Err(e) => return vec![format!("cannot read json: {}", e)],
+    };
+    let value = match serde_json::from_str::<serde_json::Value>(&json_str) {
+        Ok(value) => value,
+        Err(e) => return vec![format!("cannot parse json: {}", e)],
+    };
+    if value.get("sheets").is_some() {
+        return match serde_json::from_value::<sprite::AtlasJSON>(value) {
+            Ok(atlas) => validate_atlas(json_path, &atlas),
+            Err(e) => vec![format!("cannot parse json: {}", e)],
+        };
+    }
+    let json = match serde_json::from_value::<sprite::SpriteSheetJSON>(value) {
+        Ok(json) => json,
+        Err(e) => return vec![format!("cannot parse json: {}", e)],
+    };
+
+    let mut problems: Vec<String> = vec![];
+    let image_size = image_size(json_path, &json.meta.image, &mut problems);
+    if let Some((w, h)) = image_size {
+        if (w, h) != (json.meta.size.w, json.meta.size.h) {
+            problems.push(format!(
+                "meta size {}x{} does not match image size {}x{}",
+                json.meta.size.w, json.meta.size.h, w, h
+            ));
+        }
+    }
+    problems.extend(validate_frames(
+        &json.frames,
+        &json.meta.palette_cycles,
+        &json.meta.frame_tags,
+        image_size,
+    ));
+    problems
+}
+
+/// check every sheet packed into an atlas: that it sits inside the atlas image, and its frames
+/// inside it. problems are prefixed with the sheet they're in.
+fn validate_atlas(json_path: &str, atlas: &sprite::AtlasJSON) -> Vec<String> {
+    let mut problems: Vec<String> = vec![];
+    let image_size = image_size(json_path, &atlas.meta.image, &mut problems);
+    if atlas.sheets.is_empty() {
+        problems.push("atlas has no sheets".to_string());
+    }
+    for sheet in atlas.sheets.iter() {
+        let rect = &sheet.rect;
+        if let Some((w, h)) = image_size {
+            if rect.x + rect.w > w || rect.y + rect.h > h {
+                problems.push(format!(
+                    "sheet {:?} rect {}x{} at ({}, {}) is outside the {}x{} image",
+                    sheet.name, rect.w, rect.h, rect.x, rect.y, w, h
+                ));
+            }
+        }
+        // frames keep the coordinates they had in their own sheet's image
+        problems.extend(
+            validate_frames(
+                &sheet.frames,
+                &sheet.palette_cycles,
+                &sheet.frame_tags,
+                image_size.map(|_| (rect.w, rect.h)),
+            )
+            .into_iter()
+            .map(|problem| format!("sheet {:?}: {}", sheet.name, problem)),
+        );
+    }
+    problems
+}
+
+/// the size of the image 'image' next to the json at 'json_path', or a problem if it can't be
+/// opened.
+fn image_size(json_path: &str, image: &str, problems: &mut Vec<String>) -> Option<(u32, u32)> {
+    let image_path = path::Path::new(json_path)
+        .parent()
+        .unwrap_or(path::Path::new(""))
+        .join(image);
+    match image::image_dimensions(&image_path) {
+        Ok(size) => Some(size),
+        Err(e) => {
+            problems.push(format!("cannot open image {:?}: {}", image_path, e));
+            None
+        }
+    }
+}
+
+/// check a sheet's frames, palette cycles and frame tags against its image of 'image_size'.
+fn validate_frames(
+    frames: &[sprite::SpriteSheetJSONFrame],
+    palette_cycles: &[sprite::PaletteCycle],
+    frame_tags: &[sprite::FrameTag],
+    image_size: Option<(u32, u32)>,
+) -> Vec<String> {
+    let mut problems: Vec<String> = vec![];
+
+    if frames.is_empty() {
+        problems.push("sheet has no frames".to_string());
+    }
+
+    for (i, cycle) in palette_cycles.iter().enumerate() {
+        if cycle.duration == 0 {
+            problems.push(format!("palette cycle {} has zero duration", i));
+        }
+        if cycle.colors.len() < 2 {
+            problems.push(format!("palette cycle {} has fewer than two colors", i));
+        }
+    }
+
+    for tag in frame_tags.iter() {
+        if tag.from > tag.to || tag.to >= frames.len() {
+            problems.push(format!(
+                "frame tag {:?} from {} to {} is outside the sheet's {} frames",
+                tag.name,
+                tag.from,
+                tag.to,
+                frames.len()
+            ));
+        }
+    }
+
+    for (i, frame) in frames.iter().enumerate() {
+        if frame.duration == 0 {
+            problems.push(format!("frame {} has zero duration", i));
+        }
+        // the half-block renderer draws two pixel rows per cell
+        if frame.source_size.h % 2 != 0 {
+            problems.push(format!(
+                "frame {} has odd height {}",
+                i, frame.source_size.h
+            ));
+        }
+        // a trimmed frame has to fit back inside its source size where it was cut from
+        let (trim, source) = (&frame.sprite_source_size, &frame.source_size);
+        if trim.x + frame.frame.w > source.w || trim.y + frame.frame.h > source.h {
+            problems.push(format!(
+                "frame {} trimmed to {}x{} at ({}, {}) is outside its {}x{} source size",
+                i, frame.frame.w, frame.frame.h, trim.x, trim.y, source.w, source.h
+            ));
+        }
+        if let Some((w, h)) = image_size {
+            // a rotated frame takes up its height across the image and its width down it
+            let rect = &frame.frame;
+            let (rw, rh) = if frame.rotated {
+                (rect.h, rect.w)
+            } else {
+                (rect.w, rect.h)
+            };
+            if rect.x + rw > w || rect.y + rh > h {
+                problems.push(format!(
+                    "frame {} rect {}x{} at ({}, {}) is outside the {}x{} image",
+                    i, rw, rh, rect.x, rect.y, w, h
+                ));
+            }
+        }
+    }
+
+    problems
+}
+
+/// check every sheet the game loads, the atlas if one has been packed, plus any extra sheets or
+/// atlases given on the command line: `qffp validate [sheet.json...]`.
+pub fn run(extra_paths: &[String]) -> Result<(), AppError> {
+    let mut paths: Vec<&str> = sprite::SHEETS.to_vec();
+    if path::Path::new(pack::ATLAS).is_file() {
+        paths.push(pack::ATLAS);
+    }
+    paths.extend(extra_paths.iter().map(|p| p.as_str()));
+
+    let mut total = 0;
+    for json_path in paths {
+        let problems = validate_sheet(json_path);
+        if problems.is_empty() {
+            println!("ok    {}", json_path);
+        } else {
+            println!("FAIL  {}", json_path);
+            for problem in problems.iter() {
+                println!("      - {}", problem);
+            }
+        }
+        total += problems.len();
+    }
+
+    if total > 0 {
+        return Err(AppError::Validation(total));
+    }
+    Ok(())
+}
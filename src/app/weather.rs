@@ -0,0 +1,159 @@
+use super::{sprite, state};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use specs::{Entities, Join, Read, ReadStorage, System, Write, WriteStorage};
+
+/// the sky over the farm for a day.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Sky {
+    #[default]
+    Clear,
+    Cloudy,
+    Rain,
+    /// a dry spell, lasting DROUGHT_DAYS.
+    Drought,
+    /// a lunar dust storm: hard to see through, and crops don't grow.
+    Dust,
+}
+
+/// how many days in a row a drought lasts once it sets in.
+pub const DROUGHT_DAYS: u32 = 3;
+
+impl Sky {
+    /// a day's sky, rolled at random: mostly clear, sometimes cloudy, now and then rain, and
+    /// once in a while the start of a drought or a dust storm.
+    pub fn roll(rng: &mut impl Rng) -> Sky {
+        match rng.gen_range(0..40) {
+            0..=21 => Sky::Clear,
+            22..=29 => Sky::Cloudy,
+            30..=37 => Sky::Rain,
+            38 => Sky::Drought,
+            _ => Sky::Dust,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Sky::Clear => "clear",
+            Sky::Cloudy => "cloudy",
+            Sky::Rain => "rain",
+            Sky::Drought => "drought",
+            Sky::Dust => "dust storm",
+        }
+    }
+}
+
+/// specs resource with today's weather and the forecast for tomorrow.
+#[derive(Debug, Default)]
+pub struct Weather {
+    pub today: Sky,
+    pub tomorrow: Sky,
+    /// the day the weather was last rolled for.
+    pub day: u32,
+    /// days of drought still to come after tomorrow.
+    pub dry_days: u32,
+}
+
+impl Weather {
+    /// the line the terminal's morning report ends with.
+    pub fn forecast(&self) -> String {
+        let mut text = format!(
+            "### Weather: {} today, {} tomorrow.",
+            self.today.name(),
+            self.tomorrow.name()
+        );
+        if self.today == Sky::Rain {
+            text.push_str("\nThe rain has watered every crop outside the dome.");
+        } else if self.today == Sky::Drought {
+            text.push_str("\nCrops outside the dome need watering twice today, or they wilt.");
+        } else if self.today == Sky::Dust {
+            text.push_str("\nDust blots out the sun. Nothing will grow tonight.");
+        } else if self.tomorrow == Sky::Dust {
+            text.push_str("\nA dust storm is coming. Stay close to your tools.");
+        } else if self.tomorrow == Sky::Drought {
+            text.push_str("\nA drought is coming. Keep the watering can close.");
+        }
+        text
+    }
+}
+
+/// rolls tomorrow's weather each morning, and posts the forecast at the terminal. on a rainy
+/// morning, every crop out under the sky gets watered; in a drought, they all go thirsty.
+pub struct WeatherSystem;
+
+impl<'a> System<'a> for WeatherSystem {
+    type SystemData = (
+        Entities<'a>,
+        Write<'a, Weather>,
+        Write<'a, state::Game>,
+        Write<'a, state::GameRng>,
+        Read<'a, state::Dome>,
+        Read<'a, sprite::SpriteStore>,
+        Read<'a, sprite::SpriteIds>,
+        ReadStorage<'a, state::Interactible>,
+        ReadStorage<'a, state::Position>,
+        WriteStorage<'a, state::Sprite>,
+        WriteStorage<'a, state::Thirsty>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            mut weather,
+            mut game,
+            mut rng,
+            dome,
+            store,
+            ids,
+            interactibles,
+            positions,
+            mut sprites,
+            mut thirsty,
+        ) = data;
+        if game.day == weather.day {
+            return;
+        }
+        weather.day = game.day;
+        weather.today = weather.tomorrow;
+        weather.tomorrow = if weather.dry_days > 0 {
+            weather.dry_days -= 1;
+            Sky::Drought
+        } else {
+            let sky = Sky::roll(&mut rng.0);
+            if sky == Sky::Drought {
+                weather.dry_days = DROUGHT_DAYS - 1;
+            }
+            sky
+        };
+        game.forecast = Some(weather.forecast());
+        game.dust_storm = weather.today == Sky::Dust;
+        if !matches!(weather.today, Sky::Rain | Sky::Drought) {
+            return;
+        }
+
+        let crop_empty = ids.crop_empty;
+        for (entity, item, pos, sprite) in
+            (&entities, &interactibles, &positions, &mut sprites).join()
+        {
+            // as with sprinklers, dry plots are left alone, and the dome keeps the weather off
+            let exposed = item.item_type == state::ItemType::Crop
+                && sprite.store_index != crop_empty
+                && pos.x >= dome.x;
+            if !exposed {
+                continue;
+            }
+            match weather.today {
+                Sky::Rain => {
+                    let info = &store[sprite.store_index];
+                    if let Some(watered) = info.retag(sprite.frame, "dry", "watered") {
+                        sprite.frame = watered;
+                    }
+                }
+                Sky::Drought => {
+                    let _ = thirsty.insert(entity, state::Thirsty);
+                }
+                _ => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use super::{keys, App, AppError, Frontend, IOWrite, InputState};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+// the browser has no entropy source without js bindings, so rand is seeded from the page
+static SEED: AtomicU64 = AtomicU64::new(0);
+
+fn page_getrandom(buf: &mut [u8]) -> Result<(), getrandom::Error> {
+    for chunk in buf.chunks_mut(8) {
+        // splitmix64
+        let mut z = SEED.fetch_add(0x9e3779b97f4a7c15, Ordering::Relaxed);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^= z >> 31;
+        chunk.copy_from_slice(&z.to_le_bytes()[..chunk.len()]);
+    }
+    Ok(())
+}
+
+getrandom::register_custom_getrandom!(page_getrandom);
+
+/// everything the game draws during a tick, collected for the page to read.
+#[derive(Clone, Default)]
+struct PageOutput(Arc<Mutex<Vec<u8>>>);
+
+impl IOWrite for PageOutput {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// an xterm.js terminal. the page pushes key bytes and sizes in, and the game reads them
+/// between ticks.
+struct PageTerminal {
+    keys: Vec<u8>,
+    pending: VecDeque<InputState>,
+    size: (u16, u16),
+}
+
+impl Frontend for PageTerminal {
+    fn read_input(&mut self, _ms: u64, typing: bool) -> Result<InputState, AppError> {
+        self.pending
+            .extend(keys::parse_keys(&self.keys, typing, Default::default()));
+        self.keys.clear();
+        Ok(self.pending.pop_front().unwrap_or(InputState::None))
+    }
+
+    fn size(&self) -> Result<(u16, u16), AppError> {
+        Ok(self.size)
+    }
+}
+
+struct Page {
+    app: App<'static>,
+    terminal: PageTerminal,
+    output: PageOutput,
+    // the last tick's output, kept alive for the page to copy out of wasm memory
+    frame: Vec<u8>,
+}
+
+thread_local! {
+    static PAGE: RefCell<Option<Page>> = const { RefCell::new(None) };
+}
+
+/// start a game on a 'cols' by 'rows' terminal. returns false if the game failed to load.
+#[no_mangle]
+pub extern "C" fn qffp_start(cols: u32, rows: u32, seed: u32) -> bool {
+    SEED.store(seed as u64, Ordering::Relaxed);
+    let size = (cols as u16, rows as u16);
+    let output = PageOutput::default();
+    let Ok(app) = App::with_output(size, Box::new(output.clone()), None) else {
+        return false;
+    };
+    PAGE.with(|page| {
+        *page.borrow_mut() = Some(Page {
+            app,
+            terminal: PageTerminal {
+                keys: vec![],
+                pending: VecDeque::new(),
+                size,
+            },
+            output,
+            frame: vec![],
+        })
+    });
+    true
+}
+
+/// queue one byte of terminal input, as sent by xterm.js's onData.
+#[no_mangle]
+pub extern "C" fn qffp_key(byte: u8) {
+    PAGE.with(|page| {
+        if let Some(page) = page.borrow_mut().as_mut() {
+            page.terminal.keys.push(byte);
+        }
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn qffp_resize(cols: u32, rows: u32) {
+    PAGE.with(|page| {
+        if let Some(page) = page.borrow_mut().as_mut() {
+            page.terminal.size = (cols as u16, rows as u16);
+            page.terminal.pending.push_back(InputState::Clear);
+        }
+    });
+}
+
+/// advance the game by 'dt' milliseconds. returns the length of the output to write to the
+/// terminal, which qffp_frame points to.
+#[no_mangle]
+pub extern "C" fn qffp_tick(dt: u32) -> u32 {
+    PAGE.with(|page| {
+        let mut page = page.borrow_mut();
+        let Some(page) = page.as_mut() else {
+            return 0;
+        };
+        // there is nothing to quit to in a browser tab. a key read between steps waits for
+        // the next one
+        if page.app.input == InputState::None {
+            match page.app.process_input(&mut page.terminal, 0) {
+                Ok(InputState::Quit) => page.app.set_input(InputState::None),
+                Ok(_) => {}
+                Err(_) => return 0,
+            }
+        }
+        if page.app.advance(dt as u64).is_err() {
+            return 0;
+        }
+        page.frame = std::mem::take(&mut *page.output.0.lock().unwrap());
+        page.frame.len() as u32
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn qffp_frame() -> *const u8 {
+    PAGE.with(|page| {
+        page.borrow()
+            .as_ref()
+            .map_or(std::ptr::null(), |page| page.frame.as_ptr())
+    })
+}
@@ -0,0 +1,4 @@
+pub mod app;
+
+/// the entity component system the game's world is built on, for working with App::world.
+pub use specs;
@@ -1,15 +1,128 @@
-mod app;
+use qffp::app;
 
 fn main() -> Result<(), app::AppError> {
-    let mut app = app::App::new()?;
-    'main: loop {
-        if app.process_input(50)? == app::InputState::Quit {
-            break 'main;
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    // options for playing, accepted anywhere on the command line
+    let bell = args.iter().any(|a| a == "--bell");
+    let daily = args.iter().any(|a| a == "--daily");
+    let timer = args.iter().any(|a| a == "--timer");
+    let ascii = args.iter().any(|a| a == "--ascii");
+    let emoji = args.iter().any(|a| a == "--emoji");
+    let mouse = !args.iter().any(|a| a == "--no-mouse");
+    // '--record <replay.json>' saves the session for 'qffp cast'
+    let record = match args.iter().position(|a| a == "--record") {
+        Some(i) if i + 1 < args.len() => Some(args.drain(i..i + 2).nth(1).unwrap_or_default()),
+        Some(_) => return Err(app::AppError::Usage("--record <replay.json>".to_string())),
+        None => None,
+    };
+    // '--seed <number>' lays the farm out from a known seed, to reproduce it
+    let seed_arg = match args.iter().position(|a| a == "--seed") {
+        Some(i) if i + 1 < args.len() => {
+            match args.drain(i..i + 2).nth(1).unwrap_or_default().parse() {
+                Ok(seed) => Some(seed),
+                Err(_) => return Err(app::AppError::Usage("--seed <number>".to_string())),
+            }
+        }
+        Some(_) => return Err(app::AppError::Usage("--seed <number>".to_string())),
+        None => None,
+    };
+    // '--key <key>' is the shared secret a co-op guest needs to join the host
+    let key = match args.iter().position(|a| a == "--key") {
+        Some(i) if i + 1 < args.len() => args.drain(i..i + 2).nth(1).unwrap_or_default(),
+        Some(_) => return Err(app::AppError::Usage("--key <key>".to_string())),
+        None => String::new(),
+    };
+    args.retain(|a| {
+        a != "--bell"
+            && a != "--daily"
+            && a != "--timer"
+            && a != "--ascii"
+            && a != "--emoji"
+            && a != "--no-mouse"
+    });
+    let mode = if ascii {
+        app::display::RenderMode::Ascii
+    } else if emoji {
+        app::display::RenderMode::Emoji
+    } else {
+        app::terminal::probe_render_mode()
+    };
+
+    let mut host_addr: Option<String> = None;
+    if let Some(command) = args.first() {
+        match command.as_str() {
+            "host" => {
+                host_addr = Some(
+                    args.get(1)
+                        .cloned()
+                        .unwrap_or(format!("127.0.0.1:{}", app::net::DEFAULT_PORT)),
+                );
+            }
+            "join" => {
+                let addr = args.get(1).ok_or(app::AppError::Usage(
+                    "qffp join <host[:port]> [--key <key>]".to_string(),
+                ))?;
+                return app::net::join(addr, &key, mode, mouse);
+            }
+            "preview" => {
+                let path = args.get(1).ok_or(app::AppError::Usage(
+                    "qffp preview <sheet.json>".to_string(),
+                ))?;
+                return app::preview::run(path);
+            }
+            #[cfg(feature = "ssh")]
+            "ssh" => return app::ssh::run(&args[1..]),
+            "telnet" => return app::telnet::run(&args[1..]),
+            "export" => return app::export::run(&args[1..]),
+            "map" => return app::map::run(&args[1..]),
+            "cast" => return app::replay::run_cast(&args[1..]),
+            "verify" => return app::replay::run_verify(&args[1..]),
+            "pack" => return app::pack::run(&args[1..]),
+            "validate" => return app::validate::run(&args[1..]),
+            _ => {
+                return Err(app::AppError::Usage(format!(
+                    "unknown command '{}'",
+                    command
+                )))
+            }
         }
-        app.update()?;
-        std::thread::sleep(std::time::Duration::from_millis(20));
     }
 
-    app.exit()?;
-    Ok(())
+    let date = app::challenge::today();
+    // a recording needs to know its seed to be played back
+    let seed = if daily {
+        Some(app::challenge::seed(date))
+    } else if seed_arg.is_some() {
+        seed_arg
+    } else {
+        record.is_some().then(rand::random)
+    };
+    let mut app = app::App::new(seed, mouse)?;
+    if let Some(seed) = seed.filter(|_| record.is_some()) {
+        app.record(seed);
+    }
+    app.set_render_mode(mode);
+    if bell {
+        app.enable_bell();
+    }
+    if daily {
+        app.enable_challenge(date);
+    }
+    if timer {
+        app.enable_timer()?;
+    }
+    if let Some(addr) = host_addr {
+        app.host(&addr, &key)?;
+    }
+    let result = app.run(&mut app::terminal::LocalTerminal::new(app.layout())?);
+    // after a hangup there may be no terminal left to put back, but the farm and the recording
+    // still save. daily, recorded and --seed games are seeded, and leave the save alone
+    let exited = app.exit();
+    let saved = if seed.is_none() { app.save() } else { Ok(()) };
+    if let (Some(path), Some(replay)) = (record, app.take_recording()) {
+        replay.save(&path)?;
+    }
+    exited?;
+    saved?;
+    result
 }
@@ -0,0 +1,39 @@
+// drives the game's wasm module from an xterm.js terminal. see "Playing in a browser" in the
+// README for how to build qffp.wasm.
+const term = new Terminal({ cursorBlink: false });
+const fit = new FitAddon.FitAddon();
+term.loadAddon(fit);
+term.open(document.getElementById('terminal'));
+fit.fit();
+term.focus();
+
+WebAssembly.instantiateStreaming(fetch('qffp.wasm'), {}).then(({ instance }) => {
+  const game = instance.exports;
+  const seed = Math.floor(Math.random() * 0xffffffff);
+  if (!game.qffp_start(term.cols, term.rows, seed)) {
+    term.write('failed to load the game\r\n');
+    return;
+  }
+
+  const encoder = new TextEncoder();
+  const decoder = new TextDecoder();
+  term.onData((data) => {
+    for (const byte of encoder.encode(data)) {
+      game.qffp_key(byte);
+    }
+  });
+  term.onTitleChange((title) => (document.title = title));
+  term.onResize(({ cols, rows }) => game.qffp_resize(cols, rows));
+  window.addEventListener('resize', () => fit.fit());
+
+  let last = performance.now();
+  const frame = (now) => {
+    const len = game.qffp_tick(Math.round(now - last));
+    last = now;
+    if (len > 0) {
+      term.write(new Uint8Array(game.memory.buffer, game.qffp_frame(), len).slice());
+    }
+    requestAnimationFrame(frame);
+  };
+  requestAnimationFrame(frame);
+});